@@ -0,0 +1,69 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Serializer throughput on 1 MB byte payloads
+//!
+//! Compares a `Vec<u8>`, whose bytes `collect_seq` packs a word at a time, with
+//! the same bytes serialized element by element and with `serialize_bytes`.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use l2r0_small_serde::{to_vec_compact, to_vec_into};
+use serde::{ser::SerializeSeq, Serialize, Serializer};
+
+const LEN: usize = 1 << 20;
+
+/// Serializes the bytes one `serialize_element` at a time
+struct ElementWise<'a>(&'a [u8]);
+
+impl Serialize for ElementWise<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(self.0.len()))?;
+        for byte in self.0 {
+            seq.serialize_element(byte)?;
+        }
+        seq.end()
+    }
+}
+
+/// Serializes the bytes with `serialize_bytes`
+struct Bytes<'a>(&'a [u8]);
+
+impl Serialize for Bytes<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(self.0)
+    }
+}
+
+fn bench_bytes(c: &mut Criterion) {
+    let bytes: Vec<u8> = (0..LEN).map(|i| (i * 31) as u8).collect();
+    let mut group = c.benchmark_group("bytes_1mb");
+    group.throughput(Throughput::Bytes(LEN as u64));
+    let mut out = Vec::with_capacity(LEN / 4 + 1);
+    group.bench_function("collect_seq", |b| {
+        b.iter(|| to_vec_into(black_box(&bytes), &mut out).unwrap())
+    });
+    group.bench_function("element_wise", |b| {
+        b.iter(|| to_vec_into(&ElementWise(black_box(&bytes)), &mut out).unwrap())
+    });
+    group.bench_function("serialize_bytes", |b| {
+        b.iter(|| to_vec_into(&Bytes(black_box(&bytes)), &mut out).unwrap())
+    });
+    group.bench_function("collect_seq_alloc", |b| {
+        b.iter(|| to_vec_compact(black_box(&bytes)).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_bytes);
+criterion_main!(benches);
@@ -0,0 +1,564 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Derive macros for `l2r0-small-serde`
+//!
+//! These macros are re-exported by `l2r0-small-serde` when its `derive`
+//! feature is enabled and should not be used from this crate directly.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::{
+    parse_macro_input, parse_quote, Data, DeriveInput, Fields, GenericArgument, Generics, Member,
+    PathArguments, Type,
+};
+
+/// Derive `serde::Serialize` and `serde::Deserialize` with word-format
+/// specific layout options.
+///
+/// Without options, the generated impls encode the fields in declaration
+/// order, exactly like `#[derive(Serialize, Deserialize)]` would. The layout
+/// can be changed with container attributes:
+///
+/// - `#[word_serde(option_bitmap)]` collects the presence flags of all
+///   `Option` fields into leading bitmap words (one bit per field, 32 fields
+///   per word). Present values are then encoded without their tag word, and
+///   absent values take no space at all.
+/// - `#[word_serde(pack_fields)]` encodes the `u8` and `bool` fields first,
+///   in declaration order, followed by the other fields, so that the small
+///   fields share words instead of each being flushed by the next wider
+///   field. With `option_bitmap`, fields of type `Option<u8>` or
+///   `Option<bool>` count as small too. Decoding reads the fields back in the
+///   same order, so the struct itself is unchanged.
+///
+/// ```ignore
+/// use l2r0_small_serde::WordSerde;
+///
+/// #[derive(WordSerde)]
+/// #[word_serde(option_bitmap)]
+/// struct Sparse {
+///     id: u32,
+///     a: Option<u32>,
+///     b: Option<u64>,
+/// }
+///
+/// // Takes two words rather than four.
+/// #[derive(WordSerde)]
+/// #[word_serde(pack_fields)]
+/// struct Flags {
+///     ready: bool,
+///     height: u32,
+///     kind: u8,
+///     done: bool,
+/// }
+/// ```
+///
+/// Field-level `#[serde(...)]` attributes are not interpreted.
+#[proc_macro_derive(WordSerde, attributes(word_serde))]
+pub fn derive_word_serde(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Derive `l2r0_small_serde::WordSerialize`, which encodes a struct without
+/// serde dispatch.
+///
+/// The output is the same as that of `#[derive(Serialize)]` without
+/// attributes. Every field must implement `WordSerialize`.
+#[proc_macro_derive(WordSerialize)]
+pub fn derive_word_serialize(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_word_serialize(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Derive `l2r0_small_serde::WordDeserialize`, which decodes a struct without
+/// serde dispatch.
+///
+/// Reads the output of `#[derive(Serialize)]` without attributes. Every field
+/// must implement `WordDeserialize`.
+#[proc_macro_derive(WordDeserialize)]
+pub fn derive_word_deserialize(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_word_deserialize(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Derive `l2r0_small_serde::WordSized`, which bounds the encoded size of a
+/// struct or enum.
+///
+/// The bound is that of the encoding produced by `#[derive(Serialize)]`
+/// without attributes. Every field must implement `WordSized`.
+#[proc_macro_derive(WordSized)]
+pub fn derive_word_sized(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_word_sized(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+#[derive(Default)]
+struct Options {
+    option_bitmap: bool,
+    pack_fields: bool,
+}
+
+impl Options {
+    fn from_input(input: &DeriveInput) -> syn::Result<Self> {
+        let mut options = Options::default();
+        for attr in input
+            .attrs
+            .iter()
+            .filter(|a| a.path().is_ident("word_serde"))
+        {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("option_bitmap") {
+                    options.option_bitmap = true;
+                    Ok(())
+                } else if meta.path.is_ident("pack_fields") {
+                    options.pack_fields = true;
+                    Ok(())
+                } else {
+                    Err(meta.error("unknown word_serde option"))
+                }
+            })?;
+        }
+        Ok(options)
+    }
+}
+
+struct Field {
+    member: Member,
+    name: String,
+    ty: Type,
+    /// `Some(T)` if the field is an `Option<T>` stored in the bitmap
+    bitmap_inner: Option<Type>,
+}
+
+fn option_inner(ty: &Type) -> Option<Type> {
+    let Type::Path(path) = ty else {
+        return None;
+    };
+    let segment = path.path.segments.last()?;
+    if segment.ident != "Option" {
+        return None;
+    }
+    let PathArguments::AngleBracketed(args) = &segment.arguments else {
+        return None;
+    };
+    match args.args.first() {
+        Some(GenericArgument::Type(inner)) if args.args.len() == 1 => Some(inner.clone()),
+        _ => None,
+    }
+}
+
+/// Whether a value of type `ty` is a single byte, which packs with
+/// neighbouring bytes
+fn is_byte(ty: &Type) -> bool {
+    match ty {
+        Type::Path(path) if path.qself.is_none() => {
+            path.path.is_ident("u8") || path.path.is_ident("bool")
+        }
+        _ => false,
+    }
+}
+
+fn collect_fields(input: &DeriveInput, options: &Options) -> syn::Result<Vec<Field>> {
+    let Data::Struct(data) = &input.data else {
+        return Err(syn::Error::new_spanned(
+            &input.ident,
+            "WordSerde can only be derived for structs",
+        ));
+    };
+    let fields = match &data.fields {
+        Fields::Named(fields) => &fields.named,
+        Fields::Unnamed(fields) => &fields.unnamed,
+        Fields::Unit => {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "WordSerde cannot be derived for unit structs",
+            ))
+        }
+    };
+    let mut fields: Vec<Field> = fields
+        .iter()
+        .enumerate()
+        .map(|(i, field)| {
+            let member = match &field.ident {
+                Some(ident) => Member::Named(ident.clone()),
+                None => Member::Unnamed(i.into()),
+            };
+            let name = match &field.ident {
+                Some(ident) => ident.to_string().trim_start_matches("r#").to_string(),
+                None => i.to_string(),
+            };
+            let bitmap_inner = if options.option_bitmap {
+                option_inner(&field.ty)
+            } else {
+                None
+            };
+            Field {
+                member,
+                name,
+                ty: field.ty.clone(),
+                bitmap_inner,
+            }
+        })
+        .collect();
+    if options.pack_fields {
+        // The sort is stable, so fields keep their order within each group.
+        fields.sort_by_key(|field| !is_byte(field.bitmap_inner.as_ref().unwrap_or(&field.ty)));
+    }
+    Ok(fields)
+}
+
+fn add_bounds(generics: &Generics, bound: TokenStream2) -> Generics {
+    let mut generics = generics.clone();
+    let params: Vec<_> = generics.type_params().map(|p| p.ident.clone()).collect();
+    let where_clause = generics.make_where_clause();
+    for param in params {
+        where_clause.predicates.push(parse_quote!(#param: #bound));
+    }
+    generics
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let options = Options::from_input(&input)?;
+    let fields = collect_fields(&input, &options)?;
+    let serialize = expand_serialize(&input, &fields);
+    let deserialize = expand_deserialize(&input, &fields);
+    Ok(quote! {
+        #serialize
+        #deserialize
+    })
+}
+
+fn bitmap_words(fields: &[Field]) -> usize {
+    fields
+        .iter()
+        .filter(|f| f.bitmap_inner.is_some())
+        .count()
+        .div_ceil(32)
+}
+
+fn expand_serialize(input: &DeriveInput, fields: &[Field]) -> TokenStream2 {
+    let serde = quote!(::l2r0_small_serde::__private::serde);
+    let ident = &input.ident;
+    let name = ident.to_string();
+    let generics = add_bounds(&input.generics, quote!(#serde::Serialize));
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let words = bitmap_words(fields);
+    let mut bitmap = TokenStream2::new();
+    let mut body = TokenStream2::new();
+    let mut bit = 0usize;
+    if words > 0 {
+        bitmap.extend(quote! {
+            let mut __bitmap = [0u32; #words];
+        });
+        body.extend(quote! {
+            #serde::ser::SerializeStruct::serialize_field(
+                &mut __state, "__option_bitmap", &__bitmap)?;
+        });
+    }
+    for field in fields {
+        let member = &field.member;
+        let field_name = &field.name;
+        if field.bitmap_inner.is_some() {
+            let (word, shift) = (bit / 32, bit % 32);
+            bit += 1;
+            bitmap.extend(quote! {
+                if self.#member.is_some() {
+                    __bitmap[#word] |= 1u32 << #shift;
+                    __len += 1;
+                }
+            });
+            body.extend(quote! {
+                match &self.#member {
+                    Some(__value) => #serde::ser::SerializeStruct::serialize_field(
+                        &mut __state, #field_name, __value)?,
+                    None => #serde::ser::SerializeStruct::skip_field(&mut __state, #field_name)?,
+                }
+            });
+        } else {
+            bitmap.extend(quote! {
+                __len += 1;
+            });
+            body.extend(quote! {
+                #serde::ser::SerializeStruct::serialize_field(
+                    &mut __state, #field_name, &self.#member)?;
+            });
+        }
+    }
+    let initial_len = usize::from(words > 0);
+
+    quote! {
+        impl #impl_generics #serde::Serialize for #ident #ty_generics #where_clause {
+            fn serialize<__S>(&self, __serializer: __S) -> ::core::result::Result<__S::Ok, __S::Error>
+            where
+                __S: #serde::Serializer,
+            {
+                let mut __len = #initial_len;
+                #bitmap
+                let mut __state = #serde::Serializer::serialize_struct(__serializer, #name, __len)?;
+                #body
+                #serde::ser::SerializeStruct::end(__state)
+            }
+        }
+    }
+}
+
+fn expand_deserialize(input: &DeriveInput, fields: &[Field]) -> TokenStream2 {
+    let serde = quote!(::l2r0_small_serde::__private::serde);
+    let ident = &input.ident;
+    let name = ident.to_string();
+    let expecting = format!("struct {}", name);
+    let (_, ty_generics, _) = input.generics.split_for_impl();
+    let mut generics = add_bounds(&input.generics, quote!(#serde::Deserialize<'de>));
+    generics.params.insert(0, parse_quote!('de));
+    let (impl_generics, visitor_generics, where_clause) = generics.split_for_impl();
+
+    let words = bitmap_words(fields);
+    let mut field_names = Vec::new();
+    let mut body = TokenStream2::new();
+    let mut index = 0usize;
+    if words > 0 {
+        field_names.push("__option_bitmap".to_string());
+        body.extend(quote! {
+            let __bitmap: [u32; #words] = match #serde::de::SeqAccess::next_element(&mut __seq)? {
+                Some(__value) => __value,
+                None => return Err(#serde::de::Error::invalid_length(#index, &self)),
+            };
+        });
+        index += 1;
+    }
+    let mut bit = 0usize;
+    let mut members = Vec::new();
+    let mut locals = Vec::new();
+    for (i, field) in fields.iter().enumerate() {
+        let local = format_ident!("__field{}", i);
+        field_names.push(field.name.clone());
+        members.push(field.member.clone());
+        locals.push(local.clone());
+        match &field.bitmap_inner {
+            Some(inner) => {
+                let (word, shift) = (bit / 32, bit % 32);
+                bit += 1;
+                body.extend(quote! {
+                    let #local = if (__bitmap[#word] >> #shift) & 1 == 1 {
+                        match #serde::de::SeqAccess::next_element::<#inner>(&mut __seq)? {
+                            Some(__value) => Some(__value),
+                            None => return Err(#serde::de::Error::invalid_length(#index, &self)),
+                        }
+                    } else {
+                        None
+                    };
+                });
+            }
+            None => {
+                let ty = &field.ty;
+                body.extend(quote! {
+                    let #local = match #serde::de::SeqAccess::next_element::<#ty>(&mut __seq)? {
+                        Some(__value) => __value,
+                        None => return Err(#serde::de::Error::invalid_length(#index, &self)),
+                    };
+                });
+            }
+        }
+        index += 1;
+    }
+
+    quote! {
+        impl #impl_generics #serde::Deserialize<'de> for #ident #ty_generics #where_clause {
+            fn deserialize<__D>(__deserializer: __D) -> ::core::result::Result<Self, __D::Error>
+            where
+                __D: #serde::Deserializer<'de>,
+            {
+                struct __Visitor #impl_generics #where_clause {
+                    marker: ::core::marker::PhantomData<#ident #ty_generics>,
+                    lifetime: ::core::marker::PhantomData<&'de ()>,
+                }
+
+                impl #impl_generics #serde::de::Visitor<'de> for __Visitor #visitor_generics #where_clause {
+                    type Value = #ident #ty_generics;
+
+                    fn expecting(&self, __formatter: &mut ::core::fmt::Formatter) -> ::core::fmt::Result {
+                        __formatter.write_str(#expecting)
+                    }
+
+                    fn visit_seq<__A>(self, mut __seq: __A) -> ::core::result::Result<Self::Value, __A::Error>
+                    where
+                        __A: #serde::de::SeqAccess<'de>,
+                    {
+                        #body
+                        Ok(#ident { #(#members: #locals),* })
+                    }
+                }
+
+                const FIELDS: &[&str] = &[#(#field_names),*];
+                #serde::Deserializer::deserialize_struct(
+                    __deserializer,
+                    #name,
+                    FIELDS,
+                    __Visitor {
+                        marker: ::core::marker::PhantomData,
+                        lifetime: ::core::marker::PhantomData,
+                    },
+                )
+            }
+        }
+    }
+}
+
+fn struct_fields<'a>(input: &'a DeriveInput, derive: &str) -> syn::Result<&'a Fields> {
+    match &input.data {
+        Data::Struct(data) => Ok(&data.fields),
+        _ => Err(syn::Error::new_spanned(
+            &input.ident,
+            format!("{} can only be derived for structs", derive),
+        )),
+    }
+}
+
+fn expand_word_serialize(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let krate = quote!(::l2r0_small_serde);
+    let fields = struct_fields(&input, "WordSerialize")?;
+    let ident = &input.ident;
+    let generics = add_bounds(&input.generics, quote!(#krate::direct::WordSerialize));
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let members: Vec<Member> = fields.members().collect();
+    let names = fields.iter().map(|field| match &field.ident {
+        Some(ident) => ident.to_string().trim_start_matches("r#").to_string(),
+        None => String::new(),
+    });
+    let len = members.len();
+    // Mirror the serde data model calls made by `#[derive(Serialize)]`.
+    let body = match fields {
+        Fields::Named(_) => quote! {
+            __serializer.direct_struct(#len, |__serializer| {
+                #(__serializer.direct_field(#names, &self.#members)?;)*
+                Ok(())
+            })
+        },
+        Fields::Unnamed(_) if len == 1 => quote! {
+            #krate::direct::WordSerialize::word_serialize(&self.0, __serializer)
+        },
+        Fields::Unnamed(_) => quote! {
+            __serializer.direct_tuple(#len, |__serializer| {
+                #(#krate::direct::WordSerialize::word_serialize(&self.#members, __serializer)?;)*
+                Ok(())
+            })
+        },
+        Fields::Unit => quote! {
+            #krate::direct::WordSerialize::word_serialize(&(), __serializer)
+        },
+    };
+
+    Ok(quote! {
+        impl #impl_generics #krate::direct::WordSerialize for #ident #ty_generics #where_clause {
+            fn word_serialize<__W>(
+                &self,
+                __serializer: &mut #krate::Serializer<__W>,
+            ) -> #krate::Result<()>
+            where
+                __W: #krate::__private::WordWrite,
+            {
+                #body
+            }
+        }
+    })
+}
+
+fn expand_word_deserialize(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let krate = quote!(::l2r0_small_serde);
+    let fields = struct_fields(&input, "WordDeserialize")?;
+    let ident = &input.ident;
+    let generics = add_bounds(&input.generics, quote!(#krate::direct::WordDeserialize));
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let members: Vec<Member> = fields.members().collect();
+    let len = members.len();
+    let read = quote!(#krate::direct::WordDeserialize::word_deserialize(__deserializer)?);
+    let body = match fields {
+        Fields::Named(_) => quote! {
+            __deserializer.direct_struct(#len, |__deserializer| {
+                Ok(#ident { #(#members: __deserializer.direct_field()?),* })
+            })
+        },
+        Fields::Unnamed(_) if len == 1 => quote! {
+            Ok(#ident(#read))
+        },
+        Fields::Unnamed(_) => {
+            let reads = members.iter().map(|_| &read);
+            quote! {
+                __deserializer.direct_tuple(|__deserializer| Ok(#ident(#(#reads),*)))
+            }
+        }
+        Fields::Unit => quote! {
+            <() as #krate::direct::WordDeserialize>::word_deserialize(__deserializer)?;
+            Ok(#ident)
+        },
+    };
+
+    Ok(quote! {
+        impl #impl_generics #krate::direct::WordDeserialize for #ident #ty_generics #where_clause {
+            fn word_deserialize<'__de, __R>(
+                __deserializer: &mut #krate::Deserializer<'__de, __R>,
+            ) -> #krate::Result<Self>
+            where
+                __R: #krate::__private::WordRead + '__de,
+            {
+                #body
+            }
+        }
+    })
+}
+
+fn expand_word_sized(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let krate = quote!(::l2r0_small_serde);
+    let ident = &input.ident;
+    let generics = add_bounds(&input.generics, quote!(#krate::sized::WordSized));
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let sum = |fields: &Fields| {
+        let types = fields.iter().map(|f| &f.ty);
+        quote!(0 #(+ <#types as #krate::sized::WordSized>::MAX_WORDS)*)
+    };
+    let max_words = match &input.data {
+        Data::Struct(data) => sum(&data.fields),
+        // The variant tag takes at most a word, whichever way it's encoded.
+        Data::Enum(data) => data.variants.iter().fold(quote!(0), |acc, variant| {
+            let fields = sum(&variant.fields);
+            quote!(#krate::sized::max(#acc, 1 + #fields))
+        }),
+        Data::Union(_) => {
+            return Err(syn::Error::new_spanned(
+                ident,
+                "WordSized cannot be derived for unions",
+            ))
+        }
+    };
+
+    Ok(quote! {
+        impl #impl_generics #krate::sized::WordSized for #ident #ty_generics #where_clause {
+            const MAX_WORDS: usize = #max_words;
+        }
+    })
+}
@@ -0,0 +1,127 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! `chrono` timestamps as seconds and nanoseconds
+//!
+//! The `chrono` implementations serialize timestamps as strings, which the
+//! compact serializer doesn't support. The adapters here write a timestamp as
+//! an `i64` of seconds since the Unix epoch followed by a `u32` of
+//! nanoseconds, 3 words in total.
+//! ```rust
+//! use chrono::{DateTime, NaiveDateTime, Utc};
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct Block {
+//!     #[serde(with = "l2r0_small_serde::adapters::chrono::datetime")]
+//!     produced: DateTime<Utc>,
+//!     #[serde(with = "l2r0_small_serde::adapters::chrono::naive_datetime")]
+//!     scheduled: NaiveDateTime,
+//! }
+//! ```
+
+use chrono::{DateTime, Utc};
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize, Serializer};
+
+fn serialize_utc<S: Serializer>(value: &DateTime<Utc>, serializer: S) -> Result<S::Ok, S::Error> {
+    (value.timestamp(), value.timestamp_subsec_nanos()).serialize(serializer)
+}
+
+fn deserialize_utc<'de, D: Deserializer<'de>>(deserializer: D) -> Result<DateTime<Utc>, D::Error> {
+    let (secs, nanos) = <(i64, u32)>::deserialize(deserializer)?;
+    DateTime::from_timestamp(secs, nanos)
+        .ok_or_else(|| D::Error::custom("chrono: timestamp out of range"))
+}
+
+/// `DateTime<Utc>` as seconds and nanoseconds since the Unix epoch
+pub mod datetime {
+    use chrono::{DateTime, Utc};
+    use serde::{Deserializer, Serializer};
+
+    /// Serialize `value` as 3 words
+    pub fn serialize<S: Serializer>(
+        value: &DateTime<Utc>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        super::serialize_utc(value, serializer)
+    }
+
+    /// Deserialize a `DateTime<Utc>` from 3 words
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<DateTime<Utc>, D::Error> {
+        super::deserialize_utc(deserializer)
+    }
+}
+
+/// `NaiveDateTime` as seconds and nanoseconds since the Unix epoch
+///
+/// The value is interpreted as UTC.
+pub mod naive_datetime {
+    use chrono::NaiveDateTime;
+    use serde::{Deserializer, Serializer};
+
+    /// Serialize `value` as 3 words
+    pub fn serialize<S: Serializer>(
+        value: &NaiveDateTime,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        super::serialize_utc(&value.and_utc(), serializer)
+    }
+
+    /// Deserialize a `NaiveDateTime` from 3 words
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<NaiveDateTime, D::Error> {
+        super::deserialize_utc(deserializer).map(|value| value.naive_utc())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, NaiveDateTime, Utc};
+    use serde::{Deserialize, Serialize};
+
+    use crate::{from_slice_compact, to_vec_compact};
+
+    #[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+    struct Block {
+        #[serde(with = "crate::adapters::chrono::datetime")]
+        produced: DateTime<Utc>,
+        #[serde(with = "crate::adapters::chrono::naive_datetime")]
+        scheduled: NaiveDateTime,
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let produced = DateTime::from_timestamp(1_700_000_000, 123_456_789).unwrap();
+        let input = Block {
+            produced,
+            scheduled: DateTime::from_timestamp(-1, 5).unwrap().naive_utc(),
+        };
+        let data = to_vec_compact(&input).unwrap();
+        assert_eq!(
+            [1_700_000_000, 0, 123_456_789, 0xffffffff, 0xffffffff, 5].as_slice(),
+            data
+        );
+        let output: Block = from_slice_compact(&data).unwrap();
+        assert_eq!(input, output);
+    }
+
+    #[test]
+    fn test_out_of_range() {
+        let data = [0u32, 0, 2_000_000_000, 0, 0, 0];
+        assert!(from_slice_compact::<Block, _>(&data).is_err());
+    }
+}
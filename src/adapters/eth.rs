@@ -0,0 +1,146 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Ethereum primitives as fixed runs of words
+//!
+//! The `primitive-types` implementations encode these types as
+//! length-prefixed byte sequences or as strings. The adapters here write them
+//! as a fixed number of words instead, with no length prefix.
+//! ```rust
+//! use primitive_types::{H160, H256, U256};
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct Transfer {
+//!     #[serde(with = "l2r0_small_serde::adapters::eth::address")]
+//!     to: H160,
+//!     #[serde(with = "l2r0_small_serde::adapters::eth::u256")]
+//!     value: U256,
+//!     #[serde(with = "l2r0_small_serde::adapters::eth::h256")]
+//!     tx_hash: H256,
+//! }
+//! ```
+
+fn bytes_to_words<const N: usize>(bytes: &[u8]) -> [u32; N] {
+    core::array::from_fn(|i| u32::from_le_bytes(bytes[i * 4..i * 4 + 4].try_into().unwrap()))
+}
+
+fn words_to_bytes<const N: usize>(words: &[u32]) -> [u8; N] {
+    core::array::from_fn(|i| words[i / 4].to_le_bytes()[i % 4])
+}
+
+/// `U256` as 8 words, least significant word first
+pub mod u256 {
+    use primitive_types::U256;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    /// Serialize `value` as 8 words
+    pub fn serialize<S: Serializer>(value: &U256, serializer: S) -> Result<S::Ok, S::Error> {
+        let words: [u32; 8] = core::array::from_fn(|i| (value.0[i / 2] >> (32 * (i % 2))) as u32);
+        words.serialize(serializer)
+    }
+
+    /// Deserialize a `U256` from 8 words
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<U256, D::Error> {
+        let words = <[u32; 8]>::deserialize(deserializer)?;
+        Ok(U256(core::array::from_fn(|i| {
+            words[2 * i] as u64 | (words[2 * i + 1] as u64) << 32
+        })))
+    }
+}
+
+/// `H256` as 8 words, holding its bytes in order
+pub mod h256 {
+    use primitive_types::H256;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    /// Serialize `value` as 8 words
+    pub fn serialize<S: Serializer>(value: &H256, serializer: S) -> Result<S::Ok, S::Error> {
+        super::bytes_to_words::<8>(value.as_bytes()).serialize(serializer)
+    }
+
+    /// Deserialize an `H256` from 8 words
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<H256, D::Error> {
+        let words = <[u32; 8]>::deserialize(deserializer)?;
+        Ok(H256(super::words_to_bytes(&words)))
+    }
+}
+
+/// `H160` (an address) as 5 words, holding its bytes in order
+pub mod address {
+    use primitive_types::H160;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    /// Serialize `value` as 5 words
+    pub fn serialize<S: Serializer>(value: &H160, serializer: S) -> Result<S::Ok, S::Error> {
+        super::bytes_to_words::<5>(value.as_bytes()).serialize(serializer)
+    }
+
+    /// Deserialize an `H160` from 5 words
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<H160, D::Error> {
+        let words = <[u32; 5]>::deserialize(deserializer)?;
+        Ok(H160(super::words_to_bytes(&words)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use primitive_types::{H160, H256, U256};
+    use serde::{Deserialize, Serialize};
+
+    use crate::{from_slice_compact, to_vec_compact};
+
+    #[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+    struct Transfer {
+        #[serde(with = "crate::adapters::eth::address")]
+        to: H160,
+        #[serde(with = "crate::adapters::eth::u256")]
+        value: U256,
+        #[serde(with = "crate::adapters::eth::h256")]
+        tx_hash: H256,
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let input = Transfer {
+            to: H160(core::array::from_fn(|i| i as u8 + 1)),
+            value: U256::from(0x1122334455667788u64) << 64 | U256::from(7),
+            tx_hash: H256::repeat_byte(0xab),
+        };
+        let data = to_vec_compact(&input).unwrap();
+        assert_eq!(
+            [0x04030201, 0x08070605, 0x0c0b0a09, 0x100f0e0d, 0x14131211].as_slice(),
+            &data[..5]
+        );
+        assert_eq!(
+            [7, 0, 0x55667788, 0x11223344, 0, 0, 0, 0].as_slice(),
+            &data[5..13]
+        );
+        assert_eq!([0xabababab; 8].as_slice(), &data[13..]);
+        let output: Transfer = from_slice_compact(&data).unwrap();
+        assert_eq!(input, output);
+    }
+
+    #[test]
+    fn test_padded_bytes_layout() {
+        #[derive(Serialize)]
+        struct Hash(#[serde(with = "crate::adapters::eth::h256")] H256);
+
+        // Matches the layout of the bytes written as a padded run.
+        let hash = H256(core::array::from_fn(|i| (i * 7) as u8));
+        let mut expected = alloc::vec::Vec::new();
+        risc0_zkvm::serde::WordWrite::write_padded_bytes(&mut expected, hash.as_bytes()).unwrap();
+        assert_eq!(expected, to_vec_compact(&Hash(hash)).unwrap());
+    }
+}
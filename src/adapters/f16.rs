@@ -0,0 +1,162 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! `Vec<f32>` as half-precision floats, two per word
+//!
+//! For data that doesn't need full precision, such as model weights, this
+//! adapter converts each value to an IEEE 754 binary16 float with the `half`
+//! crate, halving the size of the encoding. The values are written as a byte
+//! run: a word holding the number of bytes, followed by the floats in order,
+//! the first in the low half of each word.
+//! ```rust
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct Layer {
+//!     #[serde(with = "l2r0_small_serde::adapters::f16")]
+//!     weights: Vec<f32>,
+//!     bias: f32,
+//! }
+//! ```
+//!
+//! Values are rounded to the nearest binary16 float. Those outside its range
+//! become infinite, and NaN stays NaN. [F16Vec] wraps a vector for use outside
+//! of a struct field.
+
+use alloc::vec::Vec;
+use core::fmt;
+
+use half::f16;
+use serde::{
+    de::{Error, SeqAccess, Visitor},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+
+/// A `Vec<f32>` serialized as half-precision floats
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct F16Vec(pub Vec<f32>);
+
+impl Serialize for F16Vec {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize(&self.0, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for F16Vec {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserialize(deserializer).map(F16Vec)
+    }
+}
+
+/// Serialize `values` as half-precision floats
+pub fn serialize<S: Serializer>(values: &[f32], serializer: S) -> Result<S::Ok, S::Error> {
+    let bytes: Vec<u8> = values
+        .iter()
+        .flat_map(|&value| f16::from_f32(value).to_le_bytes())
+        .collect();
+    serializer.serialize_bytes(&bytes)
+}
+
+/// Deserialize half-precision floats into a `Vec<f32>`
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<f32>, D::Error> {
+    deserializer.deserialize_byte_buf(F16Visitor)
+}
+
+struct F16Visitor;
+
+impl<'de> Visitor<'de> for F16Visitor {
+    type Value = Vec<f32>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an even number of bytes")
+    }
+
+    fn visit_bytes<E: Error>(self, bytes: &[u8]) -> Result<Vec<f32>, E> {
+        if !bytes.len().is_multiple_of(2) {
+            return Err(E::invalid_length(bytes.len(), &self));
+        }
+        Ok(bytes
+            .chunks_exact(2)
+            .map(|half| f16::from_le_bytes([half[0], half[1]]).to_f32())
+            .collect())
+    }
+
+    // Formats without a byte type give the bytes as a sequence.
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Vec<f32>, A::Error> {
+        let mut bytes = Vec::with_capacity(crate::cautious_capacity(seq.size_hint()));
+        while let Some(byte) = seq.next_element::<u8>()? {
+            bytes.push(byte);
+        }
+        self.visit_bytes(&bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{vec, vec::Vec};
+
+    use serde::{Deserialize, Serialize};
+
+    use super::F16Vec;
+    use crate::{
+        from_slice_compact, from_slice_compact_with_config, to_vec_compact,
+        to_vec_compact_with_config, Config,
+    };
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Layer {
+        #[serde(with = "crate::adapters::f16")]
+        weights: Vec<f32>,
+        bias: f32,
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let input = Layer {
+            weights: vec![1.0, -2.5, 0.099975586],
+            bias: 0.1,
+        };
+        let data = to_vec_compact(&input).unwrap();
+        // 1.0 is 0x3c00, -2.5 is 0xc100 and 0.099975586 is 0x2e66.
+        assert_eq!([6, 0xc1003c00, 0x2e66, 0.1f32.to_bits()].as_slice(), data);
+        let output: Layer = from_slice_compact(&data).unwrap();
+        assert_eq!(input, output);
+
+        let weights: Vec<f32> = (0..1000).map(|i| i as f32 / 8.0).collect();
+        let full = to_vec_compact(&weights).unwrap();
+        let half = to_vec_compact(&F16Vec(weights.clone())).unwrap();
+        assert_eq!(1 + 500, half.len());
+        assert_eq!(1 + 1000, full.len());
+        let F16Vec(output) = from_slice_compact(&half).unwrap();
+        assert_eq!(weights, output);
+    }
+
+    #[test]
+    fn test_precision() {
+        let input = F16Vec(vec![0.1, 1e6, -1e-9, f32::NAN]);
+        let config = Config::new().self_describing(true);
+        let data = to_vec_compact_with_config(&input, config).unwrap();
+        let F16Vec(output) = from_slice_compact_with_config(&data, config).unwrap();
+        assert_eq!(0.099975586, output[0]);
+        assert_eq!(f32::INFINITY, output[1]);
+        assert_eq!(-0.0, output[2]);
+        assert!(output[3].is_nan());
+
+        // A byte count that doesn't hold whole floats
+        let mut odd = to_vec_compact(&F16Vec(vec![1.0])).unwrap();
+        assert_eq!([2, 0x3c00].as_slice(), odd);
+        odd[0] = 3;
+        assert!(from_slice_compact::<F16Vec, _>(&odd).is_err());
+    }
+}
@@ -0,0 +1,29 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Word-efficient encodings of third-party types
+//!
+//! Each adapter is a module for use with `#[serde(with = "...")]`, enabled by
+//! the feature of the same name.
+
+#[cfg(feature = "chrono")]
+pub mod chrono;
+#[cfg(feature = "eth")]
+pub mod eth;
+#[cfg(feature = "f16")]
+pub mod f16;
+#[cfg(feature = "time")]
+pub mod time;
+#[cfg(feature = "uuid")]
+pub mod uuid;
@@ -0,0 +1,75 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! `time` timestamps as seconds and nanoseconds
+//!
+//! In non-human-readable formats, `time` serializes an `OffsetDateTime` as a
+//! tuple of its calendar fields. This adapter writes it as an `i64` of seconds
+//! since the Unix epoch followed by a `u32` of nanoseconds, 3 words in total.
+//! The offset is not encoded; values are always deserialized in UTC.
+//! ```rust
+//! use serde::{Deserialize, Serialize};
+//! use time::OffsetDateTime;
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct Block {
+//!     #[serde(with = "l2r0_small_serde::adapters::time")]
+//!     produced: OffsetDateTime,
+//! }
+//! ```
+
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize, Serializer};
+use time::OffsetDateTime;
+
+/// Serialize `value` as 3 words
+pub fn serialize<S: Serializer>(value: &OffsetDateTime, serializer: S) -> Result<S::Ok, S::Error> {
+    (value.unix_timestamp(), value.nanosecond()).serialize(serializer)
+}
+
+/// Deserialize an `OffsetDateTime` from 3 words
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<OffsetDateTime, D::Error> {
+    let (secs, nanos) = <(i64, u32)>::deserialize(deserializer)?;
+    OffsetDateTime::from_unix_timestamp(secs)
+        .and_then(|value| value.replace_nanosecond(nanos))
+        .map_err(|_| D::Error::custom("time: timestamp out of range"))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+    use time::{OffsetDateTime, UtcOffset};
+
+    use crate::{from_slice_compact, to_vec_compact};
+
+    #[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+    struct Block(#[serde(with = "crate::adapters::time")] OffsetDateTime);
+
+    #[test]
+    fn test_round_trip() {
+        let produced = OffsetDateTime::from_unix_timestamp_nanos(1_700_000_000_123_456_789)
+            .unwrap()
+            .to_offset(UtcOffset::from_hms(2, 0, 0).unwrap());
+        let data = to_vec_compact(&Block(produced)).unwrap();
+        assert_eq!([1_700_000_000, 0, 123_456_789].as_slice(), data);
+        let output: Block = from_slice_compact(&data).unwrap();
+        assert_eq!(produced, output.0);
+        assert_eq!(UtcOffset::UTC, output.0.offset());
+    }
+
+    #[test]
+    fn test_out_of_range() {
+        assert!(from_slice_compact::<Block, _>(&[0u32, 0, 2_000_000_000]).is_err());
+        assert!(from_slice_compact::<Block, _>(&[0u32, 0x8000_0000, 0]).is_err());
+    }
+}
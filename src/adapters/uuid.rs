@@ -0,0 +1,71 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! `Uuid` as 4 words
+//!
+//! In non-human-readable formats, `uuid` serializes as a length-prefixed byte
+//! sequence. This adapter writes the 16 bytes as 4 words, in order, with no
+//! length prefix.
+//! ```rust
+//! use serde::{Deserialize, Serialize};
+//! use uuid::Uuid;
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct Session {
+//!     #[serde(with = "l2r0_small_serde::adapters::uuid")]
+//!     id: Uuid,
+//! }
+//! ```
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use uuid::Uuid;
+
+/// Serialize `value` as 4 words
+pub fn serialize<S: Serializer>(value: &Uuid, serializer: S) -> Result<S::Ok, S::Error> {
+    let bytes = value.as_bytes();
+    let words: [u32; 4] =
+        core::array::from_fn(|i| u32::from_le_bytes(bytes[i * 4..i * 4 + 4].try_into().unwrap()));
+    words.serialize(serializer)
+}
+
+/// Deserialize a `Uuid` from 4 words
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Uuid, D::Error> {
+    let words = <[u32; 4]>::deserialize(deserializer)?;
+    Ok(Uuid::from_bytes(core::array::from_fn(|i| {
+        words[i / 4].to_le_bytes()[i % 4]
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+    use uuid::Uuid;
+
+    use crate::{from_slice_compact, to_vec_compact};
+
+    #[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+    struct Session(#[serde(with = "crate::adapters::uuid")] Uuid, u8);
+
+    #[test]
+    fn test_round_trip() {
+        let input = Session(Uuid::from_u128(0x00112233_44556677_8899aabb_ccddeeff), 1);
+        let data = to_vec_compact(&input).unwrap();
+        assert_eq!(
+            [0x33221100, 0x77665544, 0xbbaa9988, 0xffeeddcc, 1].as_slice(),
+            data
+        );
+        let output: Session = from_slice_compact(&data).unwrap();
+        assert_eq!(input, output);
+    }
+}
@@ -0,0 +1,334 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Deserialization into arena-allocated values
+//!
+//! Types implementing [DeserializeIn] are decoded with their collections
+//! allocated in a [Bump] arena, which avoids allocator churn when decoding many
+//! short-lived messages. Implementations are provided for the `bumpalo`
+//! collections, primitives, tuples and `Option`s of them.
+//! ```rust
+//! use bumpalo::{collections::{String, Vec}, Bump};
+//! use l2r0_small_serde::{arena::from_slice_in, to_vec_compact};
+//!
+//! let data = to_vec_compact(&(7u32, vec!["a", "b"])).unwrap();
+//! let bump = Bump::new();
+//! let (id, names): (u32, Vec<String>) = from_slice_in(&data, &bump).unwrap();
+//! assert_eq!((7, "b"), (id, names[1].as_str()));
+//! ```
+
+use core::{fmt, marker::PhantomData};
+
+use bumpalo::{
+    boxed::Box,
+    collections::{String, Vec},
+    Bump,
+};
+use bytemuck::Pod;
+use risc0_zkvm::serde::WordRead;
+use serde::{
+    de::{DeserializeSeed, SeqAccess, Visitor},
+    Deserialize, Deserializer,
+};
+
+use super::{err::Result, Config};
+
+/// A type that can be deserialized into an arena
+pub trait DeserializeIn<'bump>: Sized {
+    /// Deserialize a value whose allocations live in `bump`
+    fn deserialize_in<'de, D: Deserializer<'de>>(
+        bump: &'bump Bump,
+        deserializer: D,
+    ) -> core::result::Result<Self, D::Error>;
+}
+
+/// Deserializes a `T` into an arena
+///
+/// Use this seed to decode arena-allocated values nested in types with a
+/// custom `Deserialize` impl.
+pub struct InArena<'bump, T>(&'bump Bump, PhantomData<T>);
+
+impl<'bump, T> InArena<'bump, T> {
+    /// Construct a seed allocating in `bump`
+    pub fn new(bump: &'bump Bump) -> Self {
+        InArena(bump, PhantomData)
+    }
+}
+
+impl<'de, 'bump, T: DeserializeIn<'bump>> DeserializeSeed<'de> for InArena<'bump, T> {
+    type Value = T;
+
+    fn deserialize<D: Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> core::result::Result<T, D::Error> {
+        T::deserialize_in(self.0, deserializer)
+    }
+}
+
+/// Deserialize a slice into the specified type, allocating in `bump`.
+pub fn from_slice_in<'bump, T: DeserializeIn<'bump>, P: Pod>(
+    slice: &[P],
+    bump: &'bump Bump,
+) -> Result<T> {
+    from_slice_in_with_config(slice, bump, Config::default())
+}
+
+/// Deserialize a slice into the specified type using the given configuration,
+/// allocating in `bump`.
+pub fn from_slice_in_with_config<'bump, T: DeserializeIn<'bump>, P: Pod>(
+    slice: &[P],
+    bump: &'bump Bump,
+    config: Config,
+) -> Result<T> {
+    let words = bytemuck::allocation::pod_collect_to_vec::<P, u32>(slice);
+    let mut deserializer = super::Deserializer::from_words_with_config(words.as_slice(), config);
+    let value = deserializer.deserialize_in(bump)?;
+    deserializer.finish()?;
+    Ok(value)
+}
+
+impl<'de, R: WordRead + 'de> super::Deserializer<'de, R> {
+    /// Deserialize a value whose allocations live in `bump`
+    ///
+    /// Strings are read into a buffer owned by the deserializer and copied
+    /// into the arena, so the only allocations outside of `bump` are reused
+    /// across values.
+    pub fn deserialize_in<'bump, T: DeserializeIn<'bump>>(
+        &mut self,
+        bump: &'bump Bump,
+    ) -> Result<T> {
+        self.use_scratch_strings();
+        T::deserialize_in(bump, self)
+    }
+}
+
+macro_rules! impl_deserialize_in {
+    ($($ty:ty)*) => {
+        $(
+            impl<'bump> DeserializeIn<'bump> for $ty {
+                fn deserialize_in<'de, D: Deserializer<'de>>(
+                    _bump: &'bump Bump,
+                    deserializer: D,
+                ) -> core::result::Result<Self, D::Error> {
+                    <$ty>::deserialize(deserializer)
+                }
+            }
+        )*
+    };
+}
+
+impl_deserialize_in!(() bool u8 u16 u32 u64 u128 i8 i16 i32 i64 i128 f32 f64 char);
+
+impl<'bump> DeserializeIn<'bump> for String<'bump> {
+    fn deserialize_in<'de, D: Deserializer<'de>>(
+        bump: &'bump Bump,
+        deserializer: D,
+    ) -> core::result::Result<Self, D::Error> {
+        struct StringVisitor<'bump>(&'bump Bump);
+
+        impl<'de, 'bump> Visitor<'de> for StringVisitor<'bump> {
+            type Value = String<'bump>;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a string")
+            }
+
+            fn visit_str<E: serde::de::Error>(
+                self,
+                v: &str,
+            ) -> core::result::Result<Self::Value, E> {
+                Ok(String::from_str_in(v, self.0))
+            }
+        }
+
+        deserializer.deserialize_str(StringVisitor(bump))
+    }
+}
+
+impl<'bump, T: DeserializeIn<'bump> + 'bump> DeserializeIn<'bump> for Vec<'bump, T> {
+    fn deserialize_in<'de, D: Deserializer<'de>>(
+        bump: &'bump Bump,
+        deserializer: D,
+    ) -> core::result::Result<Self, D::Error> {
+        struct VecVisitor<'bump, T>(&'bump Bump, PhantomData<T>);
+
+        impl<'de, 'bump, T: DeserializeIn<'bump> + 'bump> Visitor<'de> for VecVisitor<'bump, T> {
+            type Value = Vec<'bump, T>;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a sequence")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(
+                self,
+                mut seq: A,
+            ) -> core::result::Result<Self::Value, A::Error> {
+                let mut values =
+                    Vec::with_capacity_in(crate::cautious_capacity(seq.size_hint()), self.0);
+                while let Some(value) = seq.next_element_seed(InArena::new(self.0))? {
+                    values.push(value);
+                }
+                Ok(values)
+            }
+        }
+
+        deserializer.deserialize_seq(VecVisitor(bump, PhantomData))
+    }
+}
+
+impl<'bump, T: DeserializeIn<'bump> + 'bump> DeserializeIn<'bump> for Box<'bump, T> {
+    fn deserialize_in<'de, D: Deserializer<'de>>(
+        bump: &'bump Bump,
+        deserializer: D,
+    ) -> core::result::Result<Self, D::Error> {
+        Ok(Box::new_in(T::deserialize_in(bump, deserializer)?, bump))
+    }
+}
+
+impl<'bump, T: DeserializeIn<'bump>> DeserializeIn<'bump> for Option<T> {
+    fn deserialize_in<'de, D: Deserializer<'de>>(
+        bump: &'bump Bump,
+        deserializer: D,
+    ) -> core::result::Result<Self, D::Error> {
+        struct OptionVisitor<'bump, T>(&'bump Bump, PhantomData<T>);
+
+        impl<'de, 'bump, T: DeserializeIn<'bump>> Visitor<'de> for OptionVisitor<'bump, T> {
+            type Value = Option<T>;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("an option")
+            }
+
+            fn visit_none<E>(self) -> core::result::Result<Self::Value, E> {
+                Ok(None)
+            }
+
+            fn visit_some<D: Deserializer<'de>>(
+                self,
+                deserializer: D,
+            ) -> core::result::Result<Self::Value, D::Error> {
+                T::deserialize_in(self.0, deserializer).map(Some)
+            }
+        }
+
+        deserializer.deserialize_option(OptionVisitor(bump, PhantomData))
+    }
+}
+
+macro_rules! impl_deserialize_in_tuple {
+    ($($len:literal => ($($name:ident)+))*) => {
+        $(
+            impl<'bump, $($name: DeserializeIn<'bump>),+> DeserializeIn<'bump> for ($($name,)+) {
+                fn deserialize_in<'de, D: Deserializer<'de>>(
+                    bump: &'bump Bump,
+                    deserializer: D,
+                ) -> core::result::Result<Self, D::Error> {
+                    struct TupleVisitor<'bump, $($name),+>(&'bump Bump, PhantomData<($($name,)+)>);
+
+                    impl<'de, 'bump, $($name: DeserializeIn<'bump>),+> Visitor<'de>
+                        for TupleVisitor<'bump, $($name),+>
+                    {
+                        type Value = ($($name,)+);
+
+                        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                            write!(formatter, "a tuple of size {}", $len)
+                        }
+
+                        #[allow(non_snake_case)]
+                        fn visit_seq<A: SeqAccess<'de>>(
+                            self,
+                            mut seq: A,
+                        ) -> core::result::Result<Self::Value, A::Error> {
+                            let mut index = 0;
+                            $(
+                                let $name = seq
+                                    .next_element_seed(InArena::<$name>::new(self.0))?
+                                    .ok_or_else(|| serde::de::Error::invalid_length(index, &self))?;
+                                index += 1;
+                            )+
+                            let _ = index;
+                            Ok(($($name,)+))
+                        }
+                    }
+
+                    deserializer.deserialize_tuple($len, TupleVisitor(bump, PhantomData))
+                }
+            }
+        )*
+    };
+}
+
+impl_deserialize_in_tuple! {
+    1 => (T0)
+    2 => (T0 T1)
+    3 => (T0 T1 T2)
+    4 => (T0 T1 T2 T3)
+}
+
+#[cfg(test)]
+mod tests {
+    use bumpalo::{
+        collections::{String, Vec},
+        Bump,
+    };
+
+    use super::*;
+    use crate::to_vec_compact;
+
+    #[test]
+    fn test_round_trip() {
+        let input = (
+            alloc::vec![alloc::string::String::from("alpha"), "beta".into()],
+            Some(alloc::vec![1u8, 2, 3]),
+            -5i64,
+        );
+        let data = to_vec_compact(&input).unwrap();
+        let bump = Bump::new();
+        let output: (Vec<String>, Option<Vec<u8>>, i64) = from_slice_in(&data, &bump).unwrap();
+        assert_eq!(
+            ["alpha", "beta"],
+            [output.0[0].as_str(), output.0[1].as_str()]
+        );
+        assert_eq!([1, 2, 3], output.1.as_deref().unwrap());
+        assert_eq!(-5, output.2);
+        assert!(bump.allocated_bytes() > 0);
+    }
+
+    #[test]
+    fn test_reused_deserializer() {
+        let data = [
+            to_vec_compact("one").unwrap(),
+            to_vec_compact("two").unwrap(),
+        ]
+        .concat();
+        let bump = Bump::new();
+        let mut deserializer = crate::Deserializer::new(data.as_slice());
+        let first: String = deserializer.deserialize_in(&bump).unwrap();
+        let second: Box<String> = deserializer.deserialize_in(&bump).unwrap();
+        assert_eq!(("one", "two"), (first.as_str(), second.as_str()));
+    }
+
+    #[test]
+    fn test_truncated() {
+        let data = to_vec_compact(&(1u32, "text")).unwrap();
+        let bump = Bump::new();
+        assert_eq!(
+            Err(crate::Error::DeserializeLengthOverflow { requested: 4 }),
+            from_slice_in::<(u32, String), _>(&data[..2], &bump).map(|_| ())
+        );
+        // A malformed length doesn't allocate up front
+        assert!(from_slice_in::<Vec<u32>, _>(&[0xffffffffu32], &bump).is_err());
+    }
+}
@@ -0,0 +1,156 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::vec::Vec;
+
+use bytemuck::Pod;
+use risc0_zkvm::serde::WordWrite;
+use serde::de::DeserializeOwned;
+
+use super::{
+    err::{Error, Result},
+    from_slice_compact, Serializer,
+};
+
+/// The BabyBear field modulus, `2^31 - 2^27 + 1`
+pub const BABYBEAR_PRIME: u32 = (1 << 31) - (1 << 27) + 1;
+
+/// Split words into 16-bit limbs that are valid BabyBear elements
+///
+/// Each word becomes two elements, the low half followed by the high half.
+pub fn encode_babybear(words: &[u32]) -> Vec<u32> {
+    let mut out = Vec::with_capacity(words.len() * 2);
+    for &word in words {
+        out.extend_from_slice(&[word & 0xffff, word >> 16]);
+    }
+    out
+}
+
+/// Join 16-bit limbs produced by [encode_babybear] back into words
+pub fn decode_babybear(elements: &[u32]) -> Result<Vec<u32>> {
+    if !elements.len().is_multiple_of(2) {
+        return Err(Error::DeserializeUnexpectedEnd {
+            needed: elements.len() + 1,
+            available: Some(elements.len()),
+        });
+    }
+    elements
+        .chunks_exact(2)
+        .map(|limbs| match limbs {
+            [lo, hi] if *lo <= 0xffff && *hi <= 0xffff => Ok(lo | hi << 16),
+            _ => Err(Error::DeserializeBadFieldElement),
+        })
+        .collect()
+}
+
+/// Serialize to a vector of BabyBear elements
+///
+/// Equivalent to [encode_babybear] of the compact encoding, without the
+/// intermediate buffer.
+pub fn to_vec_babybear<T>(value: &T) -> Result<Vec<u32>>
+where
+    T: serde::Serialize + ?Sized,
+{
+    let mut vec: Vec<u32> = Vec::new();
+    value.serialize(&mut Serializer::new(BabyBearWriter::new(&mut vec)))?;
+    Ok(vec)
+}
+
+/// Deserialize a slice of BabyBear elements into the specified type.
+///
+/// `slice` must have been produced by [to_vec_babybear], [encode_babybear] or
+/// a [BabyBearWriter].
+pub fn from_slice_babybear<T: DeserializeOwned, P: Pod>(slice: &[P]) -> Result<T> {
+    let elements = bytemuck::allocation::pod_collect_to_vec::<P, u32>(slice);
+    from_slice_compact(&decode_babybear(&elements)?)
+}
+
+/// A WordWrite that writes every word as two BabyBear elements
+///
+/// See [encode_babybear] for the layout.
+pub struct BabyBearWriter<W: WordWrite> {
+    inner: W,
+}
+
+impl<W: WordWrite> BabyBearWriter<W> {
+    /// Construct a BabyBearWriter that writes to `inner`
+    pub fn new(inner: W) -> Self {
+        BabyBearWriter { inner }
+    }
+
+    /// Returns the inner stream
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: WordWrite> WordWrite for BabyBearWriter<W> {
+    fn write_words(&mut self, words: &[u32]) -> risc0_zkvm::serde::Result<()> {
+        for &word in words {
+            self.inner.write_words(&[word & 0xffff, word >> 16])?;
+        }
+        Ok(())
+    }
+
+    fn write_padded_bytes(&mut self, bytes: &[u8]) -> risc0_zkvm::serde::Result<()> {
+        let mut words = Vec::with_capacity(bytes.len().div_ceil(4));
+        words.write_padded_bytes(bytes)?;
+        self.write_words(&words)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::String, vec, vec::Vec};
+
+    use super::*;
+    use crate::to_vec_compact;
+
+    #[test]
+    fn test_round_trip() {
+        let input = (String::from("field"), vec![u32::MAX, 0x8000_0000], -1i64);
+        let data = to_vec_babybear(&input).unwrap();
+        assert!(data.iter().all(|&element| element < BABYBEAR_PRIME));
+        assert_eq!(encode_babybear(&to_vec_compact(&input).unwrap()), data);
+        let output: (String, Vec<u32>, i64) = from_slice_babybear(&data).unwrap();
+        assert_eq!(input, output);
+    }
+
+    #[test]
+    fn test_layout() {
+        assert_eq!(
+            vec![0x5678, 0x1234, 0xffff, 0xffff],
+            encode_babybear(&[0x12345678, u32::MAX])
+        );
+        assert_eq!(
+            vec![0x12345678],
+            decode_babybear(&[0x5678, 0x1234]).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_bad_elements() {
+        assert_eq!(
+            Err(Error::DeserializeBadFieldElement),
+            decode_babybear(&[0x1_0000, 0])
+        );
+        assert_eq!(
+            Err(Error::DeserializeUnexpectedEnd {
+                needed: 2,
+                available: Some(1)
+            }),
+            decode_babybear(&[1])
+        );
+    }
+}
@@ -0,0 +1,133 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Conversion from and to other serde formats
+//!
+//! Neither bincode nor postcard is self-describing, so data is converted by
+//! decoding it into a `T` and encoding that again. The functions for each
+//! format are enabled by the feature of the same name.
+//! ```rust
+//! # #[cfg(feature = "postcard")] {
+//! use l2r0_small_serde::bridge;
+//!
+//! let bytes = postcard::to_allocvec(&(7u32, "seven")).unwrap();
+//! let words = bridge::from_postcard_bytes::<(u32, String)>(&bytes).unwrap();
+//! assert_eq!(l2r0_small_serde::to_vec_compact(&(7u32, "seven")).unwrap(), words);
+//! assert_eq!(bytes, bridge::to_postcard_bytes::<(u32, String), _>(&words).unwrap());
+//! # }
+//! ```
+
+use alloc::vec::Vec;
+
+use bytemuck::Pod;
+use serde::{de::DeserializeOwned, Serialize};
+
+use super::{
+    err::{Error, Result},
+    from_slice_compact, to_vec_compact,
+};
+
+/// Report a failure of another format as a custom error
+fn foreign<E: core::fmt::Display>(e: E) -> Error {
+    <Error as serde::de::Error>::custom(e)
+}
+
+/// Convert bincode bytes holding a `T` to the word format
+#[cfg(feature = "bincode")]
+pub fn from_bincode_bytes<T: DeserializeOwned + Serialize>(bytes: &[u8]) -> Result<Vec<u32>> {
+    let value: T = bincode::deserialize(bytes).map_err(foreign)?;
+    to_vec_compact(&value)
+}
+
+/// Convert words holding a `T` to bincode bytes
+#[cfg(feature = "bincode")]
+pub fn to_bincode_bytes<T: DeserializeOwned + Serialize, P: Pod>(words: &[P]) -> Result<Vec<u8>> {
+    let value: T = from_slice_compact(words)?;
+    bincode::serialize(&value).map_err(foreign)
+}
+
+/// Convert postcard bytes holding a `T` to the word format
+#[cfg(feature = "postcard")]
+pub fn from_postcard_bytes<T: DeserializeOwned + Serialize>(bytes: &[u8]) -> Result<Vec<u32>> {
+    let value: T = postcard::from_bytes(bytes).map_err(foreign)?;
+    to_vec_compact(&value)
+}
+
+/// Convert words holding a `T` to postcard bytes
+#[cfg(feature = "postcard")]
+pub fn to_postcard_bytes<T: DeserializeOwned + Serialize, P: Pod>(words: &[P]) -> Result<Vec<u8>> {
+    let value: T = from_slice_compact(words)?;
+    postcard::to_allocvec(&value).map_err(foreign)
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{collections::BTreeMap, string::String, vec, vec::Vec};
+
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Input {
+        id: u64,
+        name: String,
+        flags: Vec<u8>,
+        scores: BTreeMap<u32, i16>,
+        parent: Option<u32>,
+    }
+
+    fn input() -> Input {
+        Input {
+            id: 1 << 40,
+            name: "input".into(),
+            flags: vec![1, 0, 1],
+            scores: BTreeMap::from([(1, -1), (2, 300)]),
+            parent: Some(9),
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "bincode")]
+    fn test_bincode() {
+        let bytes = bincode::serialize(&input()).unwrap();
+        let words = from_bincode_bytes::<Input>(&bytes).unwrap();
+        assert_eq!(to_vec_compact(&input()).unwrap(), words);
+        assert_eq!(bytes, to_bincode_bytes::<Input, _>(&words).unwrap());
+        assert!(matches!(
+            from_bincode_bytes::<Input>(&bytes[..4]),
+            Err(Error::Custom(_))
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "postcard")]
+    fn test_postcard() {
+        let bytes = postcard::to_allocvec(&input()).unwrap();
+        let words = from_postcard_bytes::<Input>(&bytes).unwrap();
+        assert_eq!(to_vec_compact(&input()).unwrap(), words);
+        assert_eq!(bytes, to_postcard_bytes::<Input, _>(&words).unwrap());
+        assert!(matches!(
+            from_postcard_bytes::<Input>(&bytes[..4]),
+            Err(Error::Custom(_))
+        ));
+        assert_eq!(
+            Err(Error::DeserializeUnexpectedEnd {
+                needed: 1,
+                available: Some(0)
+            }),
+            to_postcard_bytes::<Input, _>(&words[..2])
+        );
+    }
+}
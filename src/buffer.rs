@@ -0,0 +1,130 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::vec::Vec;
+
+use risc0_zkvm::serde::WordRead;
+
+use super::{err::Result, MAX_PREALLOC, WORD_SIZE};
+
+/// The little-endian bytes of `words`
+pub(crate) fn words_to_le_bytes(words: &[u32]) -> Vec<u8> {
+    words.iter().flat_map(|word| word.to_le_bytes()).collect()
+}
+
+/// The words whose little-endian bytes are `bytes`, unless `bytes` ends with a
+/// partial word
+pub(crate) fn le_bytes_to_words(bytes: &[u8]) -> Option<Vec<u32>> {
+    if !bytes.len().is_multiple_of(WORD_SIZE) {
+        return None;
+    }
+    let words = bytes
+        .chunks_exact(WORD_SIZE)
+        .map(|word| u32::from_le_bytes(word.try_into().unwrap()))
+        .collect();
+    Some(words)
+}
+
+/// Read `len` words from `reader`, where `len` comes from untrusted input
+///
+/// The buffer grows by at most [MAX_PREALLOC] words at a time, as words
+/// arrive.
+pub(crate) fn read_words<R: WordRead>(reader: &mut R, len: usize) -> Result<Vec<u32>> {
+    let mut words = Vec::new();
+    while words.len() < len {
+        let start = words.len();
+        words.resize(len.min(start + MAX_PREALLOC), 0u32);
+        reader.read_words(&mut words[start..])?;
+    }
+    Ok(words)
+}
+
+#[cfg(any(feature = "compress", feature = "encrypt"))]
+/// Read `len` padded bytes from `reader`, where `len` comes from untrusted
+/// input
+pub(crate) fn read_padded_bytes<R: WordRead>(reader: &mut R, len: usize) -> Result<Vec<u8>> {
+    let words = read_words(reader, len.div_ceil(WORD_SIZE))?;
+    let mut bytes = words_to_le_bytes(&words);
+    bytes.truncate(len);
+    Ok(bytes)
+}
+
+#[cfg(any(feature = "compress", feature = "encrypt"))]
+/// A WordRead over words held in memory, such as a decoded frame
+pub(crate) struct WordBuffer {
+    words: Vec<u32>,
+    position: usize,
+}
+
+#[cfg(any(feature = "compress", feature = "encrypt"))]
+impl WordBuffer {
+    pub(crate) fn new(words: Vec<u32>) -> Self {
+        WordBuffer { words, position: 0 }
+    }
+
+    fn read_with<F>(&mut self, f: F) -> risc0_zkvm::serde::Result<()>
+    where
+        F: FnOnce(&mut &[u32]) -> risc0_zkvm::serde::Result<()>,
+    {
+        let mut remaining = &self.words[self.position..];
+        f(&mut remaining)?;
+        self.position = self.words.len() - remaining.len();
+        Ok(())
+    }
+}
+
+#[cfg(any(feature = "compress", feature = "encrypt"))]
+impl WordRead for WordBuffer {
+    fn read_words(&mut self, words: &mut [u32]) -> risc0_zkvm::serde::Result<()> {
+        self.read_with(|remaining| remaining.read_words(words))
+    }
+
+    fn read_padded_bytes(&mut self, bytes: &mut [u8]) -> risc0_zkvm::serde::Result<()> {
+        self.read_with(|remaining| remaining.read_padded_bytes(bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+
+    use super::*;
+
+    #[test]
+    fn test_conversions() {
+        let words = [0x04030201u32, 0x08070605];
+        let bytes = words_to_le_bytes(&words);
+        assert_eq!(vec![1u8, 2, 3, 4, 5, 6, 7, 8], bytes);
+        assert_eq!(Some(words.to_vec()), le_bytes_to_words(&bytes));
+        assert_eq!(None, le_bytes_to_words(&bytes[..7]));
+    }
+
+    #[test]
+    #[cfg(any(feature = "compress", feature = "encrypt"))]
+    fn test_word_buffer() {
+        let mut reader = [0x04030201u32, 0x08070605].as_slice();
+        let bytes = read_padded_bytes(&mut reader, 5).unwrap();
+        assert_eq!(vec![1u8, 2, 3, 4, 5], bytes);
+        assert!(reader.is_empty());
+
+        let mut buffer = WordBuffer::new(vec![1, 2, 3]);
+        let mut word = [0u32];
+        buffer.read_words(&mut word).unwrap();
+        assert_eq!([1], word);
+        let mut bytes = [0u8; 5];
+        buffer.read_padded_bytes(&mut bytes).unwrap();
+        assert_eq!([2, 0, 0, 0, 3], bytes);
+        assert!(buffer.read_words(&mut word).is_err());
+    }
+}
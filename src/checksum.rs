@@ -0,0 +1,105 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::vec::Vec;
+
+use bytemuck::Pod;
+use serde::de::DeserializeOwned;
+
+use super::{
+    err::{Error, Result},
+    from_slice_compact, to_vec_compact,
+};
+
+/// CRC-32 (IEEE) of the little-endian bytes of `words`
+fn crc32(words: &[u32]) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+    for byte in words.iter().flat_map(|word| word.to_le_bytes()) {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                crc >> 1 ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+/// Serialize to a vector of u32 words followed by a CRC-32 of them
+///
+/// The checksum is computed over the little-endian bytes of the payload.
+pub fn to_vec_checksummed<T>(value: &T) -> Result<Vec<u32>>
+where
+    T: serde::Serialize + ?Sized,
+{
+    let mut vec = to_vec_compact(value)?;
+    vec.push(crc32(&vec));
+    Ok(vec)
+}
+
+/// Verify the trailer of a checksummed slice and deserialize the payload.
+///
+/// `slice` must have been produced by [to_vec_checksummed].
+pub fn from_slice_checksummed<T: DeserializeOwned, P: Pod>(slice: &[P]) -> Result<T> {
+    let words = bytemuck::allocation::pod_collect_to_vec::<P, u32>(slice);
+    let (checksum, payload) = words.split_last().ok_or(Error::DeserializeUnexpectedEnd {
+        needed: 1,
+        available: Some(0),
+    })?;
+    if crc32(payload) != *checksum {
+        return Err(Error::DeserializeBadChecksum);
+    }
+    from_slice_compact(payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::String, vec, vec::Vec};
+
+    use super::*;
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(0, crc32(&[]));
+        assert_eq!(0x9be3e0a3, crc32(&[u32::from_le_bytes(*b"1234")]));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let input = (String::from("stored"), vec![1u64, 2, 3]);
+        let data = to_vec_checksummed(&input).unwrap();
+        assert_eq!(to_vec_compact(&input).unwrap(), data[..data.len() - 1]);
+        let output: (String, Vec<u64>) = from_slice_checksummed(&data).unwrap();
+        assert_eq!(input, output);
+    }
+
+    #[test]
+    fn test_corrupted() {
+        let mut data = to_vec_checksummed(&7u32).unwrap();
+        data[0] ^= 1;
+        assert_eq!(
+            Err(Error::DeserializeBadChecksum),
+            from_slice_checksummed::<u32, _>(&data)
+        );
+        assert_eq!(
+            Err(Error::DeserializeUnexpectedEnd {
+                needed: 1,
+                available: Some(0)
+            }),
+            from_slice_checksummed::<u32, u32>(&[])
+        );
+    }
+}
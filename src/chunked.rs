@@ -0,0 +1,307 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::{string::ToString, vec::Vec};
+
+use bytemuck::Pod;
+use risc0_zkvm::serde::{WordRead, WordWrite};
+use serde::de::DeserializeOwned;
+
+use super::{
+    err::{Error, Result},
+    Deserializer, Serializer, WORD_SIZE,
+};
+
+/// Set in the header of the last frame of a stream
+const LAST_FRAME: u32 = 1 << 31;
+
+/// Serialize to a vector of u32 words split into frames of at most
+/// `max_words` words
+///
+/// See [ChunkedWriter] for the layout of the frames.
+pub fn to_vec_chunked<T>(value: &T, max_words: usize) -> Result<Vec<u32>>
+where
+    T: serde::Serialize + ?Sized,
+{
+    let mut vec: Vec<u32> = Vec::new();
+    let mut writer = ChunkedWriter::new(&mut vec, max_words);
+    value.serialize(&mut Serializer::new(&mut writer))?;
+    writer.finish()?;
+    Ok(vec)
+}
+
+/// Deserialize a slice of frames into the specified type.
+///
+/// `slice` must have been produced by [to_vec_chunked] or a [ChunkedWriter]
+/// with the same `max_words`. The value must take every frame up to the end
+/// of the last one.
+pub fn from_slice_chunked<T: DeserializeOwned, P: Pod>(slice: &[P], max_words: usize) -> Result<T> {
+    let words = bytemuck::allocation::pod_collect_to_vec::<P, u32>(slice);
+    let mut reader = ChunkedReader::new(words.as_slice(), max_words);
+    let decode = |reader: &mut ChunkedReader<&[u32]>| -> Result<T> {
+        let mut deserializer = Deserializer::new(reader);
+        let value = T::deserialize(&mut deserializer)?;
+        deserializer.finish()?;
+        Ok(value)
+    };
+    let result = decode(&mut reader).and_then(|value| Ok((value, reader.remaining()?)));
+    // WordRead errors can only carry a message, so the reader records a bad
+    // frame for us to report it as such.
+    if reader.bad_frame {
+        return Err(Error::DeserializeBadFrame);
+    }
+    match result? {
+        (value, 0) => Ok(value),
+        (_, count) => Err(Error::DeserializeTrailingWords { count }),
+    }
+}
+
+/// A WordWrite that splits the words written to it into frames
+///
+/// Each frame is a header word holding the number of words that follow it,
+/// followed by those words, and takes at most `max_words` words in all. The
+/// header of the last frame written by [ChunkedWriter::finish] also has its
+/// top bit set, so that a reader knows where the stream ends. Full frames are
+/// written to the inner stream as soon as they fill up, so at most a frame is
+/// held in memory.
+pub struct ChunkedWriter<W: WordWrite> {
+    inner: W,
+    max_words: usize,
+    words: Vec<u32>,
+}
+
+impl<W: WordWrite> ChunkedWriter<W> {
+    /// Construct a ChunkedWriter that writes frames of at most `max_words`
+    /// words to `inner`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_words` leaves no room for a word after the header, or
+    /// if the length of a frame can't be held with the top bit clear.
+    pub fn new(inner: W, max_words: usize) -> Self {
+        assert!(
+            (2..=LAST_FRAME as usize).contains(&max_words),
+            "invalid frame size"
+        );
+        ChunkedWriter {
+            inner,
+            max_words,
+            words: Vec::new(),
+        }
+    }
+
+    fn write_frame(&mut self, len: usize, last: bool) -> risc0_zkvm::serde::Result<()> {
+        let header = len as u32 | if last { LAST_FRAME } else { 0 };
+        self.inner.write_words(&[header])?;
+        self.inner.write_words(&self.words[..len])?;
+        self.words.drain(..len);
+        Ok(())
+    }
+
+    fn write_full_frames(&mut self) -> risc0_zkvm::serde::Result<()> {
+        // The last frame is only written by finish, so a full frame is held
+        // back until a word follows it.
+        while self.words.len() > self.max_words - 1 {
+            self.write_frame(self.max_words - 1, false)?;
+        }
+        Ok(())
+    }
+
+    /// Write the words held back as the last frame
+    ///
+    /// The last frame is only empty if nothing was written.
+    ///
+    /// Returns the inner stream.
+    pub fn finish(mut self) -> Result<W> {
+        self.write_full_frames()?;
+        self.write_frame(self.words.len(), true)?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: WordWrite> WordWrite for ChunkedWriter<W> {
+    fn write_words(&mut self, words: &[u32]) -> risc0_zkvm::serde::Result<()> {
+        self.words.write_words(words)?;
+        self.write_full_frames()
+    }
+
+    fn write_padded_bytes(&mut self, bytes: &[u8]) -> risc0_zkvm::serde::Result<()> {
+        self.words.write_padded_bytes(bytes)?;
+        self.write_full_frames()
+    }
+}
+
+/// A WordRead over the frames written by a [ChunkedWriter]
+///
+/// Frames are read from the inner stream one at a time as the words are
+/// needed. Reading past the end of the last frame fails with
+/// [Error::DeserializeUnexpectedEnd], and a frame longer than `max_words`
+/// fails with a custom error describing [Error::DeserializeBadFrame].
+pub struct ChunkedReader<R: WordRead> {
+    inner: R,
+    max_words: usize,
+    words: Vec<u32>,
+    position: usize,
+    last: bool,
+    bad_frame: bool,
+}
+
+impl<R: WordRead> ChunkedReader<R> {
+    /// Construct a ChunkedReader over frames of at most `max_words` words
+    pub fn new(inner: R, max_words: usize) -> Self {
+        ChunkedReader {
+            inner,
+            max_words,
+            words: Vec::new(),
+            position: 0,
+            last: false,
+            bad_frame: false,
+        }
+    }
+
+    /// Read the next frame into the buffer
+    fn read_frame(&mut self) -> risc0_zkvm::serde::Result<()> {
+        if self.last {
+            return Err(risc0_zkvm::serde::Error::DeserializeUnexpectedEnd);
+        }
+        let mut header = 0u32;
+        self.inner.read_words(core::slice::from_mut(&mut header))?;
+        let len = (header & !LAST_FRAME) as usize;
+        if len >= self.max_words {
+            self.bad_frame = true;
+            return Err(risc0_zkvm::serde::Error::Custom(
+                Error::DeserializeBadFrame.to_string(),
+            ));
+        }
+        self.last = header & LAST_FRAME != 0;
+        self.words.resize(len, 0);
+        self.inner.read_words(&mut self.words)?;
+        self.position = 0;
+        Ok(())
+    }
+
+    /// Read the frames left, returning the number of words not yet read
+    fn remaining(&mut self) -> risc0_zkvm::serde::Result<usize> {
+        let mut count = self.words.len() - self.position;
+        while !self.last {
+            self.read_frame()?;
+            count += self.words.len();
+        }
+        self.position = self.words.len();
+        Ok(count)
+    }
+}
+
+impl<R: WordRead> WordRead for ChunkedReader<R> {
+    fn read_words(&mut self, mut words: &mut [u32]) -> risc0_zkvm::serde::Result<()> {
+        while !words.is_empty() {
+            if self.position == self.words.len() {
+                self.read_frame()?;
+                continue;
+            }
+            let len = words.len().min(self.words.len() - self.position);
+            let (head, rest) = words.split_at_mut(len);
+            head.copy_from_slice(&self.words[self.position..self.position + len]);
+            self.position += len;
+            words = rest;
+        }
+        Ok(())
+    }
+
+    fn read_padded_bytes(&mut self, bytes: &mut [u8]) -> risc0_zkvm::serde::Result<()> {
+        let mut words = alloc::vec![0u32; bytes.len().div_ceil(WORD_SIZE)];
+        self.read_words(&mut words)?;
+        (&words[..]).read_padded_bytes(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::String, vec, vec::Vec};
+
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let input: Vec<(u64, String)> =
+            (0..20).map(|i| (i, "witness".repeat(i as usize))).collect();
+        let compact = crate::to_vec_compact(&input).unwrap();
+        for max_words in [2, 3, 8, 1000] {
+            let data = to_vec_chunked(&input, max_words).unwrap();
+            let output: Vec<(u64, String)> = from_slice_chunked(&data, max_words).unwrap();
+            assert_eq!(input, output);
+
+            // Walk the frames, which reassemble to the plain encoding.
+            let mut payload = Vec::new();
+            let mut rest = &data[..];
+            loop {
+                let len = (rest[0] & !LAST_FRAME) as usize;
+                assert!(len < max_words);
+                payload.extend_from_slice(&rest[1..1 + len]);
+                let last = rest[0] & LAST_FRAME != 0;
+                rest = &rest[1 + len..];
+                if last {
+                    break;
+                }
+                assert_eq!(max_words - 1, len);
+            }
+            assert!(rest.is_empty());
+            assert_eq!(compact, payload);
+        }
+    }
+
+    #[test]
+    fn test_exact_frames() {
+        // A full frame is held back, so the last frame is only empty for an
+        // empty payload.
+        let data = to_vec_chunked(&[1u32, 2, 3, 4], 3).unwrap();
+        assert_eq!(vec![2, 1, 2, LAST_FRAME | 2, 3, 4], data);
+        assert_eq!(vec![LAST_FRAME], to_vec_chunked(&(), 3).unwrap());
+        assert_eq!(
+            [1u32, 2, 3, 4],
+            from_slice_chunked::<[u32; 4], _>(&data, 3).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_bad_frames() {
+        let data = to_vec_chunked(&[1u32, 2, 3, 4], 3).unwrap();
+        assert_eq!(
+            Err(Error::DeserializeBadFrame),
+            from_slice_chunked::<[u32; 4], _>(&data, 2)
+        );
+        assert_eq!(
+            Err(Error::DeserializeTrailingWords { count: 1 }),
+            from_slice_chunked::<[u32; 3], _>(&data, 3)
+        );
+        assert_eq!(
+            Err(Error::DeserializeTrailingWords { count: 3 }),
+            from_slice_chunked::<u32, _>(&data, 3)
+        );
+        assert_eq!(
+            Err(Error::DeserializeUnexpectedEnd {
+                needed: 1,
+                available: None
+            }),
+            from_slice_chunked::<[u32; 5], _>(&data, 3)
+        );
+        assert_eq!(
+            Err(Error::DeserializeUnexpectedEnd {
+                needed: 1,
+                available: None
+            }),
+            from_slice_chunked::<[u32; 4], _>(&data[..5], 3)
+        );
+    }
+}
@@ -0,0 +1,184 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::vec::Vec;
+
+use bytemuck::Pod;
+use risc0_zkvm::serde::{WordRead, WordWrite};
+use serde::de::DeserializeOwned;
+
+use super::{
+    buffer::{le_bytes_to_words, read_padded_bytes, words_to_le_bytes, WordBuffer},
+    err::{Error, Result},
+    Deserializer, Serializer,
+};
+
+/// Serialize to an LZ4-compressed vector of u32 words
+pub fn to_vec_compressed<T>(value: &T) -> Result<Vec<u32>>
+where
+    T: serde::Serialize + ?Sized,
+{
+    let mut vec: Vec<u32> = Vec::new();
+    let mut writer = CompressedWriter::new(&mut vec);
+    value.serialize(&mut Serializer::new(&mut writer))?;
+    writer.finish()?;
+    Ok(vec)
+}
+
+/// Deserialize an LZ4-compressed slice into the specified type.
+///
+/// `slice` must have been produced by [to_vec_compressed] or a
+/// [CompressedWriter].
+pub fn from_slice_compressed<T: DeserializeOwned, P: Pod>(slice: &[P]) -> Result<T> {
+    let words = bytemuck::allocation::pod_collect_to_vec::<P, u32>(slice);
+    let reader = CompressedReader::new(words.as_slice())?;
+    T::deserialize(&mut Deserializer::new(reader))
+}
+
+/// A WordWrite that compresses the words written to it
+///
+/// Words are collected in memory and written to the inner stream as a single
+/// LZ4 frame by [CompressedWriter::finish]: a word holding the size of the
+/// compressed data in bytes, followed by the padded compressed data.
+pub struct CompressedWriter<W: WordWrite> {
+    inner: W,
+    words: Vec<u32>,
+}
+
+impl<W: WordWrite> CompressedWriter<W> {
+    /// Construct a CompressedWriter that writes to `inner`
+    pub fn new(inner: W) -> Self {
+        CompressedWriter {
+            inner,
+            words: Vec::new(),
+        }
+    }
+
+    /// Compress the words written so far to the inner stream
+    ///
+    /// Returns the inner stream.
+    pub fn finish(mut self) -> Result<W> {
+        let compressed = lz4_flex::compress_prepend_size(&words_to_le_bytes(&self.words));
+        let len = compressed
+            .len()
+            .try_into()
+            .map_err(|_| Error::SerializeLengthOverflow {
+                requested: compressed.len(),
+            })?;
+        self.inner.write_words(&[len])?;
+        self.inner.write_padded_bytes(&compressed)?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: WordWrite> WordWrite for CompressedWriter<W> {
+    fn write_words(&mut self, words: &[u32]) -> risc0_zkvm::serde::Result<()> {
+        self.words.write_words(words)
+    }
+
+    fn write_padded_bytes(&mut self, bytes: &[u8]) -> risc0_zkvm::serde::Result<()> {
+        self.words.write_padded_bytes(bytes)
+    }
+}
+
+/// A WordRead over a frame written by a [CompressedWriter]
+pub struct CompressedReader {
+    words: WordBuffer,
+}
+
+impl CompressedReader {
+    /// Construct a CompressedReader
+    ///
+    /// Reads and decompresses a whole frame from `inner`.
+    pub fn new<R: WordRead>(mut inner: R) -> Result<Self> {
+        let mut len = 0u32;
+        inner.read_words(core::slice::from_mut(&mut len))?;
+        let compressed = read_padded_bytes(&mut inner, len as usize)?;
+        let (size, block) = lz4_flex::block::uncompressed_size(&compressed)
+            .map_err(|_| Error::DeserializeBadCompression)?;
+        // LZ4 expands a byte of the block to at most 255, so a larger size
+        // is corrupt and isn't allocated.
+        if size > block.len().saturating_mul(255) {
+            return Err(Error::DeserializeBadCompression);
+        }
+        let bytes =
+            lz4_flex::decompress(block, size).map_err(|_| Error::DeserializeBadCompression)?;
+        let words = le_bytes_to_words(&bytes).ok_or(Error::DeserializeBadCompression)?;
+        Ok(CompressedReader {
+            words: WordBuffer::new(words),
+        })
+    }
+}
+
+impl WordRead for CompressedReader {
+    fn read_words(&mut self, words: &mut [u32]) -> risc0_zkvm::serde::Result<()> {
+        self.words.read_words(words)
+    }
+
+    fn read_padded_bytes(&mut self, bytes: &mut [u8]) -> risc0_zkvm::serde::Result<()> {
+        self.words.read_padded_bytes(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::String, vec, vec::Vec};
+
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let input: Vec<(u64, String)> = (0..256).map(|i| (i % 7, "witness".into())).collect();
+        let data = to_vec_compressed(&input).unwrap();
+        assert!(data.len() < crate::to_vec_compact(&input).unwrap().len() / 4);
+        let output: Vec<(u64, String)> = from_slice_compressed(&data).unwrap();
+        assert_eq!(input, output);
+
+        let bytes: Vec<u8> = data.iter().flat_map(|w| w.to_le_bytes()).collect();
+        let output: Vec<(u64, String)> = from_slice_compressed(&bytes).unwrap();
+        assert_eq!(input, output);
+    }
+
+    #[test]
+    fn test_empty() {
+        let data = to_vec_compressed(&()).unwrap();
+        from_slice_compressed::<(), _>(&data).unwrap();
+    }
+
+    #[test]
+    fn test_corrupted() {
+        let mut data = to_vec_compressed(&vec![1u32; 64]).unwrap();
+        data[2] ^= 0xffff_ffff;
+        assert_eq!(
+            Err(Error::DeserializeBadCompression),
+            from_slice_compressed::<Vec<u32>, _>(&data)
+        );
+    }
+
+    #[test]
+    fn test_bad_sizes() {
+        assert_eq!(
+            Err(Error::DeserializeUnexpectedEnd {
+                needed: 0,
+                available: None
+            }),
+            from_slice_compressed::<Vec<u32>, _>(&[u32::MAX, 0])
+        );
+        // A decompressed size of 4 GiB for a single byte of block
+        assert_eq!(
+            Err(Error::DeserializeBadCompression),
+            from_slice_compressed::<Vec<u32>, _>(&[5, u32::MAX, 0])
+        );
+    }
+}
@@ -0,0 +1,244 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+/// Encoding of enum variant tags
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum VariantTag {
+    /// The variant index as a full word
+    #[default]
+    Word,
+    /// The variant index as a byte, packed like a `u8` inside composites
+    ///
+    /// Enums with more than 256 variants fail to serialize in this mode.
+    Byte,
+    /// A 32-bit FNV-1a hash of the variant name as a full word
+    ///
+    /// Unlike the index-based tags, this stays valid when the variants of an
+    /// enum are reordered between the serializing and deserializing builds.
+    NameHash,
+}
+
+/// Hash a variant name for [VariantTag::NameHash]
+pub(crate) const fn variant_name_hash(name: &str) -> u32 {
+    let bytes = name.as_bytes();
+    let mut hash = 0x811c9dc5u32;
+    let mut i = 0;
+    while i < bytes.len() {
+        hash ^= bytes[i] as u32;
+        hash = hash.wrapping_mul(0x01000193);
+        i += 1;
+    }
+    hash
+}
+
+/// Options of the compact encoding
+///
+/// The default configuration produces the standard encoding. A buffer must be
+/// deserialized with the same configuration it was serialized with.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Config {
+    pub(crate) variant_tag: VariantTag,
+    pub(crate) byte_option_tags: bool,
+    pub(crate) pack_top_level: bool,
+    pub(crate) field_count: bool,
+    pub(crate) field_sizes: bool,
+    pub(crate) intern_strings: bool,
+    pub(crate) pad_to: usize,
+    pub(crate) self_describing: bool,
+    pub(crate) human_readable: bool,
+    pub(crate) big_endian: bool,
+    pub(crate) lenient_integers: bool,
+    pub(crate) replace_invalid_chars: bool,
+}
+
+impl Config {
+    /// Construct the default configuration
+    pub const fn new() -> Self {
+        Config {
+            variant_tag: VariantTag::Word,
+            byte_option_tags: false,
+            pack_top_level: false,
+            field_count: false,
+            field_sizes: false,
+            intern_strings: false,
+            pad_to: 0,
+            self_describing: false,
+            human_readable: false,
+            big_endian: false,
+            lenient_integers: false,
+            replace_invalid_chars: false,
+        }
+    }
+
+    /// Set the encoding of enum variant tags
+    pub const fn variant_tag(mut self, variant_tag: VariantTag) -> Self {
+        self.variant_tag = variant_tag;
+        self
+    }
+
+    /// Encode the tags of `Option`s as bytes, packed like a `u8` inside
+    /// composites
+    ///
+    /// Together with [VariantTag::Byte], this lets nested options and small
+    /// enums share words with neighbouring bytes instead of spending a word on
+    /// each tag.
+    pub const fn byte_option_tags(mut self, byte_option_tags: bool) -> Self {
+        self.byte_option_tags = byte_option_tags;
+        self
+    }
+
+    /// Pack bytes at the top level as well as inside composites
+    ///
+    /// This chooses how scalars serialized on their own are laid out: one
+    /// word per scalar, the default, or packed like the fields of a struct.
+    ///
+    /// By default, a `u8` or `bool` serialized on its own takes a whole word,
+    /// and a composite ends its last word of bytes, so that every top-level
+    /// value starts a word of its own. With this option, bytes share words
+    /// across consecutive top-level values too, so a stream of small values
+    /// takes fewer words. Only a word-sized value ends a partially filled
+    /// word, so the last word is only written by
+    /// [Serializer::finish](crate::Serializer::finish).
+    ///
+    /// Either way, a single [Deserializer](crate::Deserializer) with the same
+    /// configuration reads consecutive values back as they were written.
+    /// Other integers narrower than a word take a word at any depth.
+    pub const fn pack_top_level(mut self, pack_top_level: bool) -> Self {
+        self.pack_top_level = pack_top_level;
+        self
+    }
+
+    /// Prefix every struct and struct variant with its field count
+    ///
+    /// This allows a newer type with additional trailing fields to decode
+    /// data produced from an older version of the type. The missing fields
+    /// take their defaults, so they must be marked `#[serde(default)]` (or the
+    /// struct itself must be).
+    pub const fn field_count(mut self, field_count: bool) -> Self {
+        self.field_count = field_count;
+        self
+    }
+
+    /// Prefix every struct field with its size in words
+    ///
+    /// Structs are also prefixed with their field count, as with
+    /// [Config::field_count]. In addition, an older type can skip trailing
+    /// fields it doesn't declare. Each field starts on a word boundary, so
+    /// small values no longer pack across fields.
+    pub const fn field_sizes(mut self, field_sizes: bool) -> Self {
+        self.field_sizes = field_sizes;
+        self
+    }
+
+    /// Encode repeated strings as references to their first occurrence
+    ///
+    /// Every string is preceded by a word that is either zero, for a string
+    /// that is written out in full, or `n + 1` to refer to the `n`-th string
+    /// written out so far. Skipping unknown fields with [Config::field_sizes]
+    /// is not supported in this mode, since a skipped field may contain the
+    /// first occurrence of a string.
+    pub const fn intern_strings(mut self, intern_strings: bool) -> Self {
+        self.intern_strings = intern_strings;
+        self
+    }
+
+    /// Pad the output to a multiple of `words` words
+    ///
+    /// The padding consists of zero words and is written by
+    /// [Serializer::finish](crate::Serializer::finish). It is checked and
+    /// consumed by [Deserializer::finish](crate::Deserializer::finish). A value
+    /// of 0 or 1 disables padding.
+    pub const fn pad_to(mut self, words: usize) -> Self {
+        self.pad_to = words;
+        self
+    }
+
+    /// Precede every value with a marker word describing its type
+    ///
+    /// This makes the encoding self-describing, so that it supports
+    /// `deserialize_any` and with it untagged enums, `serde::de::IgnoredAny`
+    /// and similar schema-less decoding. The markers cost a word per value,
+    /// and small values no longer pack.
+    ///
+    /// `deserialize_any` can't decode variant tags written with
+    /// [VariantTag::NameHash].
+    pub const fn self_describing(mut self, self_describing: bool) -> Self {
+        self.self_describing = self_describing;
+        self
+    }
+
+    /// Report the format as human-readable to the types being serialized and
+    /// deserialized
+    ///
+    /// Types such as IP addresses, UUIDs and timestamps use their string form
+    /// in human-readable formats and a binary form otherwise. This selects the
+    /// representation that matches the implementation on the other side; the
+    /// binary form is usually the smaller one.
+    pub const fn human_readable(mut self, human_readable: bool) -> Self {
+        self.human_readable = human_readable;
+        self
+    }
+
+    /// Order the bytes of every word from the most significant one
+    ///
+    /// By default, bytes pack into a word from its least significant byte, and
+    /// a 64- or 128-bit integer starts with its least significant word, so
+    /// that the words written out in little-endian order hold the bytes in
+    /// sequence. With this option, bytes pack from the most significant byte
+    /// and integers start with their most significant word, for consumers
+    /// that read the words in big-endian order. Words written as such, such
+    /// as a `u32`, keep their value.
+    ///
+    /// Strings and byte arrays can't be borrowed from the input in this mode.
+    pub const fn big_endian(mut self, big_endian: bool) -> Self {
+        self.big_endian = big_endian;
+        self
+    }
+
+    /// Decode integers written with a different width or signedness
+    ///
+    /// With [Config::self_describing], the marker of an integer records the
+    /// type it was written as. This option lets a field that changed from
+    /// `u32` to `u64`, or from `i64` to `i32`, still decode data written
+    /// before the change: an integer is converted to the type being decoded
+    /// if its value fits, and fails with
+    /// [Error::DeserializeLossyInteger](crate::Error::DeserializeLossyInteger)
+    /// otherwise. The encoding is unaffected, so only the decoding side needs
+    /// the option. Without markers, the width of an integer isn't recorded and
+    /// this option has no effect.
+    pub const fn lenient_integers(mut self, lenient_integers: bool) -> Self {
+        self.lenient_integers = lenient_integers;
+        self
+    }
+
+    /// Decode invalid chars and invalid utf-8 in strings as U+FFFD
+    ///
+    /// By default, a `char` that isn't a unicode scalar value, such as a
+    /// surrogate or a value past U+10FFFF, fails with
+    /// [Error::DeserializeBadChar](crate::Error::DeserializeBadChar), and a
+    /// string that isn't valid utf-8 fails with
+    /// [Error::DeserializeBadUtf8](crate::Error::DeserializeBadUtf8). With
+    /// this option, such a char decodes as the replacement character, as does
+    /// every invalid sequence of a string, so that possibly corrupted text
+    /// such as diagnostic messages decodes on a best-effort basis. Like
+    /// [Config::lenient_integers], it only affects decoding.
+    pub const fn replace_invalid_chars(mut self, replace_invalid_chars: bool) -> Self {
+        self.replace_invalid_chars = replace_invalid_chars;
+        self
+    }
+
+    pub(crate) const fn counts_fields(&self) -> bool {
+        self.field_count || self.field_sizes
+    }
+}
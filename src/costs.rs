@@ -0,0 +1,190 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::{collections::BTreeMap, vec::Vec};
+use core::fmt::{Display, Formatter};
+
+/// The words contributed by a struct field
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct FieldCost {
+    /// Number of words the field took, over all of its occurrences
+    pub words: usize,
+    /// Number of times the field was serialized
+    pub count: usize,
+}
+
+/// The words contributed by every struct field serialized
+///
+/// Fields are keyed by their path, the names of the enclosing fields followed
+/// by the name of the field itself. The words of a field include those of its
+/// nested fields, as well as its size word with [Config::field_sizes]. A byte
+/// packed into the word of an earlier byte costs nothing.
+///
+/// The [Display] impl lists the fields by decreasing cost.
+///
+/// [Config::field_sizes]: crate::Config::field_sizes
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct FieldCosts {
+    fields: BTreeMap<Vec<&'static str>, FieldCost>,
+}
+
+impl FieldCosts {
+    /// The cost of the field at `path`, if it was serialized
+    pub fn get(&self, path: &[&'static str]) -> Option<FieldCost> {
+        self.fields.get(path).copied()
+    }
+
+    /// Iterate over the fields in the order of their paths
+    pub fn iter(&self) -> impl Iterator<Item = (&[&'static str], FieldCost)> {
+        self.fields
+            .iter()
+            .map(|(path, cost)| (path.as_slice(), *cost))
+    }
+}
+
+impl Display for FieldCosts {
+    fn fmt(&self, formatter: &mut Formatter) -> core::fmt::Result {
+        let mut fields: Vec<_> = self.iter().collect();
+        fields.sort_by_key(|(_, cost)| core::cmp::Reverse(cost.words));
+        for (path, cost) in fields {
+            for (i, name) in path.iter().enumerate() {
+                if i > 0 {
+                    formatter.write_str(".")?;
+                }
+                formatter.write_str(name)?;
+            }
+            writeln!(formatter, ": {} words ({} times)", cost.words, cost.count)?;
+        }
+        Ok(())
+    }
+}
+
+/// Records the costs of fields as a Serializer writes them
+#[derive(Default)]
+pub(crate) struct Recorder {
+    path: Vec<&'static str>,
+    costs: FieldCosts,
+}
+
+impl Recorder {
+    /// Start a field nested in the current one
+    pub(crate) fn enter(&mut self, name: &'static str) {
+        self.path.push(name);
+    }
+
+    /// End the current field, which took `words`
+    pub(crate) fn exit(&mut self, words: usize) {
+        let cost = self.costs.fields.entry(self.path.clone()).or_default();
+        cost.words += words;
+        cost.count += 1;
+        self.path.pop();
+    }
+
+    pub(crate) fn costs(&self) -> &FieldCosts {
+        &self.costs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::ToString, vec, vec::Vec};
+
+    use serde::Serialize;
+
+    use super::FieldCost;
+    use crate::{Config, Serializer};
+
+    #[derive(Serialize)]
+    struct Input {
+        value: u64,
+        flag: u8,
+        tag: u8,
+    }
+
+    #[derive(Serialize)]
+    struct Block {
+        id: u32,
+        inputs: Vec<Input>,
+        seal: [u32; 4],
+    }
+
+    fn block() -> Block {
+        let input = |value| Input {
+            value,
+            flag: 1,
+            tag: 2,
+        };
+        Block {
+            id: 7,
+            inputs: vec![input(1), input(2)],
+            seal: [0; 4],
+        }
+    }
+
+    fn record(config: Config) -> (Vec<u32>, super::FieldCosts) {
+        let mut data = Vec::new();
+        let mut serializer = Serializer::with_config(&mut data, config);
+        serializer.record_field_costs();
+        block().serialize(&mut serializer).unwrap();
+        let costs = serializer.field_costs().unwrap().clone();
+        (data, costs)
+    }
+
+    fn cost(words: usize, count: usize) -> Option<FieldCost> {
+        Some(FieldCost { words, count })
+    }
+
+    #[test]
+    fn test_costs() {
+        let (data, costs) = record(Config::new());
+        assert_eq!(crate::to_vec_compact(&block()).unwrap(), data);
+        assert_eq!(cost(1, 1), costs.get(&["id"]));
+        // The length word and two inputs of three words each
+        assert_eq!(cost(7, 1), costs.get(&["inputs"]));
+        assert_eq!(cost(4, 2), costs.get(&["inputs", "value"]));
+        assert_eq!(cost(2, 2), costs.get(&["inputs", "flag"]));
+        assert_eq!(cost(0, 2), costs.get(&["inputs", "tag"]));
+        assert_eq!(cost(4, 1), costs.get(&["seal"]));
+        assert_eq!(None, costs.get(&["value"]));
+        assert_eq!(
+            "inputs: 7 words (1 times)\n\
+             inputs.value: 4 words (2 times)\n\
+             seal: 4 words (1 times)\n\
+             inputs.flag: 2 words (2 times)\n\
+             id: 1 words (1 times)\n\
+             inputs.tag: 0 words (2 times)\n",
+            costs.to_string()
+        );
+    }
+
+    #[test]
+    fn test_field_sizes() {
+        let (data, costs) = record(Config::new().field_sizes(true));
+        let total = costs.get(&["id"]).unwrap().words
+            + costs.get(&["inputs"]).unwrap().words
+            + costs.get(&["seal"]).unwrap().words;
+        // Everything but the field count of the outer struct
+        assert_eq!(data.len() - 1, total);
+        assert_eq!(cost(2, 1), costs.get(&["id"]));
+        assert_eq!(cost(4, 2), costs.get(&["inputs", "flag"]));
+    }
+
+    #[test]
+    fn test_disabled() {
+        let mut data = Vec::new();
+        let mut serializer = Serializer::new(&mut data);
+        block().serialize(&mut serializer).unwrap();
+        assert!(serializer.field_costs().is_none());
+    }
+}
@@ -0,0 +1,177 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Delta encoding of sorted integer sequences
+//!
+//! For use with `#[serde(with = "l2r0_small_serde::delta")]` on `Vec<u32>` or
+//! `Vec<u64>` fields whose values are sorted in non-decreasing order. Each
+//! value is encoded as the LEB128 varint of its difference to the previous
+//! value, and the varints are written as a byte run.
+//! ```rust
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct Blocks {
+//!     #[serde(with = "l2r0_small_serde::delta")]
+//!     numbers: Vec<u64>,
+//! }
+//! ```
+
+use alloc::vec::Vec;
+use core::{fmt, marker::PhantomData};
+
+use serde::{
+    de::{SeqAccess, Visitor},
+    ser::Error as _,
+    Deserializer, Serializer,
+};
+
+/// Serialize sorted `values` as varint deltas
+pub fn serialize<T, S>(values: &[T], serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: Copy + Into<u64>,
+    S: Serializer,
+{
+    let mut bytes = Vec::with_capacity(values.len());
+    let mut prev = 0u64;
+    for &value in values {
+        let value: u64 = value.into();
+        let mut delta = value
+            .checked_sub(prev)
+            .ok_or_else(|| S::Error::custom("delta: values are not sorted"))?;
+        prev = value;
+        while delta >= 0x80 {
+            bytes.push((delta as u8) | 0x80);
+            delta >>= 7;
+        }
+        bytes.push(delta as u8);
+    }
+    serializer.serialize_bytes(&bytes)
+}
+
+/// Deserialize varint deltas into the sorted values
+pub fn deserialize<'de, T, D>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    T: TryFrom<u64>,
+    D: Deserializer<'de>,
+{
+    deserializer.deserialize_byte_buf(DeltaVisitor(PhantomData))
+}
+
+struct DeltaVisitor<T>(PhantomData<T>);
+
+impl<T: TryFrom<u64>> DeltaVisitor<T> {
+    fn decode<E: serde::de::Error>(bytes: impl Iterator<Item = u8>) -> Result<Vec<T>, E> {
+        let mut values = Vec::new();
+        let mut prev = 0u64;
+        let mut delta = 0u64;
+        let mut shift = 0u32;
+        for byte in bytes {
+            if shift >= 64 || (shift == 63 && byte & 0x7e != 0) {
+                return Err(E::custom("delta: varint overflow"));
+            }
+            delta |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 != 0 {
+                shift += 7;
+                continue;
+            }
+            prev = prev
+                .checked_add(delta)
+                .ok_or_else(|| E::custom("delta: value overflow"))?;
+            values.push(T::try_from(prev).map_err(|_| E::custom("delta: value overflow"))?);
+            delta = 0;
+            shift = 0;
+        }
+        if shift != 0 {
+            return Err(E::custom("delta: truncated varint"));
+        }
+        Ok(values)
+    }
+}
+
+impl<'de, T: TryFrom<u64>> Visitor<'de> for DeltaVisitor<T> {
+    type Value = Vec<T>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("delta-encoded bytes")
+    }
+
+    fn visit_bytes<E: serde::de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+        Self::decode(v.iter().copied())
+    }
+
+    fn visit_byte_buf<E: serde::de::Error>(self, v: Vec<u8>) -> Result<Self::Value, E> {
+        Self::decode(v.into_iter())
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut bytes = Vec::with_capacity(crate::cautious_capacity(seq.size_hint()));
+        while let Some(byte) = seq.next_element::<u8>()? {
+            bytes.push(byte);
+        }
+        Self::decode(bytes.into_iter())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{vec, vec::Vec};
+
+    use serde::{Deserialize, Serialize};
+
+    use crate::{from_slice_compact, to_vec_compact};
+
+    #[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+    struct Indices {
+        #[serde(with = "crate::delta")]
+        small: Vec<u32>,
+        #[serde(with = "crate::delta")]
+        large: Vec<u64>,
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let input = Indices {
+            small: vec![3, 4, 4, 130, 1000],
+            large: vec![18_000_000, 18_000_001, 18_000_096, u64::MAX],
+        };
+        let data = to_vec_compact(&input).unwrap();
+        // Deltas of 1 + 1 + 1 + 1 + 2 bytes and 4 + 1 + 1 + 10 bytes
+        assert_eq!([6, 0x7e000103, 0x000006e6, 16].as_slice(), &data[..4]);
+        assert_eq!(8, data.len());
+        let output: Indices = from_slice_compact(&data).unwrap();
+        assert_eq!(input, output);
+    }
+
+    #[test]
+    fn test_unsorted() {
+        let input = Indices {
+            small: vec![2, 1],
+            large: vec![],
+        };
+        assert!(to_vec_compact(&input).is_err());
+    }
+
+    #[test]
+    fn test_overflow() {
+        let input = Indices {
+            small: vec![],
+            large: vec![u32::MAX as u64 + 1],
+        };
+        let data = to_vec_compact(&input).unwrap();
+        // Swap the fields so that the large value lands in the `u32` field.
+        let data = [&data[1..], &data[..1]].concat();
+        assert!(from_slice_compact::<Indices, _>(&data).is_err());
+    }
+}
@@ -12,13 +12,20 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use alloc::{string::String, vec};
+use alloc::{borrow::Cow, string::String, vec, vec::Vec};
 
 use bytemuck::Pod;
 use risc0_zkvm::serde::WordRead;
-use serde::de::{DeserializeOwned, DeserializeSeed, IntoDeserializer, Visitor};
+use serde::de::{Deserialize, DeserializeOwned, DeserializeSeed, IntoDeserializer, Visitor};
 
-use super::err::{Error, Result};
+#[cfg(feature = "tracing")]
+use super::trace;
+use super::{
+    align_up,
+    config::{variant_name_hash, Config, VariantTag},
+    err::{Error, Result},
+    marker, words, MAX_DEPTH, WORD_SIZE,
+};
 
 /// Deserialize a slice into the specified type.
 ///
@@ -26,30 +33,268 @@ use super::err::{Error, Result};
 /// possible, such as if `slice` is not the serialized form of an object of type
 /// `T`.
 pub fn from_slice_compact<T: DeserializeOwned, P: Pod>(slice: &[P]) -> Result<T> {
+    from_slice_compact_with_config(slice, Config::default())
+}
+
+/// Deserialize a slice into the specified type using the given configuration.
+///
+/// `config` must match the configuration `slice` was serialized with.
+pub fn from_slice_compact_with_config<T: DeserializeOwned, P: Pod>(
+    slice: &[P],
+    config: Config,
+) -> Result<T> {
+    #[cfg(feature = "tracing")]
+    let _span = trace::deserialize_span::<T>(core::mem::size_of_val(slice) / WORD_SIZE).entered();
     match bytemuck::try_cast_slice(slice) {
         Ok(slice) => {
-            let mut deserializer = Deserializer::new(slice);
-            T::deserialize(&mut deserializer)
+            let mut deserializer = Deserializer::from_words_with_config(slice, config);
+            let value = T::deserialize(&mut deserializer)?;
+            deserializer.finish()?;
+            Ok(value)
+        }
+        // P is u8 or another value without word-alignment. Data must be copied.
+        Err(bytemuck::PodCastError::TargetAlignmentGreaterAndInputNotAligned) => {
+            let vec = bytemuck::allocation::pod_collect_to_vec::<P, u32>(slice);
+            let mut deserializer = Deserializer::from_words_with_config(vec.as_slice(), config);
+            let value = T::deserialize(&mut deserializer)?;
+            deserializer.finish()?;
+            Ok(value)
+        }
+        Err(ref e) => panic!("failed to cast or read slice as [u32]: {}", e),
+    }
+}
+
+/// Deserialize a slice produced by a trusted serializer.
+///
+/// Decodes like [from_slice_compact], but skips the validation of strings,
+/// chars, lengths and the padding of packed bytes, which saves cycles in a
+/// guest reading input from a trusted host. Reads past the end of `slice`
+/// still fail.
+///
+/// # Safety
+///
+/// `slice` must have been produced by [to_vec_compact](crate::to_vec_compact)
+/// or another serializer with the default configuration, from a value of type
+/// `T`. Decoding anything else may create invalid strings or chars, which is
+/// undefined behavior.
+#[cfg(feature = "unchecked")]
+pub unsafe fn from_slice_unchecked<T: DeserializeOwned, P: Pod>(slice: &[P]) -> Result<T> {
+    from_slice_unchecked_with_config(slice, Config::default())
+}
+
+/// Deserialize a slice produced by a trusted serializer using the given
+/// configuration.
+///
+/// # Safety
+///
+/// `slice` must have been produced by a serializer with `config`, from a value
+/// of type `T`. See [from_slice_unchecked].
+#[cfg(feature = "unchecked")]
+pub unsafe fn from_slice_unchecked_with_config<T: DeserializeOwned, P: Pod>(
+    slice: &[P],
+    config: Config,
+) -> Result<T> {
+    let words = match bytemuck::try_cast_slice(slice) {
+        Ok(words) => Cow::Borrowed(words),
+        Err(bytemuck::PodCastError::TargetAlignmentGreaterAndInputNotAligned) => {
+            Cow::Owned(bytemuck::allocation::pod_collect_to_vec::<P, u32>(slice))
+        }
+        Err(ref e) => panic!("failed to cast or read slice as [u32]: {}", e),
+    };
+    let mut deserializer = Deserializer::from_words_with_config(&words, config);
+    // SAFETY: The caller guarantees that the input is trusted.
+    unsafe { deserializer.trust_input() };
+    let value = T::deserialize(&mut deserializer)?;
+    deserializer.finish()?;
+    Ok(value)
+}
+
+/// Deserialize a slice into an existing value.
+///
+/// Decodes like [from_slice_compact], but reuses the allocations of `place`
+/// where the `Deserialize` impl of `T` supports it, such as the buffers of
+/// `Vec`s and `String`s. Derived impls of structs support it when the
+/// `deserialize_in_place` feature of `serde_derive` is enabled.
+pub fn from_slice_compact_in_place<T: DeserializeOwned, P: Pod>(
+    slice: &[P],
+    place: &mut T,
+) -> Result<()> {
+    from_slice_compact_in_place_with_config(slice, place, Config::default())
+}
+
+/// Deserialize a slice into an existing value using the given configuration.
+///
+/// `config` must match the configuration `slice` was serialized with.
+pub fn from_slice_compact_in_place_with_config<T: DeserializeOwned, P: Pod>(
+    slice: &[P],
+    place: &mut T,
+    config: Config,
+) -> Result<()> {
+    fn decode<T: DeserializeOwned>(slice: &[u32], place: &mut T, config: Config) -> Result<()> {
+        let mut deserializer = Deserializer::from_words_with_config(slice, config);
+        deserializer.scratch_strings = true;
+        T::deserialize_in_place(&mut deserializer, place)?;
+        deserializer.finish()
+    }
+
+    match bytemuck::try_cast_slice(slice) {
+        Ok(slice) => decode(slice, place, config),
+        // P is u8 or another value without word-alignment. Data must be copied.
+        Err(bytemuck::PodCastError::TargetAlignmentGreaterAndInputNotAligned) => {
+            let vec = bytemuck::allocation::pod_collect_to_vec::<P, u32>(slice);
+            decode(&vec, place, config)
+        }
+        Err(ref e) => panic!("failed to cast or read slice as [u32]: {}", e),
+    }
+}
+
+/// Deserialize a slice of words into a type that may borrow from it.
+///
+/// Unlike [from_slice_compact], the value can hold references into `words`,
+/// such as a `&[u32]` decoded with [words::borrowed](crate::words::borrowed).
+pub fn from_words<'de, T: Deserialize<'de>>(words: &'de [u32]) -> Result<T> {
+    from_words_with_config(words, Config::default())
+}
+
+/// Deserialize a slice of words into a type that may borrow from it, using the
+/// given configuration.
+///
+/// `config` must match the configuration `words` was serialized with.
+pub fn from_words_with_config<'de, T: Deserialize<'de>>(
+    words: &'de [u32],
+    config: Config,
+) -> Result<T> {
+    let mut deserializer = Deserializer::from_words_with_config(words, config);
+    let value = T::deserialize(&mut deserializer)?;
+    deserializer.finish()?;
+    Ok(value)
+}
+
+/// Check that a slice decodes as the specified type.
+///
+/// Succeeds if `slice` holds exactly one value of type `T`, serialized with
+/// `config`, and nothing after it. The compact encoding is not
+/// self-describing, so the value is decoded with the `Deserialize` impl of `T`
+/// and dropped.
+pub fn validate<T: DeserializeOwned, P: Pod>(slice: &[P], config: Config) -> Result<()> {
+    fn check<T: DeserializeOwned>(slice: &[u32], config: Config) -> Result<()> {
+        let mut deserializer = Deserializer::from_words_with_config(slice, config);
+        T::deserialize(&mut deserializer)?;
+        deserializer.finish()?;
+        match deserializer.reader.remaining() {
+            Some(0) => {}
+            Some(count) => return Err(Error::DeserializeTrailingWords { count }),
+            None => unreachable!(),
         }
+        Ok(())
+    }
+
+    match bytemuck::try_cast_slice(slice) {
+        Ok(slice) => check::<T>(slice, config),
         // P is u8 or another value without word-alignment. Data must be copied.
         Err(bytemuck::PodCastError::TargetAlignmentGreaterAndInputNotAligned) => {
             let vec = bytemuck::allocation::pod_collect_to_vec::<P, u32>(slice);
-            let mut deserializer = Deserializer::new(vec.as_slice());
-            T::deserialize(&mut deserializer)
+            check::<T>(&vec, config)
         }
         Err(ref e) => panic!("failed to cast or read slice as [u32]: {}", e),
     }
 }
 
+/// Check that `bytes` are valid utf-8, unless they are trusted to be
+fn decode_utf8(bytes: &[u8], trusted: bool) -> Result<&str> {
+    if trusted {
+        // SAFETY: The caller of `from_slice_unchecked` guarantees that the
+        // input was produced by the serializer, which only writes valid utf-8.
+        return Ok(unsafe { core::str::from_utf8_unchecked(bytes) });
+    }
+    core::str::from_utf8(bytes).map_err(|e| Error::DeserializeBadUtf8 {
+        offset: e.valid_up_to(),
+    })
+}
+
+/// Check that `bytes` are valid utf-8 like [decode_utf8], unless `replace`
+/// is set, in which case invalid sequences are replaced with U+FFFD
+fn decode_text(bytes: &[u8], trusted: bool, replace: bool) -> Result<Cow<'_, str>> {
+    match decode_utf8(bytes, trusted) {
+        Ok(string) => Ok(Cow::Borrowed(string)),
+        Err(_) if replace => Ok(String::from_utf8_lossy(bytes)),
+        Err(e) => Err(e),
+    }
+}
+
+/// A WordRead over the words yielded by an iterator
+///
+/// This lets a [Deserializer] decode words as they arrive, for example from a
+/// streaming RPC, without collecting them first. Reading past the end of the
+/// iterator fails with
+/// [DeserializeUnexpectedEnd](risc0_zkvm::serde::Error::DeserializeUnexpectedEnd).
+/// ```rust
+/// use l2r0_small_serde::{to_vec_compact, Deserializer, IterReader};
+/// use serde::Deserialize;
+///
+/// let chunks = vec![to_vec_compact("streamed").unwrap(), to_vec_compact(&7u64).unwrap()];
+/// let reader = IterReader::new(chunks.into_iter().flatten());
+/// let mut deserializer = Deserializer::new(reader);
+/// assert_eq!("streamed", String::deserialize(&mut deserializer).unwrap());
+/// assert_eq!(7, u64::deserialize(&mut deserializer).unwrap());
+/// ```
+pub struct IterReader<I: Iterator<Item = u32>> {
+    iter: I,
+}
+
+impl<I: Iterator<Item = u32>> IterReader<I> {
+    /// Construct an IterReader that reads the words of `iter`
+    pub fn new(iter: I) -> Self {
+        IterReader { iter }
+    }
+
+    /// Get back the iterator, which yields the words not yet read
+    pub fn into_inner(self) -> I {
+        self.iter
+    }
+
+    fn next_word(&mut self) -> risc0_zkvm::serde::Result<u32> {
+        self.iter
+            .next()
+            .ok_or(risc0_zkvm::serde::Error::DeserializeUnexpectedEnd)
+    }
+}
+
+impl<I: Iterator<Item = u32>> WordRead for IterReader<I> {
+    fn read_words(&mut self, words: &mut [u32]) -> risc0_zkvm::serde::Result<()> {
+        for word in words {
+            *word = self.next_word()?;
+        }
+        Ok(())
+    }
+
+    fn read_padded_bytes(&mut self, bytes: &mut [u8]) -> risc0_zkvm::serde::Result<()> {
+        // As with a slice, the padding of the last word is dropped unchecked.
+        for chunk in bytes.chunks_mut(WORD_SIZE) {
+            let word = self.next_word()?.to_le_bytes();
+            chunk.copy_from_slice(&word[..chunk.len()]);
+        }
+        Ok(())
+    }
+}
+
 #[derive(Default)]
 struct ByteHandler {
     pub status: usize,
     pub buffer: [u8; 3],
+    /// Whether the unused bytes of a word are trusted to be zero
+    #[cfg(feature = "unchecked")]
+    pub trusted: bool,
 }
 
 impl ByteHandler {
     #[inline]
     fn reset(&mut self) -> Result<()> {
+        #[cfg(feature = "unchecked")]
+        if self.trusted {
+            self.status = 0;
+            return Ok(());
+        }
         if self.status == 1 {
             if self.buffer[0] != 0 || self.buffer[1] != 0 || self.buffer[2] != 0 {
                 return Err(Error::DeserializeBadByte);
@@ -58,17 +303,15 @@ impl ByteHandler {
             if self.buffer[1] != 0 || self.buffer[2] != 0 {
                 return Err(Error::DeserializeBadByte);
             }
-        } else if self.status == 3 {
-            if self.buffer[2] != 0 {
-                return Err(Error::DeserializeBadByte);
-            }
+        } else if self.status == 3 && self.buffer[2] != 0 {
+            return Err(Error::DeserializeBadByte);
         }
         self.status = 0;
         Ok(())
     }
 
     #[inline]
-    fn handle_byte<R: WordRead>(&mut self, reader: &mut R) -> Result<u8> {
+    fn handle_byte<R: WordRead>(&mut self, reader: &mut Source<'_, R>) -> Result<u8> {
         if self.status != 0 {
             let res = self.buffer[self.status - 1];
             self.status = (self.status + 1) % 4;
@@ -76,6 +319,9 @@ impl ByteHandler {
         } else {
             let mut val = 0u32;
             reader.read_words(core::slice::from_mut(&mut val))?;
+            if reader.big_endian {
+                val = val.swap_bytes();
+            }
             self.buffer = [
                 (val >> 8 & 0xff) as u8,
                 (val >> 16 & 0xff) as u8,
@@ -87,34 +333,158 @@ impl ByteHandler {
     }
 }
 
+/// Input of a Deserializer
+///
+/// Keeps track of the number of words consumed, which is needed to check and
+/// skip sized struct fields. Reads from `words` instead of `reader` when the
+/// input is a borrowed slice, so that runs of words can be lent out.
+struct Source<'de, R: WordRead> {
+    reader: R,
+    words: Option<&'de [u32]>,
+    position: usize,
+    /// Whether bytes pack from the most significant byte, as with
+    /// [Config::big_endian]
+    big_endian: bool,
+    /// Whether the input is trusted to be valid, which skips validation
+    #[cfg(feature = "unchecked")]
+    trusted: bool,
+}
+
+impl<'de, R: WordRead> Source<'de, R> {
+    /// Number of words left, if the input is a borrowed slice
+    fn remaining(&self) -> Option<usize> {
+        self.words.map(|words| words.len())
+    }
+
+    /// Fail unless `len` more words may be available
+    ///
+    /// `requested` is the length found in the input that implies `len`.
+    fn check_len(&self, len: usize, requested: usize) -> Result<()> {
+        #[cfg(feature = "unchecked")]
+        if self.trusted {
+            return Ok(());
+        }
+        match self.remaining() {
+            Some(remaining) if remaining < len => {
+                Err(Error::DeserializeLengthOverflow { requested })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Take the next `len` words from a borrowed input
+    fn borrow_words(&mut self, len: usize) -> Result<&'de [u32]> {
+        let words = self.words.as_mut().ok_or(Error::NotSupported)?;
+        if words.len() < len {
+            return Err(Error::DeserializeUnexpectedEnd {
+                needed: len,
+                available: Some(words.len()),
+            });
+        }
+        let (head, tail) = words.split_at(len);
+        *words = tail;
+        self.position += len;
+        Ok(head)
+    }
+
+    /// Read `len` words with `read`, adding context to a failure
+    fn read_with<F>(&mut self, len: usize, read: F) -> Result<()>
+    where
+        F: FnOnce(&mut dyn WordRead) -> risc0_zkvm::serde::Result<()>,
+    {
+        let available = self.remaining();
+        let res = match &mut self.words {
+            Some(input) => read(input),
+            None => read(&mut self.reader),
+        };
+        match res {
+            Ok(()) => {
+                self.position += len;
+                Ok(())
+            }
+            Err(risc0_zkvm::serde::Error::DeserializeUnexpectedEnd) => {
+                Err(Error::DeserializeUnexpectedEnd {
+                    needed: len,
+                    available,
+                })
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn read_words(&mut self, words: &mut [u32]) -> Result<()> {
+        self.read_with(words.len(), |reader| reader.read_words(words))
+    }
+
+    fn read_padded_bytes(&mut self, bytes: &mut [u8]) -> Result<()> {
+        let len = align_up(bytes.len(), WORD_SIZE) / WORD_SIZE;
+        if !self.big_endian {
+            return self.read_with(len, |reader| reader.read_padded_bytes(bytes));
+        }
+        self.read_with(len, |reader| {
+            let mut buffer = [0u32; 64];
+            for chunk in bytes.chunks_mut(buffer.len() * WORD_SIZE) {
+                let words = &mut buffer[..chunk.len().div_ceil(WORD_SIZE)];
+                reader.read_words(words)?;
+                for (bytes, word) in chunk.chunks_mut(WORD_SIZE).zip(words.iter()) {
+                    bytes.copy_from_slice(&word.to_be_bytes()[..bytes.len()]);
+                }
+            }
+            Ok(())
+        })
+    }
+}
+
 /// Enables deserializing from a WordRead
 pub struct Deserializer<'de, R: WordRead + 'de> {
-    reader: R,
+    reader: Source<'de, R>,
     byte_handler: ByteHandler,
+    config: Config,
+    /// Number of composites being read, as tracked by the serializer
+    depth: u16,
+    /// Strings read in full so far when interning
+    strings: Vec<String>,
+    /// Marker read ahead by `deserialize_any`
+    marker: Option<u32>,
+    /// Whether strings are passed to visitors borrowed from `scratch`, which
+    /// lets in-place and arena deserialization avoid allocating them
+    scratch_strings: bool,
+    scratch: Vec<u8>,
     phantom: core::marker::PhantomData<&'de ()>,
 }
 
 struct SeqAccess<'a, 'de, R: WordRead + 'de> {
     deserializer: &'a mut Deserializer<'de, R>,
     len: usize,
+    sized: bool,
 }
 
-impl<'de, 'a, R: WordRead + 'de> serde::de::SeqAccess<'de> for SeqAccess<'a, 'de, R> {
+impl<'de, R: WordRead + 'de> serde::de::SeqAccess<'de> for SeqAccess<'_, 'de, R> {
     type Error = Error;
 
     fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
     where
         T: DeserializeSeed<'de>,
     {
-        if self.len > 0 {
-            self.len -= 1;
-            Ok(Some(DeserializeSeed::deserialize(
+        if self.len == 0 {
+            return Ok(None);
+        }
+        self.len -= 1;
+        if !self.sized {
+            return Ok(Some(DeserializeSeed::deserialize(
                 seed,
                 &mut *self.deserializer,
-            )?))
-        } else {
-            Ok(None)
+            )?));
         }
+        let de = &mut *self.deserializer;
+        let size = de.try_take_word()? as usize;
+        let start = de.reader.position;
+        let value = DeserializeSeed::deserialize(seed, &mut *de)?;
+        de.byte_handler.reset()?;
+        if de.reader.position - start != size {
+            return Err(Error::DeserializeBadFieldSize);
+        }
+        Ok(Some(value))
     }
 
     fn size_hint(&self) -> Option<usize> {
@@ -122,10 +492,11 @@ impl<'de, 'a, R: WordRead + 'de> serde::de::SeqAccess<'de> for SeqAccess<'a, 'de
     }
 }
 
-impl<'de, 'a, R: WordRead + 'de> serde::de::VariantAccess<'de> for &'a mut Deserializer<'de, R> {
+impl<'de, R: WordRead + 'de> serde::de::VariantAccess<'de> for &mut Deserializer<'de, R> {
     type Error = Error;
 
     fn unit_variant(self) -> Result<()> {
+        self.expect_marker(marker::UNIT)?;
         Ok(())
     }
 
@@ -142,19 +513,75 @@ impl<'de, 'a, R: WordRead + 'de> serde::de::VariantAccess<'de> for &'a mut Deser
         fields: &'static [&'static str],
         visitor: V,
     ) -> Result<V::Value> {
-        serde::de::Deserializer::deserialize_tuple(self, fields.len(), visitor)
+        self.deserialize_fields(fields.len(), visitor)
     }
 }
 
-impl<'de, 'a, R: WordRead + 'de> serde::de::EnumAccess<'de> for &'a mut Deserializer<'de, R> {
-    type Error = Error;
-    type Variant = Self;
+struct EnumAccess<'a, 'de, R: WordRead + 'de> {
+    deserializer: &'a mut Deserializer<'de, R>,
+    variants: &'static [&'static str],
+}
 
-    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self)> {
-        let tag = self.try_take_word()?;
+impl<'a, 'de: 'a, R: WordRead + 'de> serde::de::EnumAccess<'de> for EnumAccess<'a, 'de, R> {
+    type Error = Error;
+    type Variant = &'a mut Deserializer<'de, R>;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self::Variant)> {
+        let de = self.deserializer;
+        let tag = match de.config.variant_tag {
+            VariantTag::Word => de.try_take_word()?,
+            VariantTag::Byte => de.byte_handler.handle_byte(&mut de.reader)? as u32,
+            VariantTag::NameHash => {
+                let hash = de.try_take_word()?;
+                let mut matches = self
+                    .variants
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, name)| variant_name_hash(name) == hash);
+                match (matches.next(), matches.next()) {
+                    (Some((index, _)), None) => index as u32,
+                    _ => return Err(Error::DeserializeBadVariant),
+                }
+            }
+        };
         let var =
             DeserializeSeed::deserialize(seed, IntoDeserializer::<Error>::into_deserializer(tag))?;
-        Ok((var, self))
+        Ok((var, de))
+    }
+}
+
+/// Presents a variant to `deserialize_any` as a map with a single entry from
+/// the variant index to the contents
+struct VariantMapAccess<'a, 'de, R: WordRead + 'de> {
+    deserializer: &'a mut Deserializer<'de, R>,
+    done: bool,
+}
+
+impl<'a, 'de: 'a, R: WordRead + 'de> serde::de::MapAccess<'de> for VariantMapAccess<'a, 'de, R> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>> {
+        if self.done {
+            return Ok(None);
+        }
+        self.done = true;
+        let de = &mut *self.deserializer;
+        let tag = match de.config.variant_tag {
+            VariantTag::Word => de.try_take_word()?,
+            VariantTag::Byte => de.byte_handler.handle_byte(&mut de.reader)? as u32,
+            VariantTag::NameHash => return Err(Error::NotSupported),
+        };
+        let key =
+            DeserializeSeed::deserialize(seed, IntoDeserializer::<Error>::into_deserializer(tag))?;
+        Ok(Some(key))
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value> {
+        DeserializeSeed::deserialize(seed, &mut *self.deserializer)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(!self.done as usize)
     }
 }
 
@@ -187,51 +614,465 @@ impl<'a, 'de: 'a, R: WordRead + 'de> serde::de::MapAccess<'de> for MapAccess<'a,
     }
 }
 
+impl<'de> Deserializer<'de, &'de [u32]> {
+    /// Construct a Deserializer over borrowed words
+    ///
+    /// Values deserialized from it may borrow from `words`.
+    pub fn from_words(words: &'de [u32]) -> Self {
+        Self::from_words_with_config(words, Config::default())
+    }
+
+    /// Construct a Deserializer over borrowed words with a configuration
+    pub fn from_words_with_config(words: &'de [u32], config: Config) -> Self {
+        let mut deserializer = Self::with_config(&[], config);
+        deserializer.reader.words = Some(words);
+        deserializer
+    }
+}
+
 impl<'de, R: WordRead + 'de> Deserializer<'de, R> {
     /// Construct a Deserializer
     ///
     /// Creates a deserializer for deserializing from the given WordWred
     pub fn new(reader: R) -> Self {
+        Self::with_config(reader, Config::default())
+    }
+
+    /// Construct a Deserializer with a configuration
+    ///
+    /// Creates a deserializer for deserializing from the given WordRead using
+    /// `config`, which must match the configuration used for serialization.
+    pub fn with_config(reader: R, config: Config) -> Self {
         Deserializer {
-            reader,
+            reader: Source {
+                reader,
+                words: None,
+                position: 0,
+                big_endian: config.big_endian,
+                #[cfg(feature = "unchecked")]
+                trusted: false,
+            },
             byte_handler: ByteHandler::default(),
+            config,
+            depth: 0,
+            strings: Vec::new(),
+            marker: None,
+            scratch_strings: false,
+            scratch: Vec::new(),
             phantom: core::marker::PhantomData,
         }
     }
 
-    fn try_take_word(&mut self) -> Result<u32> {
+    /// Consume the output padding configured with [Config::pad_to]
+    ///
+    /// Call this once after the last value has been deserialized. Fails if
+    /// the padding is missing or contains nonzero words.
+    pub fn finish(&mut self) -> Result<()> {
+        self.byte_handler.reset()?;
+        if self.config.pad_to > 1 {
+            let rem = self.reader.position % self.config.pad_to;
+            if rem != 0 {
+                let mut padding = vec![0u32; self.config.pad_to - rem];
+                self.reader.read_words(&mut padding)?;
+                if padding.iter().any(|&word| word != 0) {
+                    return Err(Error::DeserializeBadPadding);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Pass strings to visitors borrowed from a reused buffer
+    #[cfg(feature = "bumpalo")]
+    pub(crate) fn use_scratch_strings(&mut self) {
+        self.scratch_strings = true;
+    }
+
+    /// Check the marker of the next node when the encoding is self-describing
+    ///
+    /// Returns the number of elements held by the marker.
+    pub(crate) fn expect_marker(&mut self, kind: u32) -> Result<usize> {
+        if !self.config.self_describing {
+            return Ok(0);
+        }
+        let found = match self.marker.take() {
+            Some(found) => found,
+            None => self.try_take_word()?,
+        };
+        if marker::kind(found) != kind {
+            return Err(Error::DeserializeBadMarker);
+        }
+        Ok(marker::len(found))
+    }
+
+    /// The configuration the input is decoded with
+    pub(crate) fn config(&self) -> Config {
+        self.config
+    }
+
+    /// Whether the input is trusted to be valid
+    fn trusted(&self) -> bool {
+        #[cfg(feature = "unchecked")]
+        return self.reader.trusted;
+        #[cfg(not(feature = "unchecked"))]
+        false
+    }
+
+    /// Skip the validation of trusted input
+    ///
+    /// # Safety
+    ///
+    /// The input must have been produced by a [Serializer](crate::Serializer)
+    /// with the same configuration, from a value of the type being
+    /// deserialized.
+    #[cfg(feature = "unchecked")]
+    pub(crate) unsafe fn trust_input(&mut self) {
+        self.reader.trusted = true;
+        self.byte_handler.trusted = true;
+    }
+
+    /// Number of words read so far
+    pub(crate) fn position(&self) -> usize {
+        self.reader.position
+    }
+
+    /// End a top-level value, checking the unused bytes of its last word
+    ///
+    /// With [Config::pack_top_level], the word is shared with the value that
+    /// follows, so it isn't ended.
+    fn end_value(&mut self) -> Result<()> {
+        if self.config.pack_top_level {
+            return Ok(());
+        }
+        self.byte_handler.reset()
+    }
+
+    /// Start reading a composite, whose bytes pack together
+    ///
+    /// Fails with [Error::DeserializeTooDeep] past [MAX_DEPTH] composites.
+    pub(crate) fn enter(&mut self) -> Result<()> {
+        self.depth = self
+            .depth
+            .checked_add(1)
+            .filter(|&depth| depth <= MAX_DEPTH)
+            .ok_or(Error::DeserializeTooDeep)?;
+        Ok(())
+    }
+
+    /// Finish reading a composite, ending its last word of bytes if it is a
+    /// top-level value
+    pub(crate) fn exit(&mut self) -> Result<()> {
+        self.depth -= 1;
+        match self.depth {
+            0 => self.end_value(),
+            _ => Ok(()),
+        }
+    }
+
+    /// Read a composite with `read`
+    pub(crate) fn nested<T, F>(&mut self, read: F) -> Result<T>
+    where
+        F: FnOnce(&mut Self) -> Result<T>,
+    {
+        self.enter()?;
+        match read(self) {
+            Ok(value) => self.exit().map(|()| value),
+            Err(err) => {
+                self.depth -= 1;
+                Err(err)
+            }
+        }
+    }
+
+    /// Read an `Option` with `read`, as a composite when its tag is a byte,
+    /// which packs with the value
+    pub(crate) fn read_option<T, F>(&mut self, read: F) -> Result<T>
+    where
+        F: FnOnce(&mut Self) -> Result<T>,
+    {
+        match self.config.byte_option_tags {
+            true => self.nested(read),
+            false => read(self),
+        }
+    }
+
+    /// End a sized struct field, checking the unused bytes of its last word
+    pub(crate) fn end_field(&mut self) -> Result<()> {
+        self.byte_handler.reset()
+    }
+
+    /// Read the header of a sequence or map node
+    ///
+    /// Returns the number of elements or entries.
+    pub(crate) fn read_len(&mut self, kind: u32) -> Result<usize> {
+        self.expect_marker(kind)?;
+        Ok(self.try_take_word()? as usize)
+    }
+
+    pub(crate) fn try_take_word(&mut self) -> Result<u32> {
         self.byte_handler.reset()?;
         let mut val = 0u32;
         self.reader.read_words(core::slice::from_mut(&mut val))?;
         Ok(val)
     }
 
-    fn try_take_dword(&mut self) -> Result<u64> {
-        let low = self.try_take_word()? as u64;
-        let high = self.try_take_word()? as u64;
+    pub(crate) fn try_take_dword(&mut self) -> Result<u64> {
+        let mut low = self.try_take_word()? as u64;
+        let mut high = self.try_take_word()? as u64;
+        if self.config.big_endian {
+            core::mem::swap(&mut low, &mut high);
+        }
         Ok(low | high << 32)
     }
+
+    /// Read a unicode char
+    pub(crate) fn take_char(&mut self) -> Result<char> {
+        let word = self.try_take_word()?;
+        if self.trusted() {
+            // SAFETY: Trusted input only holds chars written by the serializer.
+            return Ok(unsafe { char::from_u32_unchecked(word) });
+        }
+        match char::from_u32(word) {
+            Some(c) => Ok(c),
+            None if self.config.replace_invalid_chars => Ok(char::REPLACEMENT_CHARACTER),
+            None => Err(Error::DeserializeBadChar),
+        }
+    }
+
+    /// Read an integer written as another type than the one of kind `kind`
+    ///
+    /// With [Config::lenient_integers], the value of an integer found with a
+    /// marker of another kind is converted to `T`. Returns `None` otherwise,
+    /// leaving the node to be read as usual.
+    pub(crate) fn take_coerced<T>(&mut self, kind: u32) -> Result<Option<T>>
+    where
+        T: TryFrom<i128> + TryFrom<u128>,
+    {
+        if !(self.config.lenient_integers && self.config.self_describing) {
+            return Ok(None);
+        }
+        let found = match self.marker.take() {
+            Some(found) => found,
+            None => self.try_take_word()?,
+        };
+        let coerced = match marker::kind(found) {
+            found_kind if found_kind == kind => None,
+            marker::U8 => Some(T::try_from(self.take_byte()? as u128).ok()),
+            marker::U16 | marker::U32 => Some(T::try_from(self.try_take_word()? as u128).ok()),
+            marker::U64 => Some(T::try_from(self.try_take_dword()? as u128).ok()),
+            marker::U128 => Some(T::try_from(u128::from_le_bytes(self.take_qword()?)).ok()),
+            marker::I8 | marker::I16 | marker::I32 => {
+                Some(T::try_from(self.try_take_word()? as i32 as i128).ok())
+            }
+            marker::I64 => Some(T::try_from(self.try_take_dword()? as i64 as i128).ok()),
+            marker::I128 => Some(T::try_from(i128::from_le_bytes(self.take_qword()?)).ok()),
+            // Not an integer, which `expect_marker` rejects.
+            _ => None,
+        };
+        match coerced {
+            Some(value) => value.map(Some).ok_or(Error::DeserializeLossyInteger),
+            None => {
+                self.marker = Some(found);
+                Ok(None)
+            }
+        }
+    }
+
+    /// Read a byte, packed with its neighbours inside composites
+    pub(crate) fn take_byte(&mut self) -> Result<u8> {
+        let byte = self.byte_handler.handle_byte(&mut self.reader)?;
+        if self.depth == 0 {
+            self.end_value()?;
+        }
+        Ok(byte)
+    }
+
+    /// Read the four words of a 128-bit integer, as its little-endian bytes
+    pub(crate) fn take_qword(&mut self) -> Result<[u8; 16]> {
+        self.byte_handler.reset()?;
+        let mut bytes = [0u8; 16];
+        self.reader.read_padded_bytes(&mut bytes)?;
+        if self.config.big_endian {
+            bytes.reverse();
+        }
+        Ok(bytes)
+    }
+
+    /// Read the tag of an `Option`, returning whether a value follows
+    pub(crate) fn take_option_tag(&mut self) -> Result<bool> {
+        self.expect_marker(marker::OPTION)?;
+        let tag = match self.config.byte_option_tags {
+            true => self.take_byte()? as u32,
+            false => self.try_take_word()?,
+        };
+        match tag {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(Error::DeserializeBadOption),
+        }
+    }
+
+    /// Read the reference word of a string when interning
+    ///
+    /// Returns the earlier string referred to, or `None` if the string follows
+    /// in full.
+    fn take_string_ref(&mut self) -> Result<Option<&str>> {
+        if !self.config.intern_strings {
+            return Ok(None);
+        }
+        match self.try_take_word()? as usize {
+            0 => Ok(None),
+            index => match self.strings.get(index - 1) {
+                Some(string) => Ok(Some(string)),
+                None => Err(Error::DeserializeBadStringRef),
+            },
+        }
+    }
+
+    /// Read a string written out in full, interning it if configured
+    fn take_full_string(&mut self, len_bytes: usize) -> Result<String> {
+        let bytes = self.take_bytes(len_bytes)?;
+        let replace = self.config.replace_invalid_chars;
+        let replaced = match decode_text(&bytes, self.trusted(), replace)? {
+            Cow::Borrowed(_) => None,
+            Cow::Owned(string) => Some(string),
+        };
+        let string = match replaced {
+            Some(string) => string,
+            // SAFETY: `decode_text` borrowed the bytes, so they are valid
+            // utf-8 or trusted to be.
+            None => unsafe { String::from_utf8_unchecked(bytes) },
+        };
+        if self.config.intern_strings {
+            self.strings.push(string.clone());
+        }
+        Ok(string)
+    }
+
+    /// Read a string node into an owned string
+    pub(crate) fn take_string(&mut self) -> Result<String> {
+        self.expect_marker(marker::STR)?;
+        if let Some(string) = self.take_string_ref()? {
+            return Ok(string.into());
+        }
+        let len_bytes = self.try_take_word()? as usize;
+        self.take_full_string(len_bytes)
+    }
+
+    /// Read `len` padded bytes into a new buffer
+    fn take_bytes(&mut self, len: usize) -> Result<Vec<u8>> {
+        self.reader
+            .check_len(align_up(len, WORD_SIZE) / WORD_SIZE, len)?;
+        // TODO: Can we use MaybeUninit here instead of zeroing out?
+        // The documentation for sys::io::Read implies that it's not
+        // safe; is there another way to not do double writes here?
+        let mut bytes = vec![0u8; len];
+        self.reader.read_padded_bytes(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    /// Decode the fields of a struct or struct variant declaring `declared`
+    /// fields
+    fn deserialize_fields<V: Visitor<'de>>(
+        &mut self,
+        declared: usize,
+        visitor: V,
+    ) -> Result<V::Value> {
+        self.nested(|de| de.read_fields(declared, visitor))
+    }
+
+    fn read_fields<V: Visitor<'de>>(&mut self, declared: usize, visitor: V) -> Result<V::Value> {
+        self.expect_marker(marker::STRUCT)?;
+        let sized = self.config.field_sizes;
+        if !self.config.counts_fields() {
+            return visitor.visit_seq(SeqAccess {
+                deserializer: self,
+                len: declared,
+                sized,
+            });
+        }
+        let count = self.try_take_word()? as usize;
+        if count > declared && !sized {
+            return Err(Error::DeserializeTooManyFields);
+        }
+        let value = visitor.visit_seq(SeqAccess {
+            deserializer: &mut *self,
+            len: count.min(declared),
+            sized,
+        })?;
+        // Skip the fields of a newer version of the type.
+        for _ in declared..count {
+            let mut size = self.try_take_word()? as usize;
+            let mut scratch = [0u32; 8];
+            while size > 0 {
+                let chunk = size.min(scratch.len());
+                self.reader.read_words(&mut scratch[..chunk])?;
+                size -= chunk;
+            }
+        }
+        Ok(value)
+    }
 }
 
-impl<'de, 'a, R: WordRead + 'de> serde::Deserializer<'de> for &'a mut Deserializer<'de, R> {
+impl<'de, R: WordRead + 'de> serde::Deserializer<'de> for &mut Deserializer<'de, R> {
     type Error = Error;
 
     fn is_human_readable(&self) -> bool {
-        false
+        self.config.human_readable
     }
 
-    fn deserialize_any<V>(self, _visitor: V) -> Result<V::Value>
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        Err(Error::NotSupported)
+        if !self.config.self_describing {
+            return Err(Error::NotSupported);
+        }
+        let found = self.try_take_word()?;
+        // The typed method takes the marker instead of reading the next one.
+        self.marker = Some(found);
+        match marker::kind(found) {
+            marker::UNIT => self.deserialize_unit(visitor),
+            marker::BOOL => self.deserialize_bool(visitor),
+            marker::U8 => self.deserialize_u8(visitor),
+            marker::I8 => self.deserialize_i8(visitor),
+            marker::U16 => self.deserialize_u16(visitor),
+            marker::I16 => self.deserialize_i16(visitor),
+            marker::U32 => self.deserialize_u32(visitor),
+            marker::I32 => self.deserialize_i32(visitor),
+            marker::U64 => self.deserialize_u64(visitor),
+            marker::I64 => self.deserialize_i64(visitor),
+            marker::U128 => self.deserialize_u128(visitor),
+            marker::I128 => self.deserialize_i128(visitor),
+            marker::F32 => self.deserialize_f32(visitor),
+            marker::F64 => self.deserialize_f64(visitor),
+            marker::CHAR => self.deserialize_char(visitor),
+            marker::STR => self.deserialize_string(visitor),
+            marker::BYTES => self.deserialize_byte_buf(visitor),
+            marker::OPTION => self.deserialize_option(visitor),
+            marker::SEQ => self.deserialize_seq(visitor),
+            marker::MAP => self.deserialize_map(visitor),
+            marker::TUPLE => self.deserialize_tuple(marker::len(found), visitor),
+            marker::STRUCT => self.deserialize_fields(marker::len(found), visitor),
+            marker::VARIANT => {
+                self.marker = None;
+                self.nested(|de| {
+                    visitor.visit_map(VariantMapAccess {
+                        deserializer: de,
+                        done: false,
+                    })
+                })
+            }
+            _ => Err(Error::DeserializeBadMarker),
+        }
     }
 
     fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        let val = match self.byte_handler.handle_byte(&mut self.reader)? {
+        self.expect_marker(marker::BOOL)?;
+        let val = match self.take_byte()? {
             0 => false,
             1 => true,
             _ => return Err(Error::DeserializeBadBool),
@@ -243,6 +1084,10 @@ impl<'de, 'a, R: WordRead + 'de> serde::Deserializer<'de> for &'a mut Deserializ
     where
         V: Visitor<'de>,
     {
+        if let Some(v) = self.take_coerced(marker::I8)? {
+            return visitor.visit_i8(v);
+        }
+        self.expect_marker(marker::I8)?;
         visitor.visit_i32(self.try_take_word()? as i32)
     }
 
@@ -250,6 +1095,10 @@ impl<'de, 'a, R: WordRead + 'de> serde::Deserializer<'de> for &'a mut Deserializ
     where
         V: Visitor<'de>,
     {
+        if let Some(v) = self.take_coerced(marker::I16)? {
+            return visitor.visit_i16(v);
+        }
+        self.expect_marker(marker::I16)?;
         visitor.visit_i32(self.try_take_word()? as i32)
     }
 
@@ -257,6 +1106,10 @@ impl<'de, 'a, R: WordRead + 'de> serde::Deserializer<'de> for &'a mut Deserializ
     where
         V: Visitor<'de>,
     {
+        if let Some(v) = self.take_coerced(marker::I32)? {
+            return visitor.visit_i32(v);
+        }
+        self.expect_marker(marker::I32)?;
         visitor.visit_i32(self.try_take_word()? as i32)
     }
 
@@ -264,6 +1117,10 @@ impl<'de, 'a, R: WordRead + 'de> serde::Deserializer<'de> for &'a mut Deserializ
     where
         V: Visitor<'de>,
     {
+        if let Some(v) = self.take_coerced(marker::I64)? {
+            return visitor.visit_i64(v);
+        }
+        self.expect_marker(marker::I64)?;
         visitor.visit_i64(self.try_take_dword()? as i64)
     }
 
@@ -271,23 +1128,32 @@ impl<'de, 'a, R: WordRead + 'de> serde::Deserializer<'de> for &'a mut Deserializ
     where
         V: Visitor<'de>,
     {
-        self.byte_handler.reset()?;
-        let mut bytes = [0u8; 16];
-        self.reader.read_padded_bytes(&mut bytes)?;
-        visitor.visit_i128(i128::from_le_bytes(bytes))
+        if let Some(v) = self.take_coerced(marker::I128)? {
+            return visitor.visit_i128(v);
+        }
+        self.expect_marker(marker::I128)?;
+        visitor.visit_i128(i128::from_le_bytes(self.take_qword()?))
     }
 
     fn deserialize_u8<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        visitor.visit_u8(self.byte_handler.handle_byte(&mut self.reader)?)
+        if let Some(v) = self.take_coerced(marker::U8)? {
+            return visitor.visit_u8(v);
+        }
+        self.expect_marker(marker::U8)?;
+        visitor.visit_u8(self.take_byte()?)
     }
 
     fn deserialize_u16<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
+        if let Some(v) = self.take_coerced(marker::U16)? {
+            return visitor.visit_u16(v);
+        }
+        self.expect_marker(marker::U16)?;
         visitor.visit_u32(self.try_take_word()?)
     }
 
@@ -295,6 +1161,10 @@ impl<'de, 'a, R: WordRead + 'de> serde::Deserializer<'de> for &'a mut Deserializ
     where
         V: Visitor<'de>,
     {
+        if let Some(v) = self.take_coerced(marker::U32)? {
+            return visitor.visit_u32(v);
+        }
+        self.expect_marker(marker::U32)?;
         visitor.visit_u32(self.try_take_word()?)
     }
 
@@ -302,6 +1172,10 @@ impl<'de, 'a, R: WordRead + 'de> serde::Deserializer<'de> for &'a mut Deserializ
     where
         V: Visitor<'de>,
     {
+        if let Some(v) = self.take_coerced(marker::U64)? {
+            return visitor.visit_u64(v);
+        }
+        self.expect_marker(marker::U64)?;
         visitor.visit_u64(self.try_take_dword()?)
     }
 
@@ -309,16 +1183,18 @@ impl<'de, 'a, R: WordRead + 'de> serde::Deserializer<'de> for &'a mut Deserializ
     where
         V: Visitor<'de>,
     {
-        self.byte_handler.reset()?;
-        let mut bytes = [0u8; 16];
-        self.reader.read_padded_bytes(&mut bytes)?;
-        visitor.visit_u128(u128::from_le_bytes(bytes))
+        if let Some(v) = self.take_coerced(marker::U128)? {
+            return visitor.visit_u128(v);
+        }
+        self.expect_marker(marker::U128)?;
+        visitor.visit_u128(u128::from_le_bytes(self.take_qword()?))
     }
 
     fn deserialize_f32<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
+        self.expect_marker(marker::F32)?;
         visitor.visit_f32(f32::from_bits(self.try_take_word()?))
     }
 
@@ -326,6 +1202,7 @@ impl<'de, 'a, R: WordRead + 'de> serde::Deserializer<'de> for &'a mut Deserializ
     where
         V: Visitor<'de>,
     {
+        self.expect_marker(marker::F64)?;
         visitor.visit_f64(f64::from_bits(self.try_take_dword()?))
     }
 
@@ -333,21 +1210,33 @@ impl<'de, 'a, R: WordRead + 'de> serde::Deserializer<'de> for &'a mut Deserializ
     where
         V: Visitor<'de>,
     {
-        let c = char::from_u32(self.try_take_word()?).ok_or(Error::DeserializeBadChar)?;
-        visitor.visit_char(c)
+        self.expect_marker(marker::CHAR)?;
+        visitor.visit_char(self.take_char()?)
     }
 
     fn deserialize_str<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
+        self.expect_marker(marker::STR)?;
+        if let Some(string) = self.take_string_ref()? {
+            return visitor.visit_str(string);
+        }
         let len_bytes = self.try_take_word()? as usize;
-        // TODO: Can we use MaybeUninit here instead of zeroing out?
-        // The documentation for sys::io::Read implies that it's not
-        // safe; is there another way to not do double writes here?
-        let mut bytes = vec![0u8; len_bytes];
-        self.reader.read_padded_bytes(&mut bytes)?;
-        visitor.visit_string(String::from_utf8(bytes).map_err(|_| Error::DeserializeBadChar)?)
+        self.reader
+            .check_len(align_up(len_bytes, WORD_SIZE) / WORD_SIZE, len_bytes)?;
+        if self.scratch_strings {
+            self.scratch.clear();
+            self.scratch.resize(len_bytes, 0);
+            self.reader.read_padded_bytes(&mut self.scratch)?;
+            let replace = self.config.replace_invalid_chars;
+            let string = decode_text(&self.scratch, self.trusted(), replace)?;
+            if self.config.intern_strings {
+                self.strings.push(string.as_ref().into());
+            }
+            return visitor.visit_str(&string);
+        }
+        visitor.visit_string(self.take_full_string(len_bytes)?)
     }
 
     fn deserialize_string<V>(self, visitor: V) -> Result<V::Value>
@@ -361,37 +1250,44 @@ impl<'de, 'a, R: WordRead + 'de> serde::Deserializer<'de> for &'a mut Deserializ
     where
         V: Visitor<'de>,
     {
+        self.expect_marker(marker::BYTES)?;
         let len_bytes = self.try_take_word()? as usize;
-        // TODO: Can we use MaybeUninit here instead of zeroing out?
-        // The documentation for sys::io::Read implies that it's not
-        // safe; is there another way to not do double writes here?
-        let mut bytes = vec![0u8; len_bytes];
-        self.reader.read_padded_bytes(&mut bytes)?;
-        visitor.visit_byte_buf(bytes)
+        // Padded bytes are the bytes of the words they occupy, so a borrowed
+        // input can lend them out directly.
+        if self.reader.words.is_some() && !self.config.big_endian {
+            let len_words = align_up(len_bytes, WORD_SIZE) / WORD_SIZE;
+            self.reader.check_len(len_words, len_bytes)?;
+            let words = self.reader.borrow_words(len_words)?;
+            let bytes: &'de [u8] = bytemuck::cast_slice(words);
+            return visitor.visit_borrowed_bytes(&bytes[..len_bytes]);
+        }
+        visitor.visit_byte_buf(self.take_bytes(len_bytes)?)
     }
 
     fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        self.deserialize_bytes(visitor)
+        self.expect_marker(marker::BYTES)?;
+        let len_bytes = self.try_take_word()? as usize;
+        visitor.visit_byte_buf(self.take_bytes(len_bytes)?)
     }
 
     fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        match self.try_take_word()? {
-            0 => visitor.visit_none(),
-            1 => visitor.visit_some(self),
-            _ => Err(Error::DeserializeBadOption),
-        }
+        self.read_option(|de| match de.take_option_tag()? {
+            false => visitor.visit_none(),
+            true => visitor.visit_some(de),
+        })
     }
 
     fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
+        self.expect_marker(marker::UNIT)?;
         visitor.visit_unit()
     }
 
@@ -402,21 +1298,53 @@ impl<'de, 'a, R: WordRead + 'de> serde::Deserializer<'de> for &'a mut Deserializ
         self.deserialize_unit(visitor)
     }
 
-    fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+    fn deserialize_newtype_struct<V>(self, name: &'static str, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        visitor.visit_newtype_struct(self)
+        if name == words::BORROWED {
+            if self.config.self_describing {
+                return Err(Error::NotSupported);
+            }
+            let len = self.try_take_word()? as usize;
+            let words = self.reader.borrow_words(len)?;
+            return visitor.visit_borrowed_bytes(bytemuck::cast_slice(words));
+        }
+        match words::element_words(name) {
+            // Elements carry their own markers in the self-describing encoding,
+            // and the words of an element are in memory order only in little
+            // endian.
+            Some(element_words) if !self.config.self_describing && !self.config.big_endian => {
+                let len = self.try_take_word()? as usize;
+                let total = len
+                    .checked_mul(element_words)
+                    .ok_or(Error::DeserializeLengthOverflow { requested: len })?;
+                self.reader.check_len(total, len)?;
+                // Grow the buffer as words arrive, so that a corrupt length
+                // fails on the end of input rather than on allocation.
+                let mut buffer = Vec::new();
+                while buffer.len() < total {
+                    let start = buffer.len();
+                    buffer.resize(total.min(start + crate::MAX_PREALLOC), 0u32);
+                    self.reader.read_words(&mut buffer[start..])?;
+                }
+                visitor.visit_bytes(bytemuck::cast_slice(&buffer))
+            }
+            _ => visitor.visit_newtype_struct(self),
+        }
     }
 
     fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        let len = self.try_take_word()? as usize;
-        visitor.visit_seq(SeqAccess {
-            deserializer: self,
-            len,
+        self.nested(|de| {
+            let len = de.read_len(marker::SEQ)?;
+            visitor.visit_seq(SeqAccess {
+                deserializer: de,
+                len,
+                sized: false,
+            })
         })
     }
 
@@ -424,9 +1352,13 @@ impl<'de, 'a, R: WordRead + 'de> serde::Deserializer<'de> for &'a mut Deserializ
     where
         V: Visitor<'de>,
     {
-        visitor.visit_seq(SeqAccess {
-            deserializer: self,
-            len,
+        self.nested(|de| {
+            de.expect_marker(marker::TUPLE)?;
+            visitor.visit_seq(SeqAccess {
+                deserializer: de,
+                len,
+                sized: false,
+            })
         })
     }
 
@@ -446,10 +1378,12 @@ impl<'de, 'a, R: WordRead + 'de> serde::Deserializer<'de> for &'a mut Deserializ
     where
         V: Visitor<'de>,
     {
-        let len = self.try_take_word()? as usize;
-        visitor.visit_map(MapAccess {
-            deserializer: self,
-            len,
+        self.nested(|de| {
+            let len = de.read_len(marker::MAP)?;
+            visitor.visit_map(MapAccess {
+                deserializer: de,
+                len,
+            })
         })
     }
 
@@ -462,19 +1396,33 @@ impl<'de, 'a, R: WordRead + 'de> serde::Deserializer<'de> for &'a mut Deserializ
     where
         V: Visitor<'de>,
     {
-        self.deserialize_tuple(fields.len(), visitor)
+        #[cfg(feature = "tracing")]
+        let (span, start) = (
+            trace::struct_span(_name, fields.len()).entered(),
+            self.position(),
+        );
+        let value = self.deserialize_fields(fields.len(), visitor)?;
+        #[cfg(feature = "tracing")]
+        span.record("words", self.position() - start);
+        Ok(value)
     }
 
     fn deserialize_enum<V>(
         self,
         _name: &'static str,
-        _variants: &'static [&'static str],
+        variants: &'static [&'static str],
         visitor: V,
     ) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        visitor.visit_enum(self)
+        self.nested(|de| {
+            de.expect_marker(marker::VARIANT)?;
+            visitor.visit_enum(EnumAccess {
+                deserializer: de,
+                variants,
+            })
+        })
     }
 
     fn deserialize_identifier<V>(self, _visitor: V) -> Result<V::Value>
@@ -484,11 +1432,11 @@ impl<'de, 'a, R: WordRead + 'de> serde::Deserializer<'de> for &'a mut Deserializ
         Err(Error::NotSupported)
     }
 
-    fn deserialize_ignored_any<V>(self, _visitor: V) -> Result<V::Value>
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        Err(Error::NotSupported)
+        self.deserialize_any(visitor)
     }
 }
 
@@ -526,6 +1474,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::approx_constant)]
     fn test_struct() {
         use serde::Deserialize;
 
@@ -593,4 +1542,270 @@ mod tests {
         };
         assert_eq!(expected, from_slice_compact(&words).unwrap());
     }
+
+    #[test]
+    fn test_bytes() {
+        use serde::de::Visitor;
+
+        #[derive(Debug, Deserialize, PartialEq, Serialize)]
+        struct Blob<'a> {
+            #[serde(borrow)]
+            head: &'a [u8],
+            len: u32,
+        }
+
+        /// A `serde_bytes`-style owned buffer
+        #[derive(Debug, PartialEq)]
+        struct ByteBuf(Vec<u8>);
+
+        impl<'de> Deserialize<'de> for ByteBuf {
+            fn deserialize<D: serde::Deserializer<'de>>(
+                deserializer: D,
+            ) -> core::result::Result<Self, D::Error> {
+                struct ByteBufVisitor;
+
+                impl Visitor<'_> for ByteBufVisitor {
+                    type Value = ByteBuf;
+
+                    fn expecting(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
+                        formatter.write_str("bytes")
+                    }
+
+                    fn visit_byte_buf<E>(self, v: Vec<u8>) -> core::result::Result<ByteBuf, E> {
+                        Ok(ByteBuf(v))
+                    }
+                }
+
+                deserializer.deserialize_byte_buf(ByteBufVisitor)
+            }
+        }
+
+        let input = Blob {
+            head: &[1, 2, 3, 4, 5],
+            len: 5,
+        };
+        let words = crate::to_vec_compact(&input).unwrap();
+        assert_eq!([5, 0x04030201, 5, 5], words.as_slice());
+        let output: Blob = from_words(&words).unwrap();
+        assert_eq!(input, output);
+        assert!(words.as_ptr_range().contains(&output.head.as_ptr().cast()));
+
+        let output: ByteBuf = from_slice_compact(&words[..3]).unwrap();
+        assert_eq!(ByteBuf(vec![1, 2, 3, 4, 5]), output);
+
+        // Bytes can't be borrowed from a generic reader.
+        let mut deserializer = Deserializer::new(words.as_slice());
+        assert!(Blob::deserialize(&mut deserializer).is_err());
+
+        // Nor from big-endian words, whose bytes are out of memory order.
+        let config = Config::new().big_endian(true);
+        let words = crate::to_vec_compact_with_config(&input, config).unwrap();
+        assert_eq!([5, 0x01020304, 0x05000000, 5], words.as_slice());
+        let output: ByteBuf = from_slice_compact_with_config(&words[..3], config).unwrap();
+        assert_eq!(ByteBuf(vec![1, 2, 3, 4, 5]), output);
+        assert!(from_words_with_config::<Blob>(&words, config).is_err());
+    }
+
+    #[test]
+    fn test_lenient_integers() {
+        #[derive(Serialize)]
+        struct Old {
+            id: u32,
+            delta: i64,
+            small: u8,
+            tags: Vec<u16>,
+        }
+
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct New {
+            id: u64,
+            delta: i32,
+            small: i16,
+            tags: Vec<u128>,
+        }
+
+        let old = |delta| Old {
+            id: 7,
+            delta,
+            small: 200,
+            tags: vec![1, 2],
+        };
+        let config = Config::new().self_describing(true);
+        let data = crate::to_vec_compact_with_config(&old(-3), config).unwrap();
+        assert_eq!(
+            Err(Error::DeserializeBadMarker),
+            from_slice_compact_with_config::<New, _>(&data, config)
+        );
+
+        let lenient = config.lenient_integers(true);
+        let expected = New {
+            id: 7,
+            delta: -3,
+            small: 200,
+            tags: vec![1, 2],
+        };
+        assert_eq!(Ok(expected), from_slice_compact_with_config(&data, lenient));
+        let data = crate::to_vec_compact_with_config(&old(1 << 40), config).unwrap();
+        assert_eq!(
+            Err(Error::DeserializeLossyInteger),
+            from_slice_compact_with_config::<New, _>(&data, lenient)
+        );
+        let data = crate::to_vec_compact_with_config(&-1i32, config).unwrap();
+        assert_eq!(
+            Err(Error::DeserializeLossyInteger),
+            from_slice_compact_with_config::<u64, _>(&data, lenient)
+        );
+        assert_eq!(
+            Ok(-1i128),
+            crate::direct::from_slice_direct_with_config(&data, lenient)
+        );
+
+        // Other nodes are still checked against the type.
+        let data = crate::to_vec_compact_with_config(&'a', config).unwrap();
+        assert_eq!(
+            Err(Error::DeserializeBadMarker),
+            from_slice_compact_with_config::<u32, _>(&data, lenient)
+        );
+    }
+
+    #[test]
+    fn test_too_deep() {
+        #[derive(Debug, Deserialize)]
+        struct Nested(#[allow(dead_code)] Vec<Nested>);
+
+        // Sequences holding a single sequence, down to an empty one
+        let nested = |depth: usize| [vec![1u32; depth], vec![0]].concat();
+        assert!(from_slice_compact::<Nested, _>(&nested(200)).is_ok());
+        assert_eq!(
+            Err(Error::DeserializeTooDeep),
+            from_slice_compact::<Nested, _>(&nested(300)).map(|_| ())
+        );
+
+        let config = Config::new().self_describing(true);
+        let data: Vec<u32> = [marker::SEQ, 1]
+            .repeat(300)
+            .into_iter()
+            .chain([marker::SEQ, 0])
+            .collect();
+        assert_eq!(
+            Err(Error::DeserializeTooDeep),
+            from_slice_compact_with_config::<crate::word_value::Value, _>(&data, config)
+        );
+    }
+
+    #[test]
+    fn test_replace_invalid_chars() {
+        let config = Config::new().replace_invalid_chars(true);
+        for word in [0xd800, 0xdfff, 0x110000, u32::MAX] {
+            assert_eq!(
+                Err(Error::DeserializeBadChar),
+                from_slice_compact::<char, _>(&[word])
+            );
+            assert_eq!(
+                Ok('\u{fffd}'),
+                from_slice_compact_with_config(&[word], config)
+            );
+        }
+
+        // "hi", an invalid byte and an unfinished two-byte sequence
+        let words = [4, u32::from_le_bytes([b'h', b'i', 0xff, 0xc3])];
+        assert_eq!(
+            Err(Error::DeserializeBadUtf8 { offset: 2 }),
+            from_slice_compact::<String, _>(&words)
+        );
+        let output: String = from_slice_compact_with_config(&words, config).unwrap();
+        assert_eq!("hi\u{fffd}\u{fffd}", output);
+        let mut output = String::from("reused");
+        crate::from_slice_compact_in_place_with_config(&words, &mut output, config).unwrap();
+        assert_eq!("hi\u{fffd}\u{fffd}", output);
+
+        // An interned string refers to the string as decoded.
+        let config = config.intern_strings(true);
+        let words = [0, 4, words[1], 1];
+        let output: (String, String) = from_slice_compact_with_config(&words, config).unwrap();
+        assert_eq!(output.0, output.1);
+    }
+
+    #[test]
+    fn test_iter_reader() {
+        let input = (String::from("across chunks"), [1u8, 2, 3], vec![4u64, 5]);
+        let words = crate::to_vec_compact(&input).unwrap();
+        // The string starts in the first chunk and ends in a later one.
+        let chunks: Vec<Vec<u32>> = words.chunks(2).map(<[u32]>::to_vec).collect();
+        let mut deserializer = Deserializer::new(IterReader::new(chunks.into_iter().flatten()));
+        let output = <(String, [u8; 3], Vec<u64>)>::deserialize(&mut deserializer).unwrap();
+        deserializer.finish().unwrap();
+        assert_eq!(input, output);
+
+        let mut reader = IterReader::new(words.iter().copied().take(3));
+        assert_eq!(
+            Err(Error::DeserializeUnexpectedEnd {
+                needed: 4,
+                available: None
+            }),
+            <(String, u8)>::deserialize(&mut Deserializer::new(&mut reader))
+        );
+        assert_eq!(0, reader.into_inner().count());
+    }
+
+    #[test]
+    fn test_top_level_scalars_streamed() {
+        let packed = Config::new().pack_top_level(true);
+        for (config, expected) in [
+            (Config::new(), [1, 2, 3, 4, 5].as_slice()),
+            (packed, [0x0201, 3, 0x0504].as_slice()),
+        ] {
+            let mut words = Vec::new();
+            let mut serializer = crate::Serializer::with_config(&mut words, config);
+            1u8.serialize(&mut serializer).unwrap();
+            2u8.serialize(&mut serializer).unwrap();
+            3u16.serialize(&mut serializer).unwrap();
+            4u8.serialize(&mut serializer).unwrap();
+            5u8.serialize(&mut serializer).unwrap();
+            serializer.finish().unwrap();
+            assert_eq!(expected, words);
+
+            let reader = IterReader::new(words.into_iter());
+            let mut deserializer = if config == packed {
+                Deserializer::with_config(reader, config)
+            } else {
+                Deserializer::new(reader)
+            };
+            assert_eq!(1u8, u8::deserialize(&mut deserializer).unwrap());
+            assert_eq!(2u8, u8::deserialize(&mut deserializer).unwrap());
+            assert_eq!(3u16, u16::deserialize(&mut deserializer).unwrap());
+            assert_eq!(4u8, u8::deserialize(&mut deserializer).unwrap());
+            assert_eq!(5u8, u8::deserialize(&mut deserializer).unwrap());
+            deserializer.finish().unwrap();
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "unchecked")]
+    fn test_unchecked() {
+        let input = (String::from("héllo"), 'ß', [1u8, 2, 3], Some(7u64));
+        let words = crate::to_vec_compact(&input).unwrap();
+        let output: (String, char, [u8; 3], Option<u64>) =
+            unsafe { from_slice_unchecked(&words) }.unwrap();
+        assert_eq!(input, output);
+        let bytes: Vec<u8> = bytemuck::cast_slice(&words).to_vec();
+        let output: (String, char, [u8; 3], Option<u64>) =
+            unsafe { from_slice_unchecked(&bytes) }.unwrap();
+        assert_eq!(input, output);
+
+        // The padding of packed bytes isn't validated.
+        let mut words = crate::to_vec_compact(&[1u8, 2, 3]).unwrap();
+        words[0] |= 0xff << 24;
+        assert_eq!(
+            Err(Error::DeserializeBadByte),
+            from_slice_compact::<[u8; 3], _>(&words)
+        );
+        assert_eq!(Ok([1, 2, 3]), unsafe {
+            from_slice_unchecked::<[u8; 3], _>(&words)
+        });
+
+        // Reads past the end still fail.
+        let words = crate::to_vec_compact("abcdefgh").unwrap();
+        assert!(unsafe { from_slice_unchecked::<String, _>(&words[..2]) }.is_err());
+    }
 }
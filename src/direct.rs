@@ -0,0 +1,619 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Encoding without serde dispatch
+//!
+//! Types implementing [WordSerialize] and [WordDeserialize] are encoded by
+//! calling into the [Serializer] and [Deserializer] directly, without the
+//! visitors and access objects of serde. The encoding is the same as that of
+//! the serde impls with every [Config], so values written one way can be read
+//! the other way.
+//!
+//! With the `derive` feature, the impls can be derived for structs whose
+//! fields implement the traits. Implementations are provided for primitives,
+//! `String`, `Vec`, `Box`, `Option` and arrays of them.
+//! ```rust
+//! use l2r0_small_serde::{direct, WordDeserialize, WordSerialize};
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Debug, PartialEq, Serialize, Deserialize, WordSerialize, WordDeserialize)]
+//! struct Point {
+//!     x: u32,
+//!     y: Option<i64>,
+//! }
+//!
+//! let point = Point { x: 1, y: Some(-2) };
+//! let data = direct::to_vec_direct(&point).unwrap();
+//! assert_eq!(l2r0_small_serde::to_vec_compact(&point).unwrap(), data);
+//! assert_eq!(point, direct::from_slice_direct(&data).unwrap());
+//! ```
+//!
+//! Derived impls follow `#[derive(Serialize, Deserialize)]` without
+//! attributes. Unlike the serde impls, they don't skip the fields of a newer
+//! version of the type when [Config::field_count] is set, and fail with
+//! [Error::DeserializeTooManyFields] instead.
+
+use alloc::{borrow::Cow, boxed::Box, string::String, vec::Vec};
+
+use bytemuck::Pod;
+use risc0_zkvm::serde::{WordRead, WordWrite};
+use serde::{ser::SerializeStruct, Serializer as _};
+
+use super::{
+    err::{Error, Result},
+    marker, Config, Deserializer, Serializer,
+};
+
+/// A type that can be serialized without serde dispatch
+pub trait WordSerialize {
+    /// Serialize `self` into `serializer`
+    fn word_serialize<W: WordWrite>(&self, serializer: &mut Serializer<W>) -> Result<()>;
+}
+
+/// A type that can be deserialized without serde dispatch
+pub trait WordDeserialize: Sized {
+    /// Deserialize a value from `deserializer`
+    fn word_deserialize<'de, R: WordRead + 'de>(
+        deserializer: &mut Deserializer<'de, R>,
+    ) -> Result<Self>;
+}
+
+/// Serialize to a vector of u32 words without serde dispatch
+pub fn to_vec_direct<T: WordSerialize + ?Sized>(value: &T) -> Result<Vec<u32>> {
+    to_vec_direct_with_config(value, Config::default())
+}
+
+/// Serialize to a vector of u32 words without serde dispatch using the given
+/// configuration
+pub fn to_vec_direct_with_config<T: WordSerialize + ?Sized>(
+    value: &T,
+    config: Config,
+) -> Result<Vec<u32>> {
+    let mut vec: Vec<u32> = Vec::with_capacity(core::mem::size_of_val(value));
+    let mut serializer = Serializer::with_config(&mut vec, config);
+    value.word_serialize(&mut serializer)?;
+    serializer.finish()?;
+    Ok(vec)
+}
+
+/// Deserialize a slice into the specified type without serde dispatch
+pub fn from_slice_direct<T: WordDeserialize, P: Pod>(slice: &[P]) -> Result<T> {
+    from_slice_direct_with_config(slice, Config::default())
+}
+
+/// Deserialize a slice into the specified type without serde dispatch using
+/// the given configuration
+///
+/// `config` must match the configuration `slice` was serialized with.
+pub fn from_slice_direct_with_config<T: WordDeserialize, P: Pod>(
+    slice: &[P],
+    config: Config,
+) -> Result<T> {
+    // Only data that isn't word-aligned or word-sized is copied.
+    let words = match bytemuck::try_cast_slice(slice) {
+        Ok(words) => Cow::Borrowed(words),
+        Err(_) => Cow::Owned(bytemuck::allocation::pod_collect_to_vec::<P, u32>(slice)),
+    };
+    let mut deserializer = Deserializer::from_words_with_config(&words, config);
+    let value = T::word_deserialize(&mut deserializer)?;
+    deserializer.finish()?;
+    Ok(value)
+}
+
+// Entry points of the derived impls. They are public for the generated code
+// only.
+impl<W: WordWrite> Serializer<W> {
+    #[doc(hidden)]
+    pub fn direct_struct<F>(&mut self, len: usize, fields: F) -> Result<()>
+    where
+        F: FnOnce(&mut Self) -> Result<()>,
+    {
+        let state = self.serialize_struct("", len)?;
+        fields(state)?;
+        SerializeStruct::end(self)
+    }
+
+    #[doc(hidden)]
+    pub fn direct_field<T: WordSerialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.serialize_struct_field(key, |serializer| value.word_serialize(serializer))
+    }
+
+    #[doc(hidden)]
+    pub fn direct_tuple<F>(&mut self, len: usize, fields: F) -> Result<()>
+    where
+        F: FnOnce(&mut Self) -> Result<()>,
+    {
+        let state = self.serialize_tuple_struct("", len)?;
+        fields(state)?;
+        serde::ser::SerializeTupleStruct::end(self)
+    }
+}
+
+impl<'de, R: WordRead + 'de> Deserializer<'de, R> {
+    #[doc(hidden)]
+    pub fn direct_struct<T, F>(&mut self, len: usize, fields: F) -> Result<T>
+    where
+        F: FnOnce(&mut Self) -> Result<T>,
+    {
+        self.nested(|de| {
+            de.expect_marker(marker::STRUCT)?;
+            if de.config().counts_fields() {
+                let count = de.try_take_word()? as usize;
+                if count > len {
+                    return Err(Error::DeserializeTooManyFields);
+                }
+                if count < len {
+                    return Err(serde::de::Error::invalid_length(count, &"all fields"));
+                }
+            }
+            fields(de)
+        })
+    }
+
+    #[doc(hidden)]
+    pub fn direct_field<T: WordDeserialize>(&mut self) -> Result<T> {
+        if !self.config().field_sizes {
+            return T::word_deserialize(self);
+        }
+        let size = self.try_take_word()? as usize;
+        let start = self.position();
+        let value = T::word_deserialize(self)?;
+        self.end_field()?;
+        if self.position() - start != size {
+            return Err(Error::DeserializeBadFieldSize);
+        }
+        Ok(value)
+    }
+
+    #[doc(hidden)]
+    pub fn direct_tuple<T, F>(&mut self, fields: F) -> Result<T>
+    where
+        F: FnOnce(&mut Self) -> Result<T>,
+    {
+        self.nested(|de| {
+            de.expect_marker(marker::TUPLE)?;
+            fields(de)
+        })
+    }
+}
+
+macro_rules! impl_word_serde {
+    ($($ty:ty => $serialize:ident, |$d:ident| $deserialize:block;)*) => {
+        $(
+            impl WordSerialize for $ty {
+                fn word_serialize<W: WordWrite>(&self, serializer: &mut Serializer<W>) -> Result<()> {
+                    serializer.$serialize(*self)
+                }
+            }
+
+            impl WordDeserialize for $ty {
+                fn word_deserialize<'de, R: WordRead + 'de>(
+                    $d: &mut Deserializer<'de, R>,
+                ) -> Result<Self> $deserialize
+            }
+        )*
+    };
+}
+
+/// Decode a signed integer stored in a word, failing like serde if it's out of
+/// range
+fn narrow<T: TryFrom<i32>>(word: u32) -> Result<T> {
+    let v = word as i32;
+    T::try_from(v).map_err(|_| {
+        serde::de::Error::invalid_value(
+            serde::de::Unexpected::Signed(v.into()),
+            &core::any::type_name::<T>(),
+        )
+    })
+}
+
+/// Decode an unsigned integer stored in a word, failing like serde if it's out
+/// of range
+fn narrow_unsigned<T: TryFrom<u32>>(word: u32) -> Result<T> {
+    T::try_from(word).map_err(|_| {
+        serde::de::Error::invalid_value(
+            serde::de::Unexpected::Unsigned(word.into()),
+            &core::any::type_name::<T>(),
+        )
+    })
+}
+
+impl_word_serde! {
+    bool => serialize_bool, |d| {
+        d.expect_marker(marker::BOOL)?;
+        match d.take_byte()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(Error::DeserializeBadBool),
+        }
+    };
+    u8 => serialize_u8, |d| {
+        if let Some(v) = d.take_coerced(marker::U8)? {
+            return Ok(v);
+        }
+        d.expect_marker(marker::U8)?;
+        d.take_byte()
+    };
+    u16 => serialize_u16, |d| {
+        if let Some(v) = d.take_coerced(marker::U16)? {
+            return Ok(v);
+        }
+        d.expect_marker(marker::U16)?;
+        narrow_unsigned(d.try_take_word()?)
+    };
+    u32 => serialize_u32, |d| {
+        if let Some(v) = d.take_coerced(marker::U32)? {
+            return Ok(v);
+        }
+        d.expect_marker(marker::U32)?;
+        d.try_take_word()
+    };
+    u64 => serialize_u64, |d| {
+        if let Some(v) = d.take_coerced(marker::U64)? {
+            return Ok(v);
+        }
+        d.expect_marker(marker::U64)?;
+        d.try_take_dword()
+    };
+    u128 => serialize_u128, |d| {
+        if let Some(v) = d.take_coerced(marker::U128)? {
+            return Ok(v);
+        }
+        d.expect_marker(marker::U128)?;
+        Ok(u128::from_le_bytes(d.take_qword()?))
+    };
+    i8 => serialize_i8, |d| {
+        if let Some(v) = d.take_coerced(marker::I8)? {
+            return Ok(v);
+        }
+        d.expect_marker(marker::I8)?;
+        narrow(d.try_take_word()?)
+    };
+    i16 => serialize_i16, |d| {
+        if let Some(v) = d.take_coerced(marker::I16)? {
+            return Ok(v);
+        }
+        d.expect_marker(marker::I16)?;
+        narrow(d.try_take_word()?)
+    };
+    i32 => serialize_i32, |d| {
+        if let Some(v) = d.take_coerced(marker::I32)? {
+            return Ok(v);
+        }
+        d.expect_marker(marker::I32)?;
+        Ok(d.try_take_word()? as i32)
+    };
+    i64 => serialize_i64, |d| {
+        if let Some(v) = d.take_coerced(marker::I64)? {
+            return Ok(v);
+        }
+        d.expect_marker(marker::I64)?;
+        Ok(d.try_take_dword()? as i64)
+    };
+    i128 => serialize_i128, |d| {
+        if let Some(v) = d.take_coerced(marker::I128)? {
+            return Ok(v);
+        }
+        d.expect_marker(marker::I128)?;
+        Ok(i128::from_le_bytes(d.take_qword()?))
+    };
+    f32 => serialize_f32, |d| {
+        d.expect_marker(marker::F32)?;
+        Ok(f32::from_bits(d.try_take_word()?))
+    };
+    f64 => serialize_f64, |d| {
+        d.expect_marker(marker::F64)?;
+        Ok(f64::from_bits(d.try_take_dword()?))
+    };
+    char => serialize_char, |d| {
+        d.expect_marker(marker::CHAR)?;
+        d.take_char()
+    };
+}
+
+impl WordSerialize for () {
+    fn word_serialize<W: WordWrite>(&self, serializer: &mut Serializer<W>) -> Result<()> {
+        serializer.serialize_unit()
+    }
+}
+
+impl WordDeserialize for () {
+    fn word_deserialize<'de, R: WordRead + 'de>(
+        deserializer: &mut Deserializer<'de, R>,
+    ) -> Result<Self> {
+        deserializer.expect_marker(marker::UNIT)?;
+        Ok(())
+    }
+}
+
+impl WordSerialize for str {
+    fn word_serialize<W: WordWrite>(&self, serializer: &mut Serializer<W>) -> Result<()> {
+        serializer.serialize_str(self)
+    }
+}
+
+impl WordSerialize for String {
+    fn word_serialize<W: WordWrite>(&self, serializer: &mut Serializer<W>) -> Result<()> {
+        serializer.serialize_str(self)
+    }
+}
+
+impl WordDeserialize for String {
+    fn word_deserialize<'de, R: WordRead + 'de>(
+        deserializer: &mut Deserializer<'de, R>,
+    ) -> Result<Self> {
+        deserializer.take_string()
+    }
+}
+
+impl<T: WordSerialize + ?Sized> WordSerialize for &T {
+    fn word_serialize<W: WordWrite>(&self, serializer: &mut Serializer<W>) -> Result<()> {
+        (**self).word_serialize(serializer)
+    }
+}
+
+impl<T: WordSerialize + ?Sized> WordSerialize for Box<T> {
+    fn word_serialize<W: WordWrite>(&self, serializer: &mut Serializer<W>) -> Result<()> {
+        (**self).word_serialize(serializer)
+    }
+}
+
+impl<T: WordDeserialize> WordDeserialize for Box<T> {
+    fn word_deserialize<'de, R: WordRead + 'de>(
+        deserializer: &mut Deserializer<'de, R>,
+    ) -> Result<Self> {
+        T::word_deserialize(deserializer).map(Box::new)
+    }
+}
+
+impl<T: WordSerialize> WordSerialize for Option<T> {
+    fn word_serialize<W: WordWrite>(&self, serializer: &mut Serializer<W>) -> Result<()> {
+        match self {
+            Some(value) => serializer.write_some(|serializer| value.word_serialize(serializer)),
+            None => serializer.write_option_tag(false),
+        }
+    }
+}
+
+impl<T: WordDeserialize> WordDeserialize for Option<T> {
+    fn word_deserialize<'de, R: WordRead + 'de>(
+        deserializer: &mut Deserializer<'de, R>,
+    ) -> Result<Self> {
+        deserializer.read_option(|de| match de.take_option_tag()? {
+            true => T::word_deserialize(de).map(Some),
+            false => Ok(None),
+        })
+    }
+}
+
+impl<T: WordSerialize> WordSerialize for [T] {
+    fn word_serialize<W: WordWrite>(&self, serializer: &mut Serializer<W>) -> Result<()> {
+        let state = serializer.serialize_seq(Some(self.len()))?;
+        for element in self {
+            element.word_serialize(state)?;
+        }
+        serde::ser::SerializeSeq::end(serializer)
+    }
+}
+
+impl<T: WordSerialize> WordSerialize for Vec<T> {
+    fn word_serialize<W: WordWrite>(&self, serializer: &mut Serializer<W>) -> Result<()> {
+        self.as_slice().word_serialize(serializer)
+    }
+}
+
+impl<T: WordDeserialize> WordDeserialize for Vec<T> {
+    fn word_deserialize<'de, R: WordRead + 'de>(
+        deserializer: &mut Deserializer<'de, R>,
+    ) -> Result<Self> {
+        deserializer.nested(|de| {
+            let len = de.read_len(marker::SEQ)?;
+            // Cap the allocation like serde does, so that a corrupt length
+            // fails on the end of input rather than on allocation.
+            let cap = 1024 * 1024 / core::mem::size_of::<T>().max(1);
+            let mut vec = Vec::with_capacity(len.min(cap));
+            for _ in 0..len {
+                vec.push(T::word_deserialize(de)?);
+            }
+            Ok(vec)
+        })
+    }
+}
+
+impl<T: WordSerialize, const N: usize> WordSerialize for [T; N] {
+    fn word_serialize<W: WordWrite>(&self, serializer: &mut Serializer<W>) -> Result<()> {
+        let state = serializer.serialize_tuple(N)?;
+        for element in self {
+            element.word_serialize(state)?;
+        }
+        serde::ser::SerializeTuple::end(serializer)
+    }
+}
+
+impl<T: WordDeserialize, const N: usize> WordDeserialize for [T; N] {
+    fn word_deserialize<'de, R: WordRead + 'de>(
+        deserializer: &mut Deserializer<'de, R>,
+    ) -> Result<Self> {
+        let elements: Vec<T> = deserializer.nested(|de| {
+            de.expect_marker(marker::TUPLE)?;
+            (0..N).map(|_| T::word_deserialize(de)).collect()
+        })?;
+        match elements.try_into() {
+            Ok(array) => Ok(array),
+            Err(_) => unreachable!("collected exactly N elements"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{boxed::Box, string::String, vec, vec::Vec};
+
+    use serde::{Deserialize, Serialize};
+
+    use super::{from_slice_direct_with_config, to_vec_direct_with_config};
+    use crate::{
+        from_slice_compact_with_config, to_vec_compact_with_config, Config, Error, WordDeserialize,
+        WordSerialize,
+    };
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize, WordSerialize, WordDeserialize)]
+    struct Header(u8, bool, i16);
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize, WordSerialize, WordDeserialize)]
+    struct Id(u64);
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize, WordSerialize, WordDeserialize)]
+    struct Marker;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize, WordSerialize, WordDeserialize)]
+    struct Entry<T> {
+        key: String,
+        value: T,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize, WordSerialize, WordDeserialize)]
+    struct Block {
+        header: Header,
+        id: Id,
+        marker: Marker,
+        flags: Vec<u8>,
+        entries: Vec<Entry<Option<i64>>>,
+        root: [u32; 3],
+        wide: i128,
+        ratio: f64,
+        tag: char,
+        parent: Option<Box<Block>>,
+    }
+
+    fn block() -> Block {
+        let child = Block {
+            header: Header(7, false, -3),
+            id: Id(1 << 40),
+            marker: Marker,
+            flags: vec![],
+            entries: vec![],
+            root: [0; 3],
+            wide: -1,
+            ratio: 0.5,
+            tag: 'c',
+            parent: None,
+        };
+        Block {
+            header: Header(1, true, 300),
+            id: Id(2),
+            marker: Marker,
+            flags: vec![1, 2, 3, 4, 5],
+            entries: vec![
+                Entry {
+                    key: "a".into(),
+                    value: Some(-1),
+                },
+                Entry {
+                    key: "a".into(),
+                    value: None,
+                },
+            ],
+            root: [1, 2, 3],
+            wide: 1 << 100,
+            ratio: -2.25,
+            tag: 'ß',
+            parent: Some(Box::new(child)),
+        }
+    }
+
+    fn configs() -> [Config; 8] {
+        [
+            Config::new(),
+            Config::new().self_describing(true),
+            Config::new().byte_option_tags(true),
+            Config::new().pack_top_level(true),
+            Config::new().field_count(true),
+            Config::new().field_sizes(true),
+            Config::new().intern_strings(true),
+            Config::new()
+                .pad_to(8)
+                .self_describing(true)
+                .field_sizes(true),
+        ]
+    }
+
+    #[test]
+    fn test_same_as_serde() {
+        let input = block();
+        for config in configs() {
+            let data = to_vec_compact_with_config(&input, config).unwrap();
+            assert_eq!(data, to_vec_direct_with_config(&input, config).unwrap());
+            let output: Block = from_slice_direct_with_config(&data, config).unwrap();
+            assert_eq!(input, output);
+            let output: Block = from_slice_compact_with_config(&data, config).unwrap();
+            assert_eq!(input, output);
+        }
+    }
+
+    #[test]
+    fn test_top_level() {
+        let config = Config::new();
+        let data = to_vec_direct_with_config(&5u8, config).unwrap();
+        assert_eq!(vec![5], data);
+        let data = to_vec_direct_with_config("hi", config).unwrap();
+        assert_eq!(to_vec_compact_with_config("hi", config).unwrap(), data);
+        let output: String = from_slice_direct_with_config(&data, config).unwrap();
+        assert_eq!("hi", output);
+        // Bytes that aren't word-aligned are copied.
+        let bytes: Vec<u8> = [0]
+            .into_iter()
+            .chain(bytemuck::cast_slice(&data).iter().copied())
+            .collect();
+        let output: String = from_slice_direct_with_config(&bytes[1..], config).unwrap();
+        assert_eq!("hi", output);
+    }
+
+    #[test]
+    fn test_errors() {
+        let config = Config::new();
+        let data = to_vec_compact_with_config(&(1u8, true, 70000u32), config).unwrap();
+        assert_eq!(
+            from_slice_compact_with_config::<Header, _>(&data, config),
+            from_slice_direct_with_config::<Header, _>(&data, config)
+        );
+        let data = to_vec_compact_with_config(&true, config).unwrap();
+        assert_eq!(Ok(true), from_slice_direct_with_config(&data, config));
+        assert_eq!(
+            Err(Error::DeserializeBadBool),
+            from_slice_direct_with_config::<bool, _>(&[2u32], config)
+        );
+
+        // Fields of a newer version of the type aren't skipped.
+        let config = Config::new().field_count(true);
+        let data = to_vec_compact_with_config(
+            &Entry {
+                key: String::from("k"),
+                value: 1u32,
+            },
+            config,
+        )
+        .unwrap();
+        let mut longer: Vec<u32> = data.iter().copied().chain([9]).collect();
+        longer[0] = 3;
+        assert_eq!(
+            Err(Error::DeserializeTooManyFields),
+            from_slice_direct_with_config::<Entry<u32>, _>(&longer, config)
+        );
+    }
+}
@@ -0,0 +1,213 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::vec::Vec;
+
+use bytemuck::Pod;
+use chacha20poly1305::{
+    aead::{Aead, KeyInit},
+    ChaCha20Poly1305, Nonce,
+};
+use risc0_zkvm::serde::{WordRead, WordWrite};
+use serde::de::DeserializeOwned;
+
+use super::{
+    buffer::{le_bytes_to_words, read_padded_bytes, words_to_le_bytes, WordBuffer},
+    err::{Error, Result},
+    Deserializer, Serializer, WORD_SIZE,
+};
+
+/// Number of words of the nonce at the start of a frame
+const NONCE_WORDS: usize = 3;
+
+/// Serialize to a ChaCha20-Poly1305 encrypted vector of u32 words
+///
+/// `nonce` must never be used twice with the same `key`.
+pub fn to_vec_encrypted<T>(value: &T, key: &[u8; 32], nonce: [u8; 12]) -> Result<Vec<u32>>
+where
+    T: serde::Serialize + ?Sized,
+{
+    let mut vec: Vec<u32> = Vec::new();
+    let mut writer = EncryptedWriter::new(&mut vec, key, nonce);
+    value.serialize(&mut Serializer::new(&mut writer))?;
+    writer.finish()?;
+    Ok(vec)
+}
+
+/// Decrypt a slice and deserialize it into the specified type.
+///
+/// `slice` must have been produced by [to_vec_encrypted] or an
+/// [EncryptedWriter] with the same `key`.
+pub fn from_slice_encrypted<T: DeserializeOwned, P: Pod>(slice: &[P], key: &[u8; 32]) -> Result<T> {
+    let words = bytemuck::allocation::pod_collect_to_vec::<P, u32>(slice);
+    let reader = EncryptedReader::new(words.as_slice(), key)?;
+    T::deserialize(&mut Deserializer::new(reader))
+}
+
+/// A WordWrite that encrypts the words written to it
+///
+/// Words are collected in memory and written to the inner stream as a single
+/// frame by [EncryptedWriter::finish]: the three words of the nonce, a word
+/// holding the size of the ciphertext in bytes, and the ciphertext with its
+/// authentication tag. The nonce is stored in the clear, so only the key has
+/// to be shared with the reader, and it must never be used twice with the
+/// same key.
+pub struct EncryptedWriter<W: WordWrite> {
+    inner: W,
+    cipher: ChaCha20Poly1305,
+    nonce: [u8; 12],
+    words: Vec<u32>,
+}
+
+impl<W: WordWrite> EncryptedWriter<W> {
+    /// Construct an EncryptedWriter that writes to `inner`
+    pub fn new(inner: W, key: &[u8; 32], nonce: [u8; 12]) -> Self {
+        EncryptedWriter {
+            inner,
+            cipher: ChaCha20Poly1305::new(key.into()),
+            nonce,
+            words: Vec::new(),
+        }
+    }
+
+    /// Encrypt the words written so far to the inner stream
+    ///
+    /// Returns the inner stream.
+    pub fn finish(mut self) -> Result<W> {
+        let bytes = words_to_le_bytes(&self.words);
+        let ciphertext = self
+            .cipher
+            .encrypt(Nonce::from_slice(&self.nonce), bytes.as_slice())
+            .map_err(|_| Error::SerializeLengthOverflow {
+                requested: bytes.len(),
+            })?;
+        let len = ciphertext
+            .len()
+            .try_into()
+            .map_err(|_| Error::SerializeLengthOverflow {
+                requested: ciphertext.len(),
+            })?;
+        self.inner.write_padded_bytes(&self.nonce)?;
+        self.inner.write_words(&[len])?;
+        self.inner.write_padded_bytes(&ciphertext)?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: WordWrite> WordWrite for EncryptedWriter<W> {
+    fn write_words(&mut self, words: &[u32]) -> risc0_zkvm::serde::Result<()> {
+        self.words.write_words(words)
+    }
+
+    fn write_padded_bytes(&mut self, bytes: &[u8]) -> risc0_zkvm::serde::Result<()> {
+        self.words.write_padded_bytes(bytes)
+    }
+}
+
+/// A WordRead over a frame written by an [EncryptedWriter]
+pub struct EncryptedReader {
+    words: WordBuffer,
+}
+
+impl EncryptedReader {
+    /// Construct an EncryptedReader
+    ///
+    /// Reads, authenticates and decrypts a whole frame from `inner`. Fails
+    /// with [Error::DeserializeBadCiphertext] if the frame was altered or
+    /// encrypted with another key.
+    pub fn new<R: WordRead>(mut inner: R, key: &[u8; 32]) -> Result<Self> {
+        let mut nonce = [0u8; NONCE_WORDS * WORD_SIZE];
+        inner.read_padded_bytes(&mut nonce)?;
+        let mut len = 0u32;
+        inner.read_words(core::slice::from_mut(&mut len))?;
+        let ciphertext = read_padded_bytes(&mut inner, len as usize)?;
+        let bytes = ChaCha20Poly1305::new(key.into())
+            .decrypt(Nonce::from_slice(&nonce), ciphertext.as_slice())
+            .map_err(|_| Error::DeserializeBadCiphertext)?;
+        let words = le_bytes_to_words(&bytes).ok_or(Error::DeserializeBadCiphertext)?;
+        Ok(EncryptedReader {
+            words: WordBuffer::new(words),
+        })
+    }
+}
+
+impl WordRead for EncryptedReader {
+    fn read_words(&mut self, words: &mut [u32]) -> risc0_zkvm::serde::Result<()> {
+        self.words.read_words(words)
+    }
+
+    fn read_padded_bytes(&mut self, bytes: &mut [u8]) -> risc0_zkvm::serde::Result<()> {
+        self.words.read_padded_bytes(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::String, vec::Vec};
+
+    use super::*;
+
+    const KEY: [u8; 32] = [7; 32];
+    const NONCE: [u8; 12] = *b"input-000001";
+
+    #[test]
+    fn test_round_trip() {
+        let input = (String::from("private input"), [3u64; 4]);
+        let data = to_vec_encrypted(&input, &KEY, NONCE).unwrap();
+        let plain = crate::to_vec_compact(&input).unwrap();
+        // The nonce, the length word, the payload and the tag
+        assert_eq!(NONCE_WORDS + 1 + plain.len() + 4, data.len());
+        assert!(!data.windows(plain.len()).any(|w| w == plain));
+        let output: (String, [u64; 4]) = from_slice_encrypted(&data, &KEY).unwrap();
+        assert_eq!(input, output);
+
+        let bytes: Vec<u8> = data.iter().flat_map(|w| w.to_le_bytes()).collect();
+        let output: (String, [u64; 4]) = from_slice_encrypted(&bytes, &KEY).unwrap();
+        assert_eq!(input, output);
+    }
+
+    #[test]
+    fn test_empty() {
+        let data = to_vec_encrypted(&(), &KEY, NONCE).unwrap();
+        from_slice_encrypted::<(), _>(&data, &KEY).unwrap();
+    }
+
+    #[test]
+    fn test_tampered() {
+        let data = to_vec_encrypted(&[1u32; 8], &KEY, NONCE).unwrap();
+        for index in [0, NONCE_WORDS + 1, data.len() - 1] {
+            let mut data = data.clone();
+            data[index] ^= 1;
+            assert_eq!(
+                Err(Error::DeserializeBadCiphertext),
+                from_slice_encrypted::<[u32; 8], _>(&data, &KEY)
+            );
+        }
+        assert_eq!(
+            Err(Error::DeserializeBadCiphertext),
+            from_slice_encrypted::<[u32; 8], _>(&data, &[8; 32])
+        );
+        // The length isn't authenticated, so it is only trusted up to the end
+        // of input.
+        let mut data = data;
+        data[NONCE_WORDS] = u32::MAX;
+        assert_eq!(
+            Err(Error::DeserializeUnexpectedEnd {
+                needed: 0,
+                available: None
+            }),
+            from_slice_encrypted::<[u32; 8], _>(&data, &KEY)
+        );
+    }
+}
@@ -0,0 +1,119 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::vec::Vec;
+
+use risc0_zkvm::serde::WordWrite;
+
+use super::{err::Result, to_vec_compact_with_config, Config, Serializer};
+
+/// Serialize a value whose type is only known at runtime to a vector of u32
+/// words
+///
+/// The output is the same as that of [to_vec_compact](crate::to_vec_compact)
+/// on the concrete value.
+pub fn to_vec_erased(value: &dyn erased_serde::Serialize) -> Result<Vec<u32>> {
+    to_vec_compact_with_config(value, Config::default())
+}
+
+/// Serialize a value whose type is only known at runtime to a vector of u32
+/// words using the given configuration.
+pub fn to_vec_erased_with_config(
+    value: &dyn erased_serde::Serialize,
+    config: Config,
+) -> Result<Vec<u32>> {
+    to_vec_compact_with_config(value, config)
+}
+
+impl<W: WordWrite> Serializer<W> {
+    /// Borrow the serializer as an `erased_serde::Serializer`
+    ///
+    /// This lets code that only sees `dyn erased_serde::Serializer`, such as
+    /// a plugin, write to this serializer. Errors reach the caller as
+    /// `erased_serde::Error`, which only keeps their message.
+    /// ```rust
+    /// use l2r0_small_serde::Serializer;
+    ///
+    /// let values: Vec<Box<dyn erased_serde::Serialize>> = vec![Box::new(7u32), Box::new("abc")];
+    /// let mut words = Vec::new();
+    /// let mut serializer = Serializer::new(&mut words);
+    /// for value in &values {
+    ///     value.erased_serialize(&mut serializer.erase()).unwrap();
+    /// }
+    /// assert_eq!(vec![7, 3, 0x636261], words);
+    /// ```
+    pub fn erase(&mut self) -> impl erased_serde::Serializer + '_ {
+        <dyn erased_serde::Serializer>::erase(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{boxed::Box, collections::BTreeMap, string::String, vec, vec::Vec};
+
+    use serde::Serialize;
+
+    use super::*;
+    use crate::to_vec_compact;
+
+    #[derive(Serialize)]
+    struct Event {
+        height: u64,
+        flags: [bool; 3],
+        note: Option<String>,
+    }
+
+    fn values() -> Vec<Box<dyn erased_serde::Serialize>> {
+        vec![
+            Box::new(Event {
+                height: 9,
+                flags: [true, false, true],
+                note: Some("erased".into()),
+            }),
+            Box::new(BTreeMap::from([(1u8, 'a'), (2, 'b')])),
+            Box::new((5u8, 6u8)),
+            Box::new(()),
+        ]
+    }
+
+    #[test]
+    fn test_to_vec_erased() {
+        let values = values();
+        assert_eq!(
+            to_vec_compact(&BTreeMap::from([(1u8, 'a'), (2, 'b')])).unwrap(),
+            to_vec_erased(values[1].as_ref()).unwrap()
+        );
+        let config = Config::new().self_describing(true);
+        assert_eq!(
+            to_vec_compact_with_config(&(5u8, 6u8), config).unwrap(),
+            to_vec_erased_with_config(values[2].as_ref(), config).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_erase() {
+        let mut erased = Vec::new();
+        let mut serializer = Serializer::new(&mut erased);
+        for value in values() {
+            value.erased_serialize(&mut serializer.erase()).unwrap();
+        }
+        serializer.finish().unwrap();
+
+        let mut expected = Vec::new();
+        for value in values() {
+            expected.extend(to_vec_erased(value.as_ref()).unwrap());
+        }
+        assert_eq!(expected, erased);
+    }
+}
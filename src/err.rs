@@ -12,30 +12,113 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+#[cfg(feature = "alloc")]
 use alloc::string::{String, ToString};
-use core::fmt::{Display, Formatter};
+use core::fmt::{Debug, Display, Formatter, Write};
 
 /// Errors used by Serde
+///
+/// Without the `alloc` feature, custom errors hold their message in a
+/// [Message] of fixed capacity.
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub enum Error {
     /// A custom error
+    #[cfg(feature = "alloc")]
     Custom(String),
+    /// A custom error
+    #[cfg(not(feature = "alloc"))]
+    Custom(Message),
     /// Found a bool that wasn't 0 or 1
     DeserializeBadBool,
     /// Found some nonzero bytes in the buffer
     DeserializeBadByte,
     /// Found an invalid unicode char
     DeserializeBadChar,
+    /// Found a checksum that doesn't match the data
+    DeserializeBadChecksum,
+    /// Found encrypted data that failed to authenticate
+    DeserializeBadCiphertext,
+    /// Found compressed data that failed to decompress
+    DeserializeBadCompression,
+    /// Found a malformed hex or base64 string
+    DeserializeBadEncoding,
+    /// Found a field element that isn't a 16-bit limb
+    DeserializeBadFieldElement,
+    /// Found a struct field whose size doesn't match its contents
+    DeserializeBadFieldSize,
+    /// Found a chunked frame larger than the maximum frame size
+    DeserializeBadFrame,
+    /// Found a versioned header that is missing or describes an unknown layout
+    DeserializeBadHeader,
+    /// Found nonzero words in the output padding
+    DeserializeBadPadding,
+    /// Found a node marker of the wrong kind
+    DeserializeBadMarker,
     /// Found an Option discriminant that wasn't 0 or 1
     DeserializeBadOption,
+    /// Followed a path to a value that isn't in the encoding
+    DeserializeBadPath,
     /// Tried to parse invalid utf-8
-    DeserializeBadUtf8,
+    DeserializeBadUtf8 {
+        /// Index of the first invalid byte of the string
+        offset: usize,
+    },
+    /// Found a variant tag that doesn't match exactly one variant
+    DeserializeBadVariant,
+    /// Found a reference to a string that wasn't interned
+    DeserializeBadStringRef,
+    /// Found a length that exceeds the input or the encoding
+    DeserializeLengthOverflow {
+        /// The length found, in elements
+        requested: usize,
+    },
+    /// Found an integer of another type whose value doesn't fit the type
+    /// decoded, with [Config::lenient_integers](crate::Config::lenient_integers)
+    DeserializeLossyInteger,
+    /// Found composites nested deeper than the deserializer supports
+    DeserializeTooDeep,
+    /// Found more struct fields than the type declares
+    DeserializeTooManyFields,
+    /// Found words after the end of the value
+    DeserializeTrailingWords {
+        /// Number of words after the value
+        count: usize,
+    },
     /// Unexpected end during deserialization
-    DeserializeUnexpectedEnd,
+    DeserializeUnexpectedEnd {
+        /// Number of words the read asked for
+        needed: usize,
+        /// Number of words left in the input, if its length is known
+        available: Option<usize>,
+    },
+    /// Found map keys that aren't in canonical order, or a repeated key
+    DeserializeUnsortedMap,
+    /// Found a versioned header of an unsupported format version
+    DeserializeUnsupportedVersion {
+        /// The version found
+        version: u16,
+    },
     /// Not supported
     NotSupported,
     /// The serialize buffer is full
     SerializeBufferFull,
+    /// Serialized a different number of elements than announced
+    SerializeLengthMismatch {
+        /// The announced number of elements
+        expected: usize,
+        /// The number of elements found
+        found: usize,
+    },
+    /// Tried to serialize a length that doesn't fit the encoding
+    SerializeLengthOverflow {
+        /// The length of the value
+        requested: usize,
+    },
+    /// A frame was left partially written
+    SerializeIncompleteFrame,
+    /// Tried to serialize composites nested deeper than the deserializer
+    /// supports
+    SerializeTooDeep,
 }
 
 /// A Result type for `risc0_zkvm::serde` operations that can fail
@@ -43,29 +126,113 @@ pub type Result<T> = core::result::Result<T, Error>;
 
 impl Display for Error {
     fn fmt(&self, formatter: &mut Formatter) -> core::fmt::Result {
-        formatter.write_str(match self {
+        let msg = match self {
+            #[cfg(feature = "alloc")]
             Self::Custom(msg) => msg,
+            #[cfg(not(feature = "alloc"))]
+            Self::Custom(msg) => return Display::fmt(msg, formatter),
             Self::DeserializeBadBool => "Found a bool that wasn't 0 or 1",
             Self::DeserializeBadByte => "Found some nonzero bytes in the buffer",
             Self::DeserializeBadChar => "Found an invalid unicode char",
+            Self::DeserializeBadChecksum => "Found a checksum that doesn't match the data",
+            Self::DeserializeBadCiphertext => "Found encrypted data that failed to authenticate",
+            Self::DeserializeBadCompression => "Found compressed data that failed to decompress",
+            Self::DeserializeBadEncoding => "Found a malformed hex or base64 string",
+            Self::DeserializeBadFieldElement => "Found a field element that isn't a 16-bit limb",
+            Self::DeserializeBadFieldSize => {
+                "Found a struct field whose size doesn't match its contents"
+            }
+            Self::DeserializeBadFrame => "Found a chunked frame larger than the maximum frame size",
+            Self::DeserializeBadHeader => {
+                "Found a versioned header that is missing or describes an unknown layout"
+            }
+            Self::DeserializeBadMarker => "Found a node marker of the wrong kind",
             Self::DeserializeBadOption => "Found an Option discriminant that wasn't 0 or 1",
-            Self::DeserializeBadUtf8 => "Tried to parse invalid utf-8",
-            Self::DeserializeUnexpectedEnd => "Unexpected end during deserialization",
+            Self::DeserializeBadPath => "Followed a path to a value that isn't in the encoding",
+            Self::DeserializeBadPadding => "Found nonzero words in the output padding",
+            Self::DeserializeBadUtf8 { offset } => {
+                return write!(formatter, "Tried to parse invalid utf-8 at byte {offset}");
+            }
+            Self::DeserializeBadVariant => {
+                "Found a variant tag that doesn't match exactly one variant"
+            }
+            Self::DeserializeBadStringRef => "Found a reference to a string that wasn't interned",
+            Self::DeserializeLengthOverflow { requested } => {
+                return write!(
+                    formatter,
+                    "Found a length of {requested} that exceeds the input"
+                );
+            }
+            Self::DeserializeLossyInteger => {
+                "Found an integer that doesn't fit the type it is decoded as"
+            }
+            Self::DeserializeTooDeep => "Found composites nested too deeply",
+            Self::DeserializeTooManyFields => "Found more struct fields than the type declares",
+            Self::DeserializeTrailingWords { count } => {
+                return write!(formatter, "Found {count} words after the end of the value");
+            }
+            Self::DeserializeUnexpectedEnd { needed, available } => {
+                return match available {
+                    Some(available) => write!(
+                        formatter,
+                        "Unexpected end during deserialization: needed {needed} words, \
+                         {available} available"
+                    ),
+                    None => write!(
+                        formatter,
+                        "Unexpected end during deserialization: needed {needed} words"
+                    ),
+                };
+            }
+            Self::DeserializeUnsortedMap => "Found map keys out of canonical order",
+            Self::DeserializeUnsupportedVersion { version } => {
+                return write!(
+                    formatter,
+                    "Found a header of unsupported format version {version}"
+                );
+            }
             Self::NotSupported => "Not supported",
             Self::SerializeBufferFull => "The serialize buffer is full",
-        })
+            Self::SerializeIncompleteFrame => "A frame was left partially written",
+            Self::SerializeTooDeep => "Tried to serialize composites nested too deeply",
+            Self::SerializeLengthMismatch { expected, found } => {
+                return write!(
+                    formatter,
+                    "Serialized {found} elements instead of the announced {expected}"
+                );
+            }
+            Self::SerializeLengthOverflow { requested } => {
+                return write!(
+                    formatter,
+                    "Tried to serialize a length of {requested}, which doesn't fit the encoding"
+                );
+            }
+        };
+        formatter.write_str(msg)
     }
 }
 
-impl serde::ser::Error for Error {
+impl Error {
+    #[cfg(feature = "alloc")]
     fn custom<T: Display>(msg: T) -> Self {
         Error::Custom(msg.to_string())
     }
+
+    #[cfg(not(feature = "alloc"))]
+    fn custom<T: Display>(msg: T) -> Self {
+        Error::Custom(Message::new(msg))
+    }
+}
+
+impl serde::ser::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Error::custom(msg)
+    }
 }
 
 impl serde::de::Error for Error {
     fn custom<T: Display>(msg: T) -> Self {
-        Error::Custom(msg.to_string())
+        Error::custom(msg)
     }
 }
 
@@ -75,14 +242,151 @@ impl serde::ser::StdError for Error {}
 impl From<risc0_zkvm::serde::Error> for Error {
     fn from(value: risc0_zkvm::serde::Error) -> Self {
         match value {
+            #[cfg(feature = "alloc")]
             risc0_zkvm::serde::Error::Custom(s) => Error::Custom(s),
+            #[cfg(not(feature = "alloc"))]
+            risc0_zkvm::serde::Error::Custom(s) => Error::Custom(Message::new(s)),
             risc0_zkvm::serde::Error::DeserializeBadBool => Error::DeserializeBadBool,
             risc0_zkvm::serde::Error::DeserializeBadChar => Error::DeserializeBadChar,
             risc0_zkvm::serde::Error::DeserializeBadOption => Error::DeserializeBadOption,
-            risc0_zkvm::serde::Error::DeserializeBadUtf8 => Error::DeserializeBadUtf8,
-            risc0_zkvm::serde::Error::DeserializeUnexpectedEnd => Error::DeserializeUnexpectedEnd,
+            // The zkVM errors carry no context, so the fields are left at zero or unknown.
+            risc0_zkvm::serde::Error::DeserializeBadUtf8 => Error::DeserializeBadUtf8 { offset: 0 },
+            risc0_zkvm::serde::Error::DeserializeUnexpectedEnd => Error::DeserializeUnexpectedEnd {
+                needed: 0,
+                available: None,
+            },
             risc0_zkvm::serde::Error::NotSupported => Error::NotSupported,
             risc0_zkvm::serde::Error::SerializeBufferFull => Error::SerializeBufferFull,
         }
     }
 }
+
+/// An error message held without allocating
+///
+/// Messages longer than [Message::CAPACITY] bytes are cut at a char boundary,
+/// and display with a trailing `...`.
+#[derive(Clone, Eq, PartialEq)]
+pub struct Message {
+    buf: [u8; Message::CAPACITY],
+    len: usize,
+    truncated: bool,
+}
+
+impl Message {
+    /// Maximum number of bytes of the message kept
+    pub const CAPACITY: usize = 64;
+
+    /// Format `msg` into a Message
+    pub fn new<T: Display>(msg: T) -> Self {
+        let mut message = Message {
+            buf: [0; Message::CAPACITY],
+            len: 0,
+            truncated: false,
+        };
+        // Writing never fails, the excess is dropped instead.
+        let _ = write!(message, "{msg}");
+        message
+    }
+
+    /// The message as kept, without the marker of truncation
+    pub fn as_str(&self) -> &str {
+        // Only whole chars are ever copied into the buffer.
+        core::str::from_utf8(&self.buf[..self.len]).unwrap()
+    }
+
+    /// Whether part of the message was dropped
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+}
+
+impl Write for Message {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        if self.truncated {
+            return Ok(());
+        }
+        let mut end = s.len().min(Message::CAPACITY - self.len);
+        while !s.is_char_boundary(end) {
+            end -= 1;
+        }
+        self.buf[self.len..self.len + end].copy_from_slice(&s.as_bytes()[..end]);
+        self.len += end;
+        self.truncated = end < s.len();
+        Ok(())
+    }
+}
+
+impl Display for Message {
+    fn fmt(&self, formatter: &mut Formatter) -> core::fmt::Result {
+        formatter.write_str(self.as_str())?;
+        if self.truncated {
+            formatter.write_str("...")?;
+        }
+        Ok(())
+    }
+}
+
+impl Debug for Message {
+    fn fmt(&self, formatter: &mut Formatter) -> core::fmt::Result {
+        Debug::fmt(self.as_str(), formatter)?;
+        if self.truncated {
+            formatter.write_str("...")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::fmt::Write;
+
+    use super::Message;
+
+    /// Formats into a stack buffer, to check Display without allocating
+    struct Buf {
+        buf: [u8; 256],
+        len: usize,
+    }
+
+    impl Write for Buf {
+        fn write_str(&mut self, s: &str) -> core::fmt::Result {
+            self.buf[self.len..self.len + s.len()].copy_from_slice(s.as_bytes());
+            self.len += s.len();
+            Ok(())
+        }
+    }
+
+    fn display(msg: &Message) -> Buf {
+        let mut buf = Buf {
+            buf: [0; 256],
+            len: 0,
+        };
+        write!(buf, "{msg}").unwrap();
+        buf
+    }
+
+    #[test]
+    fn test_message() {
+        let msg = Message::new(format_args!("missing field `{}`", "nonce"));
+        assert_eq!("missing field `nonce`", msg.as_str());
+        assert!(!msg.is_truncated());
+        assert_eq!(b"missing field `nonce`", &display(&msg).buf[..21]);
+    }
+
+    #[test]
+    fn test_truncated() {
+        // 'é' takes two bytes, so the last one doesn't fit.
+        let long = [("é", 31), ("ab", 1), ("é", 1)];
+        let mut msg = Message::new("");
+        for (s, count) in long {
+            for _ in 0..count {
+                msg.write_str(s).unwrap();
+            }
+        }
+        assert!(msg.is_truncated());
+        assert_eq!(Message::CAPACITY, msg.as_str().len());
+        assert!(msg.as_str().ends_with("éab"));
+        let out = display(&msg);
+        assert_eq!(b"ab...", &out.buf[out.len - 5..out.len]);
+    }
+}
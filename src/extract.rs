@@ -0,0 +1,408 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::{fmt, marker::PhantomData};
+
+use serde::{
+    de::{DeserializeSeed, Error as _, IgnoredAny, MapAccess, SeqAccess, Visitor},
+    Deserialize,
+};
+
+use super::{
+    err::{Error, Result},
+    word_value::Shape,
+    Deserializer,
+};
+
+/// Decode the value at `path` within `words`, which hold a value of the given
+/// shape
+///
+/// Every step of `path` selects a part of the composite value reached so far:
+/// the field of a [Shape::Tuple], the element of a [Shape::Seq], the entry of
+/// a [Shape::Map] followed by 0 for its key or 1 for its value, 0 for the
+/// contents of a present [Shape::Option], or the tag of a [Shape::Enum] that
+/// the variant must have. The values before the one selected are skipped
+/// without being built, and those after it aren't read at all.
+/// ```rust
+/// use l2r0_small_serde::{extract_field, to_vec_compact, word_value::Shape};
+/// use serde::Serialize;
+///
+/// #[derive(Serialize)]
+/// struct Output {
+///     logs: Vec<String>,
+///     status: u32,
+/// }
+///
+/// let output = Output { logs: vec!["a".repeat(1000); 100], status: 3 };
+/// let words = to_vec_compact(&output).unwrap();
+/// let shape = Shape::Tuple(vec![Shape::Seq(Box::new(Shape::Str)), Shape::Word]);
+/// assert_eq!(3u32, extract_field::<u32>(&words, &shape, &[1]).unwrap());
+/// ```
+///
+/// Fails with [Error::DeserializeBadPath] if the path leads to a field, element
+/// or variant that isn't there. `words` must have been serialized with the
+/// default configuration.
+pub fn extract_field<'de, T: Deserialize<'de>>(
+    words: &'de [u32],
+    shape: &Shape,
+    path: &[usize],
+) -> Result<T> {
+    let mut deserializer = Deserializer::from_words(words);
+    Extract {
+        shape,
+        path,
+        phantom: PhantomData,
+    }
+    .deserialize(&mut deserializer)
+}
+
+fn bad_path<E: serde::de::Error>() -> E {
+    E::custom(Error::DeserializeBadPath)
+}
+
+fn missing<E: serde::de::Error>() -> E {
+    E::invalid_length(0, &"more elements")
+}
+
+/// Decodes the `T` at `path` within a value of `shape`
+struct Extract<'a, T> {
+    shape: &'a Shape,
+    path: &'a [usize],
+    phantom: PhantomData<T>,
+}
+
+impl<'a, T> Extract<'a, T> {
+    fn next(&self, shape: &'a Shape, path: &'a [usize]) -> Self {
+        Extract {
+            shape,
+            path,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<'de, T: Deserialize<'de>> DeserializeSeed<'de> for Extract<'_, T> {
+    type Value = T;
+
+    fn deserialize<D: serde::Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> core::result::Result<T, D::Error> {
+        if self.path.is_empty() {
+            return T::deserialize(deserializer);
+        }
+        match self.shape {
+            Shape::Option(_) => deserializer.deserialize_option(self),
+            Shape::Seq(_) => deserializer.deserialize_seq(self),
+            Shape::Tuple(shapes) => deserializer.deserialize_tuple(shapes.len(), self),
+            Shape::Map(..) => deserializer.deserialize_map(self),
+            Shape::Enum(_) => deserializer.deserialize_tuple(2, self),
+            _ => Err(bad_path()),
+        }
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Visitor<'de> for Extract<'_, T> {
+    type Value = T;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "a value of shape {:?}", self.shape)
+    }
+
+    fn visit_none<E: serde::de::Error>(self) -> core::result::Result<T, E> {
+        Err(bad_path())
+    }
+
+    fn visit_some<D: serde::Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> core::result::Result<T, D::Error> {
+        match (self.shape, self.path) {
+            (Shape::Option(shape), [0, rest @ ..]) => {
+                self.next(shape, rest).deserialize(deserializer)
+            }
+            _ => Err(bad_path()),
+        }
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> core::result::Result<T, A::Error> {
+        let [index, rest @ ..] = self.path else {
+            unreachable!()
+        };
+        match self.shape {
+            Shape::Seq(shape) => {
+                for _ in 0..*index {
+                    seq.next_element_seed(Skip(shape))?.ok_or_else(bad_path)?;
+                }
+                seq.next_element_seed(self.next(shape, rest))?
+                    .ok_or_else(bad_path)
+            }
+            Shape::Tuple(shapes) => {
+                let shape = shapes.get(*index).ok_or_else(bad_path)?;
+                for shape in &shapes[..*index] {
+                    seq.next_element_seed(Skip(shape))?.ok_or_else(missing)?;
+                }
+                seq.next_element_seed(self.next(shape, rest))?
+                    .ok_or_else(missing)
+            }
+            Shape::Enum(shapes) => {
+                let tag: u32 = seq.next_element()?.ok_or_else(missing)?;
+                if tag as usize != *index {
+                    return Err(bad_path());
+                }
+                let shape = shapes
+                    .get(*index)
+                    .ok_or_else(|| A::Error::custom(Error::DeserializeBadVariant))?;
+                seq.next_element_seed(self.next(shape, rest))?
+                    .ok_or_else(missing)
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> core::result::Result<T, A::Error> {
+        let (Shape::Map(key, value), [index, rest @ ..]) = (self.shape, self.path) else {
+            unreachable!()
+        };
+        for _ in 0..*index {
+            map.next_entry_seed(Skip(key), Skip(value))?
+                .ok_or_else(bad_path)?;
+        }
+        match rest {
+            [0, rest @ ..] => map
+                .next_key_seed(self.next(key, rest))?
+                .ok_or_else(bad_path),
+            [1, rest @ ..] => {
+                map.next_key_seed(Skip(key))?.ok_or_else(bad_path)?;
+                map.next_value_seed(self.next(value, rest))
+            }
+            _ => Err(bad_path()),
+        }
+    }
+}
+
+/// Reads past a value of the given shape without building it
+struct Skip<'a>(&'a Shape);
+
+impl<'de> DeserializeSeed<'de> for Skip<'_> {
+    type Value = ();
+
+    fn deserialize<D: serde::Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> core::result::Result<(), D::Error> {
+        match self.0 {
+            Shape::Unit => <()>::deserialize(deserializer),
+            Shape::Bool => bool::deserialize(deserializer).map(drop),
+            Shape::Byte => u8::deserialize(deserializer).map(drop),
+            Shape::Word => u32::deserialize(deserializer).map(drop),
+            Shape::DWord => u64::deserialize(deserializer).map(drop),
+            Shape::Str => deserializer.deserialize_str(IgnoredAny).map(drop),
+            Shape::Bytes => deserializer.deserialize_bytes(IgnoredAny).map(drop),
+            Shape::Option(_) => deserializer.deserialize_option(self),
+            Shape::Seq(_) => deserializer.deserialize_seq(self),
+            Shape::Tuple(shapes) => deserializer.deserialize_tuple(shapes.len(), self),
+            Shape::Map(..) => deserializer.deserialize_map(self),
+            Shape::Enum(_) => deserializer.deserialize_tuple(2, self),
+        }
+    }
+}
+
+impl<'de> Visitor<'de> for Skip<'_> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "a value of shape {:?}", self.0)
+    }
+
+    fn visit_none<E>(self) -> core::result::Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_some<D: serde::Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> core::result::Result<(), D::Error> {
+        let Shape::Option(shape) = self.0 else {
+            unreachable!()
+        };
+        Skip(shape).deserialize(deserializer)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> core::result::Result<(), A::Error> {
+        match self.0 {
+            Shape::Seq(shape) => while seq.next_element_seed(Skip(shape))?.is_some() {},
+            Shape::Tuple(shapes) => {
+                for shape in shapes {
+                    seq.next_element_seed(Skip(shape))?.ok_or_else(missing)?;
+                }
+            }
+            Shape::Enum(shapes) => {
+                let tag: u32 = seq.next_element()?.ok_or_else(missing)?;
+                let shape = shapes
+                    .get(tag as usize)
+                    .ok_or_else(|| A::Error::custom(Error::DeserializeBadVariant))?;
+                seq.next_element_seed(Skip(shape))?.ok_or_else(missing)?;
+            }
+            _ => unreachable!(),
+        }
+        Ok(())
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> core::result::Result<(), A::Error> {
+        let Shape::Map(key, value) = self.0 else {
+            unreachable!()
+        };
+        while map.next_entry_seed(Skip(key), Skip(value))?.is_some() {}
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{
+        boxed::Box,
+        collections::BTreeMap,
+        string::{String, ToString},
+        vec,
+        vec::Vec,
+    };
+
+    use serde::Serialize;
+
+    use super::*;
+    use crate::to_vec_compact;
+
+    #[derive(Serialize)]
+    enum Event {
+        Start,
+        Log(String),
+        Stop { code: u32, flags: [bool; 3] },
+    }
+
+    #[derive(Serialize)]
+    struct Output {
+        version: u8,
+        events: Vec<Event>,
+        labels: BTreeMap<String, (u8, u64)>,
+        parent: Option<[u32; 2]>,
+        status: u8,
+    }
+
+    fn output() -> Output {
+        Output {
+            version: 2,
+            events: vec![
+                Event::Start,
+                Event::Log("started".into()),
+                Event::Stop {
+                    code: 9,
+                    flags: [true, false, true],
+                },
+            ],
+            labels: BTreeMap::from([("a".into(), (1, 10)), ("b".into(), (2, 20))]),
+            parent: Some([5, 6]),
+            status: 7,
+        }
+    }
+
+    fn shape() -> Shape {
+        let event = Shape::Enum(vec![
+            Shape::Unit,
+            Shape::Str,
+            Shape::Tuple(vec![
+                Shape::Word,
+                Shape::Tuple(vec![Shape::Bool, Shape::Bool, Shape::Bool]),
+            ]),
+        ]);
+        Shape::Tuple(vec![
+            Shape::Byte,
+            Shape::Seq(Box::new(event)),
+            Shape::Map(
+                Box::new(Shape::Str),
+                Box::new(Shape::Tuple(vec![Shape::Byte, Shape::DWord])),
+            ),
+            Shape::Option(Box::new(Shape::Tuple(vec![Shape::Word, Shape::Word]))),
+            Shape::Byte,
+        ])
+    }
+
+    #[test]
+    fn test_extract() {
+        let words = to_vec_compact(&output()).unwrap();
+        let shape = shape();
+        assert_eq!(7u8, extract_field::<u8>(&words, &shape, &[4]).unwrap());
+        assert_eq!(2u8, extract_field::<u8>(&words, &shape, &[0]).unwrap());
+        assert_eq!(
+            "started",
+            extract_field::<String>(&words, &shape, &[1, 1, 1]).unwrap()
+        );
+        assert_eq!(
+            9u32,
+            extract_field::<u32>(&words, &shape, &[1, 2, 2, 0]).unwrap()
+        );
+        assert_eq!(
+            [true, false, true],
+            extract_field::<[bool; 3]>(&words, &shape, &[1, 2, 2, 1]).unwrap()
+        );
+        assert_eq!(
+            "b",
+            extract_field::<String>(&words, &shape, &[2, 1, 0]).unwrap()
+        );
+        assert_eq!(
+            20u64,
+            extract_field::<u64>(&words, &shape, &[2, 1, 1, 1]).unwrap()
+        );
+        assert_eq!(
+            6u32,
+            extract_field::<u32>(&words, &shape, &[3, 0, 1]).unwrap()
+        );
+        // The empty path decodes the whole value.
+        let (a, b): (u32, u32) = extract_field(
+            &words[words.len() - 3..],
+            &Shape::Tuple(vec![Shape::Word, Shape::Word]),
+            &[],
+        )
+        .unwrap();
+        assert_eq!((5, 6), (a, b));
+    }
+
+    #[test]
+    fn test_bad_path() {
+        let words = to_vec_compact(&output()).unwrap();
+        let shape = shape();
+        let bad_path = Err(Error::Custom(Error::DeserializeBadPath.to_string()));
+        for path in [
+            &[5][..],
+            &[1, 3],
+            // The first event isn't a log.
+            &[1, 0, 1],
+            &[2, 2, 0],
+            &[2, 0, 2],
+            &[0, 0],
+        ] {
+            assert_eq!(
+                bad_path,
+                extract_field::<u8>(&words, &shape, path),
+                "{path:?}"
+            );
+        }
+        let none = to_vec_compact(&None::<u32>).unwrap();
+        let option = Shape::Option(Box::new(Shape::Word));
+        assert_eq!(
+            Err(Error::Custom(Error::DeserializeBadPath.to_string())),
+            extract_field::<u32>(&none, &option, &[0])
+        );
+    }
+}
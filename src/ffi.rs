@@ -0,0 +1,324 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A C interface for tools written in other languages
+//!
+//! Services in e.g. Go or Python can produce guest inputs and read journals
+//! without reimplementing the encoding. Values cross the boundary as JSON,
+//! described by a JSON [Shape]:
+//! ```c
+//! uint32_t *words;
+//! size_t len;
+//! char *error;
+//! if (l2r0_encode("{\"Tuple\":[\"Word\",{\"Seq\":\"Byte\"}]}", "[7,[1,2]]",
+//!                 &words, &len, &error) != 0) {
+//!     fprintf(stderr, "%s\n", error);
+//!     l2r0_free_string(error);
+//! }
+//! ```
+//!
+//! Values are the JSON rendering of a [Value]: every number is the raw word or
+//! double word, so signed integers are given in two's complement and floats by
+//! their bits. An option is `null` or its contents, a variant the array of its
+//! tag and contents, and bytes an array of numbers. Decoding renders a value
+//! the same way, so its output can be encoded again.
+//!
+//! The crate is built as an `rlib`; link the functions into a C library with
+//! e.g. `cargo rustc --release --features ffi --crate-type staticlib`.
+//!
+//! Every function returns 0 on success. On failure it returns -1 and, unless
+//! `error` is null, stores a message there. All buffers returned must be
+//! released with [l2r0_free_words] or [l2r0_free_string].
+
+use alloc::{boxed::Box, ffi::CString, string::String, vec::Vec};
+use core::ffi::{c_char, CStr};
+
+use serde::de::DeserializeSeed;
+
+use super::{
+    err::{Error, Result},
+    to_vec_compact,
+    word_value::{Shape, Value},
+};
+
+/// Report a JSON failure as a custom error
+fn json(e: serde_json::Error) -> Error {
+    <Error as serde::de::Error>::custom(e)
+}
+
+/// Borrow a NUL-terminated UTF-8 argument
+///
+/// # Safety
+///
+/// `ptr` must point to a NUL-terminated string that outlives the borrow.
+unsafe fn arg<'a>(ptr: *const c_char) -> Result<&'a str> {
+    if ptr.is_null() {
+        return Err(Error::Custom("null string argument".into()));
+    }
+    CStr::from_ptr(ptr)
+        .to_str()
+        .map_err(|e| Error::DeserializeBadUtf8 {
+            offset: e.valid_up_to(),
+        })
+}
+
+fn parse_shape(shape: &str) -> Result<Shape> {
+    serde_json::from_str(shape).map_err(json)
+}
+
+fn encode(shape: &str, value: &str) -> Result<Vec<u32>> {
+    let shape = parse_shape(shape)?;
+    let mut deserializer = serde_json::Deserializer::from_str(value);
+    let value: Value = (&shape).deserialize(&mut deserializer).map_err(json)?;
+    deserializer.end().map_err(json)?;
+    to_vec_compact(&value)
+}
+
+fn decode(shape: &str, words: &[u32]) -> Result<String> {
+    let value = Value::from_slice(words, &parse_shape(shape)?)?;
+    serde_json::to_string(&value).map_err(json)
+}
+
+/// Hand a string to the caller, who frees it with [l2r0_free_string]
+fn into_raw_string(s: String) -> *mut c_char {
+    // JSON escapes NUL, but an error message may quote one; drop it rather
+    // than failing.
+    CString::new(s.replace('\0', "")).unwrap().into_raw()
+}
+
+/// Store the outcome of a call, returning its status
+///
+/// # Safety
+///
+/// `error` must be null or valid for writes.
+unsafe fn finish<T>(result: Result<T>, error: *mut *mut c_char, ok: impl FnOnce(T)) -> i32 {
+    match result {
+        Ok(value) => {
+            ok(value);
+            0
+        }
+        Err(e) => {
+            if !error.is_null() {
+                *error = into_raw_string(alloc::format!("{e}"));
+            }
+            -1
+        }
+    }
+}
+
+/// Encode the JSON `value` as a value of the JSON `shape`
+///
+/// On success stores the words and their number in `words` and `len`.
+///
+/// # Safety
+///
+/// `shape` and `value` must be NUL-terminated strings. `words` and `len` must
+/// be valid for writes, and `error` null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn l2r0_encode(
+    shape: *const c_char,
+    value: *const c_char,
+    words: *mut *mut u32,
+    len: *mut usize,
+    error: *mut *mut c_char,
+) -> i32 {
+    let result = arg(shape).and_then(|shape| encode(shape, arg(value)?));
+    finish(result, error, |data| {
+        let data = data.into_boxed_slice();
+        *len = data.len();
+        *words = Box::into_raw(data).cast();
+    })
+}
+
+/// Decode `len` words as a value of the JSON `shape`, rendered as JSON
+///
+/// On success stores the JSON in `value`.
+///
+/// # Safety
+///
+/// `shape` must be a NUL-terminated string and `words` must point to `len`
+/// words, or be null if `len` is 0. `value` must be valid for writes, and
+/// `error` null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn l2r0_decode(
+    shape: *const c_char,
+    words: *const u32,
+    len: usize,
+    value: *mut *mut c_char,
+    error: *mut *mut c_char,
+) -> i32 {
+    let words = match len {
+        0 => &[],
+        _ => core::slice::from_raw_parts(words, len),
+    };
+    let result = arg(shape).and_then(|shape| decode(shape, words));
+    finish(result, error, |json| *value = into_raw_string(json))
+}
+
+/// Free words returned by [l2r0_encode]
+///
+/// # Safety
+///
+/// `words` and `len` must be as returned by [l2r0_encode], and not freed
+/// before.
+#[no_mangle]
+pub unsafe extern "C" fn l2r0_free_words(words: *mut u32, len: usize) {
+    if !words.is_null() {
+        drop(Box::from_raw(core::ptr::slice_from_raw_parts_mut(
+            words, len,
+        )));
+    }
+}
+
+/// Free a string returned by [l2r0_decode] or stored as an error
+///
+/// # Safety
+///
+/// `s` must be null or a string returned by this interface, not freed before.
+#[no_mangle]
+pub unsafe extern "C" fn l2r0_free_string(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{collections::BTreeMap, string::String, vec, vec::Vec};
+    use core::ptr;
+
+    use super::*;
+
+    const SHAPE: &CStr = c"{\"Tuple\":[\"Word\",{\"Seq\":\"Byte\"},\"Bytes\",{\"Option\":\"DWord\"},{\"Enum\":[\"Unit\",\"Str\"]},{\"Map\":[\"Word\",\"Bool\"]}]}";
+
+    #[derive(serde::Serialize)]
+    struct Input {
+        id: u32,
+        data: Vec<u8>,
+        #[serde(with = "serde_bytes_like")]
+        raw: Vec<u8>,
+        parent: Option<u64>,
+        label: Label,
+        seen: BTreeMap<u32, bool>,
+    }
+
+    #[derive(serde::Serialize)]
+    enum Label {
+        #[allow(dead_code)]
+        None,
+        Named(String),
+    }
+
+    mod serde_bytes_like {
+        pub fn serialize<S: serde::Serializer>(v: &[u8], s: S) -> Result<S::Ok, S::Error> {
+            s.serialize_bytes(v)
+        }
+    }
+
+    unsafe fn encode(value: &CStr) -> core::result::Result<Vec<u32>, String> {
+        let mut words = ptr::null_mut();
+        let mut len = 0;
+        let mut error = ptr::null_mut();
+        if l2r0_encode(
+            SHAPE.as_ptr(),
+            value.as_ptr(),
+            &mut words,
+            &mut len,
+            &mut error,
+        ) != 0
+        {
+            let message = CStr::from_ptr(error).to_str().unwrap().into();
+            l2r0_free_string(error);
+            return Err(message);
+        }
+        let data = core::slice::from_raw_parts(words, len).to_vec();
+        l2r0_free_words(words, len);
+        Ok(data)
+    }
+
+    unsafe fn decode(words: &[u32]) -> String {
+        let mut value = ptr::null_mut();
+        let status = l2r0_decode(
+            SHAPE.as_ptr(),
+            words.as_ptr(),
+            words.len(),
+            &mut value,
+            ptr::null_mut(),
+        );
+        assert_eq!(0, status);
+        let json = CStr::from_ptr(value).to_str().unwrap().into();
+        l2r0_free_string(value);
+        json
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let input = Input {
+            id: 7,
+            data: vec![1, 2, 3],
+            raw: vec![4, 5],
+            parent: Some(1 << 40),
+            label: Label::Named("main".into()),
+            seen: BTreeMap::from([(3, true)]),
+        };
+        let expected = to_vec_compact(&input).unwrap();
+        let json = c"[7, [1, 2, 3], [4, 5], 1099511627776, [1, \"main\"], {\"3\": true}]";
+        let words = unsafe { encode(json) }.unwrap();
+        assert_eq!(expected, words);
+
+        let output = unsafe { decode(&words) };
+        assert_eq!(
+            "[7,[1,2,3],[4,5],1099511627776,[1,\"main\"],{\"3\":true}]",
+            output
+        );
+        let output = CString::new(output).unwrap();
+        assert_eq!(expected, unsafe { encode(&output) }.unwrap());
+    }
+
+    #[test]
+    fn test_errors() {
+        let error = unsafe { encode(c"[7]") }.unwrap_err();
+        assert!(error.contains("more elements"), "{error}");
+
+        let mut value = ptr::null_mut();
+        let mut error = ptr::null_mut();
+        let status = unsafe { l2r0_decode(SHAPE.as_ptr(), ptr::null(), 0, &mut value, &mut error) };
+        assert_eq!(-1, status);
+        assert!(value.is_null());
+        unsafe { l2r0_free_string(error) };
+        // The error is optional
+        let status = unsafe {
+            l2r0_decode(
+                c"\"Word\"".as_ptr(),
+                ptr::null(),
+                0,
+                &mut value,
+                ptr::null_mut(),
+            )
+        };
+        assert_eq!(-1, status);
+        // A length word from the input doesn't allocate up front
+        let words = [0xffffffff];
+        let status = unsafe {
+            l2r0_decode(
+                c"{\"Seq\":\"Word\"}".as_ptr(),
+                words.as_ptr(),
+                words.len(),
+                &mut value,
+                ptr::null_mut(),
+            )
+        };
+        assert_eq!(-1, status);
+    }
+}
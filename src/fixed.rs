@@ -0,0 +1,179 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Output to fixed-capacity vectors
+//!
+//! These writers let a guest serialize into a `heapless::Vec` or an
+//! `arrayvec::ArrayVec` without the global allocator. A write that exceeds the
+//! capacity fails with [Error::SerializeBufferFull](crate::Error) and leaves
+//! the vector as it was before the write.
+
+use risc0_zkvm::serde::{Error as WordError, WordWrite};
+
+use super::{err::Result, Config, Serializer, WORD_SIZE};
+
+/// The words holding `bytes`, with the last one padded with zeros
+fn padded_words(bytes: &[u8]) -> impl Iterator<Item = u32> + '_ {
+    bytes.chunks(WORD_SIZE).map(|chunk| {
+        let mut word = [0u8; WORD_SIZE];
+        word[..chunk.len()].copy_from_slice(chunk);
+        u32::from_le_bytes(word)
+    })
+}
+
+/// Serialize to a `heapless::Vec` of u32 words
+#[cfg(feature = "heapless")]
+pub fn to_heapless_vec<T, const N: usize>(value: &T) -> Result<heapless::Vec<u32, N>>
+where
+    T: serde::Serialize + ?Sized,
+{
+    let mut vec = heapless::Vec::new();
+    let mut serializer = Serializer::with_config(HeaplessWriter::new(&mut vec), Config::default());
+    value.serialize(&mut serializer)?;
+    serializer.finish()?;
+    Ok(vec)
+}
+
+/// A WordWrite that appends to a `heapless::Vec`
+#[cfg(feature = "heapless")]
+pub struct HeaplessWriter<'a, const N: usize> {
+    vec: &'a mut heapless::Vec<u32, N>,
+}
+
+#[cfg(feature = "heapless")]
+impl<'a, const N: usize> HeaplessWriter<'a, N> {
+    /// Construct a HeaplessWriter that appends to `vec`
+    pub fn new(vec: &'a mut heapless::Vec<u32, N>) -> Self {
+        HeaplessWriter { vec }
+    }
+}
+
+#[cfg(feature = "heapless")]
+impl<const N: usize> WordWrite for HeaplessWriter<'_, N> {
+    fn write_words(&mut self, words: &[u32]) -> risc0_zkvm::serde::Result<()> {
+        self.vec
+            .extend_from_slice(words)
+            .map_err(|_| WordError::SerializeBufferFull)
+    }
+
+    fn write_padded_bytes(&mut self, bytes: &[u8]) -> risc0_zkvm::serde::Result<()> {
+        if self.vec.len() + bytes.len().div_ceil(WORD_SIZE) > N {
+            return Err(WordError::SerializeBufferFull);
+        }
+        for word in padded_words(bytes) {
+            self.vec.push(word).unwrap();
+        }
+        Ok(())
+    }
+}
+
+/// Serialize to an `arrayvec::ArrayVec` of u32 words
+#[cfg(feature = "arrayvec")]
+pub fn to_array_vec<T, const N: usize>(value: &T) -> Result<arrayvec::ArrayVec<u32, N>>
+where
+    T: serde::Serialize + ?Sized,
+{
+    let mut vec = arrayvec::ArrayVec::new();
+    let mut serializer = Serializer::with_config(ArrayVecWriter::new(&mut vec), Config::default());
+    value.serialize(&mut serializer)?;
+    serializer.finish()?;
+    Ok(vec)
+}
+
+/// A WordWrite that appends to an `arrayvec::ArrayVec`
+#[cfg(feature = "arrayvec")]
+pub struct ArrayVecWriter<'a, const N: usize> {
+    vec: &'a mut arrayvec::ArrayVec<u32, N>,
+}
+
+#[cfg(feature = "arrayvec")]
+impl<'a, const N: usize> ArrayVecWriter<'a, N> {
+    /// Construct an ArrayVecWriter that appends to `vec`
+    pub fn new(vec: &'a mut arrayvec::ArrayVec<u32, N>) -> Self {
+        ArrayVecWriter { vec }
+    }
+}
+
+#[cfg(feature = "arrayvec")]
+impl<const N: usize> WordWrite for ArrayVecWriter<'_, N> {
+    fn write_words(&mut self, words: &[u32]) -> risc0_zkvm::serde::Result<()> {
+        self.vec
+            .try_extend_from_slice(words)
+            .map_err(|_| WordError::SerializeBufferFull)
+    }
+
+    fn write_padded_bytes(&mut self, bytes: &[u8]) -> risc0_zkvm::serde::Result<()> {
+        if bytes.len().div_ceil(WORD_SIZE) > self.vec.remaining_capacity() {
+            return Err(WordError::SerializeBufferFull);
+        }
+        self.vec.extend(padded_words(bytes));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Error;
+
+    #[derive(serde::Serialize)]
+    struct Receipt<'a> {
+        id: u32,
+        flags: [bool; 3],
+        note: &'a str,
+    }
+
+    const RECEIPT: Receipt = Receipt {
+        id: 4,
+        flags: [true, true, false],
+        note: "fixed",
+    };
+
+    #[cfg(feature = "heapless")]
+    #[test]
+    fn test_heapless() {
+        let vec: heapless::Vec<u32, 5> = to_heapless_vec(&RECEIPT).unwrap();
+        assert_eq!([4, 0x0101, 5, 0x65786966, 0x64], vec.as_slice());
+        assert_eq!(
+            Err(Error::SerializeBufferFull),
+            to_heapless_vec::<_, 4>(&RECEIPT)
+        );
+
+        // A failed write leaves the vector as it was.
+        let mut vec = heapless::Vec::<u32, 3>::new();
+        let mut writer = HeaplessWriter::new(&mut vec);
+        writer.write_words(&[1, 2]).unwrap();
+        assert!(writer.write_padded_bytes(b"abcde").is_err());
+        assert!(writer.write_words(&[3, 4]).is_err());
+        assert_eq!([1, 2], vec.as_slice());
+    }
+
+    #[cfg(feature = "arrayvec")]
+    #[test]
+    fn test_arrayvec() {
+        let vec: arrayvec::ArrayVec<u32, 8> = to_array_vec(&RECEIPT).unwrap();
+        assert_eq!([4, 0x0101, 5, 0x65786966, 0x64], vec.as_slice());
+        assert_eq!(
+            Err(Error::SerializeBufferFull),
+            to_array_vec::<_, 4>(&RECEIPT)
+        );
+
+        let mut vec = arrayvec::ArrayVec::<u32, 3>::new();
+        let mut writer = ArrayVecWriter::new(&mut vec);
+        writer.write_words(&[1, 2]).unwrap();
+        assert!(writer.write_padded_bytes(b"abcde").is_err());
+        writer.write_padded_bytes(b"abc").unwrap();
+        assert_eq!([1, 2, 0x636261], vec.as_slice());
+    }
+}
@@ -0,0 +1,129 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::vec::Vec;
+
+use risc0_zkvm::serde::WordRead;
+use serde::de::DeserializeOwned;
+
+use super::{
+    buffer::read_words,
+    err::{Error, Result},
+    to_vec_compact_with_config, Config, Deserializer,
+};
+
+/// Serialize to a vector of u32 words preceded by their count
+///
+/// A consumer of a continuous stream reads the frame with [read_frame].
+pub fn to_vec_framed<T>(value: &T) -> Result<Vec<u32>>
+where
+    T: serde::Serialize + ?Sized,
+{
+    to_vec_framed_with_config(value, Config::default())
+}
+
+/// Serialize to a vector of u32 words preceded by their count, using the given
+/// configuration.
+pub fn to_vec_framed_with_config<T>(value: &T, config: Config) -> Result<Vec<u32>>
+where
+    T: serde::Serialize + ?Sized,
+{
+    let mut vec = alloc::vec![0];
+    vec.extend(to_vec_compact_with_config(value, config)?);
+    let len = vec.len() - 1;
+    vec[0] = u32::try_from(len).map_err(|_| Error::SerializeLengthOverflow { requested: len })?;
+    Ok(vec)
+}
+
+/// Read a frame written by [to_vec_framed] from `reader` and deserialize it.
+///
+/// Reads exactly the words of the frame, so the next frame can be read from
+/// the same stream. The value must take the whole frame.
+pub fn read_frame<T: DeserializeOwned, R: WordRead>(reader: R) -> Result<T> {
+    read_frame_with_config(reader, Config::default())
+}
+
+/// Read a frame written by [to_vec_framed_with_config] from `reader` and
+/// deserialize it using the given configuration.
+///
+/// `config` must match the configuration the frame was serialized with.
+pub fn read_frame_with_config<T: DeserializeOwned, R: WordRead>(
+    mut reader: R,
+    config: Config,
+) -> Result<T> {
+    let mut len = 0u32;
+    reader.read_words(core::slice::from_mut(&mut len))?;
+    let words = read_words(&mut reader, len as usize)?;
+    let mut deserializer = Deserializer::from_words_with_config(&words, config);
+    let value = T::deserialize(&mut deserializer)?;
+    deserializer.finish()?;
+    match words.len() - deserializer.position() {
+        0 => Ok(value),
+        count => Err(Error::DeserializeTrailingWords { count }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::String, vec, vec::Vec};
+
+    use super::*;
+    use crate::to_vec_compact;
+
+    #[test]
+    fn test_stream() {
+        let first = (String::from("frame"), 7u8);
+        let second = vec![1u64, 2];
+        let mut stream = to_vec_framed(&first).unwrap();
+        assert_eq!(to_vec_compact(&first).unwrap().len() as u32, stream[0]);
+        stream.extend(to_vec_framed(&second).unwrap());
+
+        let mut reader = stream.as_slice();
+        assert_eq!(first, read_frame::<(String, u8), _>(&mut reader).unwrap());
+        assert_eq!(second, read_frame::<Vec<u64>, _>(&mut reader).unwrap());
+        assert!(reader.is_empty());
+    }
+
+    #[test]
+    fn test_config() {
+        let config = Config::new().pad_to(4);
+        let data = to_vec_framed_with_config(&[1u32; 5], config).unwrap();
+        assert_eq!(9, data.len());
+        let output: [u32; 5] = read_frame_with_config(data.as_slice(), config).unwrap();
+        assert_eq!([1; 5], output);
+    }
+
+    #[test]
+    fn test_bad_frames() {
+        let data = to_vec_framed(&(1u32, 2u32)).unwrap();
+        assert_eq!(
+            Err(Error::DeserializeTrailingWords { count: 1 }),
+            read_frame::<u32, _>(data.as_slice())
+        );
+        assert_eq!(
+            Err(Error::DeserializeUnexpectedEnd {
+                needed: 0,
+                available: None
+            }),
+            read_frame::<(u32, u32), _>(&data[..2])
+        );
+        assert_eq!(
+            Err(Error::DeserializeUnexpectedEnd {
+                needed: 0,
+                available: None
+            }),
+            read_frame::<Vec<u32>, _>([u32::MAX, 0].as_slice())
+        );
+    }
+}
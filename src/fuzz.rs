@@ -0,0 +1,210 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Entry points for structure-aware fuzzing
+//!
+//! These helpers panic when a property of the encoding is violated, so they
+//! can be called directly from a `cargo fuzz` target:
+//! ```rust,ignore
+//! #![no_main]
+//! use libfuzzer_sys::fuzz_target;
+//!
+//! fuzz_target!(|data: &[u8]| {
+//!     l2r0_small_serde::fuzz::fuzz_roundtrip::<MyInput>(data);
+//! });
+//! ```
+//!
+//! [Config] and [VariantTag] implement `Arbitrary`, so a fuzzer can explore
+//! the configurations too. The generated padding is kept small to stay within
+//! the memory limits of fuzzers.
+
+use alloc::vec::Vec;
+use core::fmt::Debug;
+
+use arbitrary::{Arbitrary, Unstructured};
+use serde::{de::DeserializeOwned, Serialize};
+
+use super::{from_slice_compact_with_config, to_vec_compact_with_config, Config, VariantTag};
+
+impl<'a> Arbitrary<'a> for VariantTag {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(*u.choose(&[VariantTag::Word, VariantTag::Byte, VariantTag::NameHash])?)
+    }
+
+    fn size_hint(_depth: usize) -> (usize, Option<usize>) {
+        (1, Some(1))
+    }
+}
+
+impl<'a> Arbitrary<'a> for Config {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(Config {
+            variant_tag: u.arbitrary()?,
+            byte_option_tags: u.arbitrary()?,
+            pack_top_level: u.arbitrary()?,
+            field_count: u.arbitrary()?,
+            field_sizes: u.arbitrary()?,
+            intern_strings: u.arbitrary()?,
+            pad_to: u.int_in_range(0..=16)?,
+            self_describing: u.arbitrary()?,
+            human_readable: u.arbitrary()?,
+            big_endian: u.arbitrary()?,
+            lenient_integers: u.arbitrary()?,
+            replace_invalid_chars: u.arbitrary()?,
+        })
+    }
+
+    fn size_hint(depth: usize) -> (usize, Option<usize>) {
+        let (min, max) = VariantTag::size_hint(depth);
+        (min + 11, max.map(|max| max + 11))
+    }
+}
+
+/// Check that an arbitrary `T` built from `data` decodes to itself under an
+/// arbitrary configuration
+///
+/// Returns without checking anything if `data` is too short to build a value,
+/// or if the value doesn't serialize, e.g. a map with too many entries.
+///
+/// # Panics
+///
+/// Panics if the encoding fails to decode or decodes to a different value.
+pub fn fuzz_roundtrip<'a, T>(data: &'a [u8])
+where
+    T: Arbitrary<'a> + Serialize + DeserializeOwned + PartialEq + Debug,
+{
+    let mut u = Unstructured::new(data);
+    let (Ok(config), Ok(value)) = (Config::arbitrary(&mut u), T::arbitrary(&mut u)) else {
+        return;
+    };
+    let Ok(words) = to_vec_compact_with_config(&value, config) else {
+        return;
+    };
+    let output: T = match from_slice_compact_with_config(&words, config) {
+        Ok(output) => output,
+        Err(e) => panic!("failed to decode {value:?} with {config:?}: {e}"),
+    };
+    assert_eq!(value, output, "round trip with {config:?}");
+}
+
+/// Decode possibly corrupted `words` as a `T` with the default configuration
+///
+/// See [fuzz_decode_with_config].
+pub fn fuzz_decode<T>(words: &[u32]) -> Option<T>
+where
+    T: Serialize + DeserializeOwned,
+{
+    fuzz_decode_with_config(words, Config::default())
+}
+
+/// Decode possibly corrupted `words` as a `T`
+///
+/// Invalid input must be rejected with an error rather than a panic or an
+/// excessive allocation. When decoding succeeds, the value is encoded again,
+/// which must produce an encoding that is stable under another round trip.
+///
+/// # Panics
+///
+/// Panics if the value decoded doesn't round-trip.
+pub fn fuzz_decode_with_config<T>(words: &[u32], config: Config) -> Option<T>
+where
+    T: Serialize + DeserializeOwned,
+{
+    let value: T = from_slice_compact_with_config(words, config).ok()?;
+    let encoded = to_vec_compact_with_config(&value, config).ok()?;
+    let again: T = match from_slice_compact_with_config(&encoded, config) {
+        Ok(again) => again,
+        Err(e) => panic!("failed to decode re-encoded {encoded:?} with {config:?}: {e}"),
+    };
+    let twice: Vec<u32> =
+        to_vec_compact_with_config(&again, config).expect("a decoded value failed to serialize");
+    assert_eq!(encoded, twice, "re-encoding with {config:?}");
+    Some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{collections::BTreeMap, string::String, vec, vec::Vec};
+
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Arbitrary, Debug, PartialEq, Serialize, Deserialize)]
+    enum Op {
+        Nop,
+        Push(i64),
+        Call { target: u32, flags: [u8; 3] },
+    }
+
+    #[derive(Arbitrary, Debug, PartialEq, Serialize, Deserialize)]
+    struct Program {
+        name: String,
+        ops: Vec<Op>,
+        labels: BTreeMap<String, u32>,
+        entry: Option<(bool, char)>,
+        bytes: Vec<u8>,
+    }
+
+    /// Deterministic pseudo-random input
+    fn noise(seed: u64, len: usize) -> Vec<u8> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(6364136223846793005).wrapping_add(1);
+                (state >> 56) as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        for seed in 0..256 {
+            fuzz_roundtrip::<Program>(&noise(seed, 512));
+            fuzz_roundtrip::<(u128, Option<String>)>(&noise(seed, 64));
+        }
+        fuzz_roundtrip::<Program>(&[]);
+    }
+
+    #[test]
+    fn test_decode() {
+        let program = Program {
+            name: "main".into(),
+            ops: vec![Op::Push(-1), Op::Nop],
+            labels: BTreeMap::from([("start".into(), 0)]),
+            entry: Some((true, 'x')),
+            bytes: vec![1, 2, 3],
+        };
+        let config = Config::new().field_sizes(true);
+        let words = to_vec_compact_with_config(&program, config).unwrap();
+        assert_eq!(
+            Some(&program),
+            fuzz_decode_with_config(&words, config).as_ref()
+        );
+
+        for seed in 0..256 {
+            let data = noise(seed, 1024);
+            let mut u = Unstructured::new(&data);
+            let config = Config::arbitrary(&mut u).unwrap();
+            // Corrupt a valid encoding, as well as decoding pure noise.
+            let mut corrupted = words.clone();
+            let index = u.choose_index(corrupted.len()).unwrap();
+            corrupted[index] = u.arbitrary().unwrap();
+            fuzz_decode_with_config::<Program>(&corrupted, config);
+            let noise: Vec<u32> = u.arbitrary().unwrap();
+            fuzz_decode_with_config::<Program>(&noise, config);
+            fuzz_decode::<(Vec<Op>, u64)>(&noise);
+        }
+    }
+}
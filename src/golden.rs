@@ -0,0 +1,356 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Frozen encodings of sample values
+//!
+//! A [Golden] set holds sample values of the types whose encoding must not
+//! change, such as those read by an on-chain verifier. Its manifest lists the
+//! words of every value; checked into the repository, it makes a test fail
+//! when an upgrade of this crate or a change to a type alters any encoding.
+//! ```rust
+//! use l2r0_small_serde::golden::Golden;
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Debug, PartialEq, Serialize, Deserialize)]
+//! struct Receipt {
+//!     id: u32,
+//!     ok: bool,
+//! }
+//!
+//! let mut golden = Golden::new();
+//! golden.add("receipt", Receipt { id: 7, ok: true }).unwrap();
+//! golden.add("empty", Vec::<u64>::new()).unwrap();
+//!
+//! // Written once with `std::fs::write("golden.txt", golden.manifest())`,
+//! // and read back with `include_str!("golden.txt")`.
+//! let manifest = "receipt 0700000001000000\nempty 00000000\n";
+//! assert_eq!(manifest, golden.manifest());
+//! golden.verify(manifest).unwrap();
+//! ```
+//!
+//! Each line of the manifest holds the name of a vector and its words in the
+//! form of [encode_hex], separated by a space. Empty lines and lines starting
+//! with `#` are skipped, so the manifest can carry comments.
+
+use alloc::{boxed::Box, string::String, vec::Vec};
+use core::fmt::{Display, Formatter};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    decode_hex, encode_hex, err::Error, from_slice_compact_with_config, to_vec_compact_with_config,
+    Config, Result,
+};
+
+/// A difference between a [Golden] set and a manifest
+#[derive(Clone, Debug, PartialEq)]
+pub enum Mismatch {
+    /// The line of the manifest, counted from 1, is not a new name followed
+    /// by hex words
+    Malformed {
+        /// The number of the line
+        line: usize,
+    },
+    /// The vector isn't in the manifest
+    Missing {
+        /// The name of the vector
+        name: String,
+    },
+    /// The vector of the manifest isn't in the set
+    Unregistered {
+        /// The name of the vector
+        name: String,
+    },
+    /// The value encodes to other words than those of the manifest
+    Changed {
+        /// The name of the vector
+        name: String,
+        /// The words of the manifest
+        frozen: Vec<u32>,
+        /// The words the value encodes to now
+        current: Vec<u32>,
+    },
+    /// The words of the manifest fail to deserialize
+    Deserialize {
+        /// The name of the vector
+        name: String,
+        /// The error deserializing the words
+        error: Error,
+    },
+    /// The words of the manifest deserialize to a different value
+    RoundTrip {
+        /// The name of the vector
+        name: String,
+    },
+}
+
+impl Display for Mismatch {
+    fn fmt(&self, formatter: &mut Formatter) -> core::fmt::Result {
+        match self {
+            Self::Malformed { line } => write!(formatter, "Manifest line {line} is malformed"),
+            Self::Missing { name } => write!(formatter, "Vector {name} isn't in the manifest"),
+            Self::Unregistered { name } => {
+                write!(formatter, "Vector {name} of the manifest isn't registered")
+            }
+            Self::Changed {
+                name,
+                frozen,
+                current,
+            } => write!(
+                formatter,
+                "Vector {name} encodes to {current:?} instead of {frozen:?}"
+            ),
+            Self::Deserialize { name, error } => {
+                write!(formatter, "Vector {name} failed to deserialize: {error}")
+            }
+            Self::RoundTrip { name } => {
+                write!(formatter, "Vector {name} deserialized to a different value")
+            }
+        }
+    }
+}
+
+/// Whether the words decode to the value of a vector
+type Decodes = Box<dyn Fn(&[u32]) -> Result<bool>>;
+
+struct Vector {
+    name: String,
+    words: Vec<u32>,
+    decodes: Decodes,
+}
+
+/// A set of named sample values and their encodings
+#[derive(Default)]
+pub struct Golden {
+    config: Config,
+    vectors: Vec<Vector>,
+}
+
+impl Golden {
+    /// Construct an empty set encoded with the default configuration
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Construct an empty set encoded with `config`
+    pub fn with_config(config: Config) -> Self {
+        Golden {
+            config,
+            vectors: Vec::new(),
+        }
+    }
+
+    /// Add `value` as the vector called `name`
+    ///
+    /// Fails if the value fails to serialize.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `name` is empty, holds whitespace, starts with `#` or is
+    /// already in the set.
+    pub fn add<T>(&mut self, name: &str, value: T) -> Result<&mut Self>
+    where
+        T: Serialize + DeserializeOwned + PartialEq + 'static,
+    {
+        assert!(
+            !name.is_empty() && !name.starts_with('#') && !name.contains(char::is_whitespace),
+            "vector names are single words"
+        );
+        assert!(
+            self.vectors.iter().all(|vector| vector.name != name),
+            "vector {name} is already in the set"
+        );
+        let config = self.config;
+        let words = to_vec_compact_with_config(&value, config)?;
+        self.vectors.push(Vector {
+            name: name.into(),
+            words,
+            decodes: Box::new(move |words| {
+                Ok(from_slice_compact_with_config::<T, _>(words, config)? == value)
+            }),
+        });
+        Ok(self)
+    }
+
+    /// The manifest of the set, a line per vector in the order added
+    pub fn manifest(&self) -> String {
+        let mut out = String::new();
+        for vector in &self.vectors {
+            out.push_str(&vector.name);
+            out.push(' ');
+            out.push_str(&encode_hex(&vector.words));
+            out.push('\n');
+        }
+        out
+    }
+
+    /// Check the set against `manifest`
+    ///
+    /// Every vector must be in the manifest with the words its value encodes
+    /// to now, and those words must decode back to the value. Fails with all
+    /// the differences found.
+    pub fn verify(&self, manifest: &str) -> core::result::Result<(), Vec<Mismatch>> {
+        let mut mismatches = Vec::new();
+        let mut frozen: Vec<(&str, Vec<u32>)> = Vec::new();
+        for (index, line) in manifest.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (name, hex) = line.split_once(' ').unwrap_or((line, ""));
+            match decode_hex(hex.trim()) {
+                Ok(words) if frozen.iter().all(|(seen, _)| *seen != name) => {
+                    frozen.push((name, words))
+                }
+                _ => mismatches.push(Mismatch::Malformed { line: index + 1 }),
+            }
+        }
+        for vector in &self.vectors {
+            let name = || vector.name.clone();
+            let Some((_, words)) = frozen.iter().find(|(name, _)| *name == vector.name) else {
+                mismatches.push(Mismatch::Missing { name: name() });
+                continue;
+            };
+            if *words != vector.words {
+                mismatches.push(Mismatch::Changed {
+                    name: name(),
+                    frozen: words.clone(),
+                    current: vector.words.clone(),
+                });
+            }
+            match (vector.decodes)(words) {
+                Ok(true) => {}
+                Ok(false) => mismatches.push(Mismatch::RoundTrip { name: name() }),
+                Err(error) => mismatches.push(Mismatch::Deserialize {
+                    name: name(),
+                    error,
+                }),
+            }
+        }
+        for (name, _) in &frozen {
+            if self.vectors.iter().all(|vector| vector.name != *name) {
+                mismatches.push(Mismatch::Unregistered {
+                    name: (*name).into(),
+                });
+            }
+        }
+        match mismatches.is_empty() {
+            true => Ok(()),
+            false => Err(mismatches),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::String, vec};
+
+    use serde::{Deserialize, Serialize};
+
+    use super::{Golden, Mismatch};
+    use crate::{Config, Error};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Header {
+        version: u8,
+        root: [u32; 2],
+        parent: Option<u64>,
+    }
+
+    fn golden() -> Golden {
+        let mut golden = Golden::new();
+        golden
+            .add(
+                "header",
+                Header {
+                    version: 1,
+                    root: [2, 3],
+                    parent: Some(4),
+                },
+            )
+            .unwrap()
+            .add("name", String::from("hi"))
+            .unwrap()
+            .add("unit", ())
+            .unwrap();
+        golden
+    }
+
+    fn name(name: &str) -> String {
+        name.into()
+    }
+
+    #[test]
+    fn test_manifest() {
+        let golden = golden();
+        let manifest = golden.manifest();
+        assert_eq!(
+            "header 010000000200000003000000010000000400000000000000\n\
+             name 0200000068690000\n\
+             unit \n",
+            manifest
+        );
+        golden.verify(&manifest).unwrap();
+        let commented = String::from("# Frozen vectors\n\n") + &manifest.replace(' ', "  ");
+        golden.verify(&commented).unwrap();
+    }
+
+    #[test]
+    fn test_mismatches() {
+        let golden = golden();
+        let manifest = golden
+            .manifest()
+            .replace("name 02", "name 03")
+            .replace("unit", "zz 1");
+        assert_eq!(
+            Err(vec![
+                Mismatch::Malformed { line: 3 },
+                Mismatch::Changed {
+                    name: name("name"),
+                    frozen: vec![3, 0x6968],
+                    current: vec![2, 0x6968],
+                },
+                // The extra byte is a nul, which is valid utf-8.
+                Mismatch::RoundTrip { name: name("name") },
+                Mismatch::Missing { name: name("unit") },
+            ]),
+            golden.verify(&manifest)
+        );
+
+        // A vector the type no longer decodes, written before a field widened
+        let mut golden = Golden::with_config(Config::new());
+        golden.add("id", 7u64).unwrap().add("count", 0u32).unwrap();
+        assert_eq!(
+            Err(vec![
+                Mismatch::Changed {
+                    name: name("id"),
+                    frozen: vec![7],
+                    current: vec![7, 0],
+                },
+                Mismatch::Deserialize {
+                    name: name("id"),
+                    error: Error::DeserializeUnexpectedEnd {
+                        needed: 1,
+                        available: Some(0),
+                    },
+                },
+                Mismatch::Missing {
+                    name: name("count")
+                },
+                Mismatch::Unregistered { name: name("old") },
+            ]),
+            golden.verify("id 07000000\nold 00000000\n")
+        );
+    }
+}
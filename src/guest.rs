@@ -0,0 +1,243 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Guest I/O in the compact encoding
+//!
+//! These helpers mirror [risc0_zkvm::guest::env::read],
+//! [risc0_zkvm::guest::env::write] and [risc0_zkvm::guest::env::commit], but
+//! encode values with this crate instead of the standard zkVM serializer. The
+//! host has to use the compact encoding for the same streams.
+//! ```rust,no_run
+//! use l2r0_small_serde::guest;
+//!
+//! let (a, b): (u64, u64) = guest::read();
+//! guest::commit(&(a * b));
+//! ```
+
+use alloc::vec::Vec;
+
+use risc0_zkvm::{guest::env, serde::WordWrite};
+use serde::{de::DeserializeOwned, Serialize};
+
+use super::{
+    err::{Error, Result},
+    Config, Deserializer, Serializer,
+};
+
+/// Read private data from the host and deserialize it.
+///
+/// Panics if the data doesn't decode as a `T`.
+pub fn read<T: DeserializeOwned>() -> T {
+    let mut deserializer = Deserializer::new(env::stdin());
+    let value = T::deserialize(&mut deserializer).unwrap();
+    deserializer.finish().unwrap();
+    value
+}
+
+/// Serialize the given data and write it to the STDOUT of the zkVM.
+///
+/// This is available to the host as the private output on the prover. It is
+/// not included in the receipt.
+pub fn write<T: Serialize + ?Sized>(data: &T) {
+    let mut serializer = Serializer::new(env::stdout());
+    data.serialize(&mut serializer).unwrap();
+    serializer.finish().unwrap();
+}
+
+/// Serialize the given data and commit it to the journal.
+///
+/// Data in the journal is included in the receipt and is available to the
+/// verifier.
+pub fn commit<T: Serialize + ?Sized>(data: &T) {
+    let mut serializer = Serializer::new(env::journal());
+    data.serialize(&mut serializer).unwrap();
+    serializer.finish().unwrap();
+}
+
+/// Return a [CommitWriter] over the journal
+pub fn journal_writer() -> CommitWriter<impl WordWrite> {
+    CommitWriter::new(env::journal())
+}
+
+/// A WordWrite that commits whole values
+///
+/// Words written to a CommitWriter are held back until the frame they belong
+/// to is complete, so a value that fails to serialize never reaches the inner
+/// stream and values can't interleave. [CommitWriter::commit] writes a value
+/// as one frame. Words written through [WordWrite] form a frame that is ended
+/// by [CommitWriter::end_frame].
+pub struct CommitWriter<W: WordWrite> {
+    inner: W,
+    config: Config,
+    record_len: bool,
+    pending: Vec<u32>,
+    words_written: usize,
+}
+
+impl<W: WordWrite> CommitWriter<W> {
+    /// Construct a CommitWriter that writes to `inner`
+    pub fn new(inner: W) -> Self {
+        Self::with_config(inner, Config::default())
+    }
+
+    /// Construct a CommitWriter that serializes values using `config`
+    pub fn with_config(inner: W, config: Config) -> Self {
+        CommitWriter {
+            inner,
+            config,
+            record_len: false,
+            pending: Vec::new(),
+            words_written: 0,
+        }
+    }
+
+    /// Precede every frame with its length in words
+    ///
+    /// This lets a reader split the stream into frames without decoding them.
+    pub fn record_len(mut self, record_len: bool) -> Self {
+        self.record_len = record_len;
+        self
+    }
+
+    /// Serialize `value` and write it as one frame
+    ///
+    /// Fails with [Error::SerializeIncompleteFrame] if a frame written through
+    /// [WordWrite] hasn't been ended. If `value` fails to serialize, nothing is
+    /// written.
+    pub fn commit<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        if !self.pending.is_empty() {
+            return Err(Error::SerializeIncompleteFrame);
+        }
+        let mut serializer = Serializer::with_config(&mut self.pending, self.config);
+        let res = value
+            .serialize(&mut serializer)
+            .and_then(|_| serializer.finish());
+        if res.is_err() {
+            self.pending.clear();
+        }
+        res?;
+        self.end_frame()
+    }
+
+    /// Write the words written through [WordWrite] so far as one frame
+    pub fn end_frame(&mut self) -> Result<()> {
+        if self.record_len {
+            let len = self.pending.len();
+            let len = u32::try_from(len)
+                .map_err(|_| Error::SerializeLengthOverflow { requested: len })?;
+            self.inner.write_words(&[len])?;
+            self.words_written += 1;
+        }
+        self.inner.write_words(&self.pending)?;
+        self.words_written += self.pending.len();
+        self.pending.clear();
+        Ok(())
+    }
+
+    /// The number of words written to the inner stream, including lengths
+    pub fn words_written(&self) -> usize {
+        self.words_written
+    }
+
+    /// Return the inner stream
+    ///
+    /// Fails with [Error::SerializeIncompleteFrame] if a frame hasn't been
+    /// ended.
+    pub fn finish(self) -> Result<W> {
+        if !self.pending.is_empty() {
+            return Err(Error::SerializeIncompleteFrame);
+        }
+        Ok(self.inner)
+    }
+}
+
+impl<W: WordWrite> WordWrite for CommitWriter<W> {
+    fn write_words(&mut self, words: &[u32]) -> risc0_zkvm::serde::Result<()> {
+        self.pending.write_words(words)
+    }
+
+    fn write_padded_bytes(&mut self, bytes: &[u8]) -> risc0_zkvm::serde::Result<()> {
+        self.pending.write_padded_bytes(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::String, vec::Vec};
+
+    use serde::ser::Error as _;
+
+    use super::*;
+    use crate::{from_slice_compact, to_vec_compact};
+
+    struct Failing;
+
+    impl Serialize for Failing {
+        fn serialize<S: serde::Serializer>(
+            &self,
+            serializer: S,
+        ) -> core::result::Result<S::Ok, S::Error> {
+            use serde::ser::SerializeTuple;
+            let mut tuple = serializer.serialize_tuple(2)?;
+            tuple.serialize_element(&1u8)?;
+            Err(S::Error::custom("failed"))
+        }
+    }
+
+    #[test]
+    fn test_commit() {
+        let mut writer = CommitWriter::new(Vec::new());
+        writer.commit(&(1u8, 2u8)).unwrap();
+        assert!(writer.commit(&Failing).is_err());
+        writer.commit("abc").unwrap();
+        assert_eq!(3, writer.words_written());
+        let journal = writer.finish().unwrap();
+        assert_eq!(
+            [
+                to_vec_compact(&(1u8, 2u8)).unwrap(),
+                to_vec_compact("abc").unwrap()
+            ]
+            .concat(),
+            journal
+        );
+    }
+
+    #[test]
+    fn test_record_len() {
+        let mut writer = CommitWriter::new(Vec::new()).record_len(true);
+        writer.commit(&String::from("abcde")).unwrap();
+        writer.commit(&7u64).unwrap();
+        let journal = writer.finish().unwrap();
+        assert_eq!(3, journal[0]);
+        assert_eq!(
+            "abcde",
+            from_slice_compact::<String, _>(&journal[1..4]).unwrap()
+        );
+        assert_eq!(2, journal[4]);
+        assert_eq!(7, from_slice_compact::<u64, _>(&journal[5..]).unwrap());
+    }
+
+    #[test]
+    fn test_incomplete_frame() {
+        let mut writer = CommitWriter::new(Vec::new());
+        writer.write_words(&[1, 2]).unwrap();
+        assert_eq!(Err(Error::SerializeIncompleteFrame), writer.commit(&3u32));
+        writer.end_frame().unwrap();
+        writer.write_words(&[4]).unwrap();
+        assert_eq!(
+            Err(Error::SerializeIncompleteFrame),
+            writer.finish().map(|_| ())
+        );
+    }
+}
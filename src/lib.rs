@@ -29,16 +29,39 @@
 //! assert_eq!(input, output);
 //! ```
 //!
-//! On the guest side, the necessary (de)serialization functionality is
-//! included in [`env`] module functions such as [`env::read`] and
-//! [`env::commit`], so this crate rarely needs to be directly used in the
-//! guest.
-//!
-//! [`env`]: ../guest/env/index.html
-//! [`env::commit`]: ../guest/env/fn.commit.html
-//! [`env::read`]: ../guest/env/fn.read.html
+//! On the guest side, the `guest` feature provides counterparts to the zkVM
+//! `env` functions such as `env::read` and `env::commit` that use this
+//! encoding, so that the guest doesn't need to wire up a [Serializer] or
+//! [Deserializer] itself.
+
+#![no_std]
 
+#[cfg(feature = "alloc")]
 extern crate alloc;
+#[cfg(test)]
+extern crate std;
+// Lets the derive macros refer to this crate by name from within its own tests.
+extern crate self as l2r0_small_serde;
+
+/// Size of a zkVM machine word in bytes.
+pub const WORD_SIZE: usize = core::mem::size_of::<u32>();
+
+/// Largest number of elements allocated ahead of reading them, when their
+/// number comes from the input
+///
+/// A corrupt length then fails on the end of input rather than on allocation.
+#[cfg(feature = "alloc")]
+pub(crate) const MAX_PREALLOC: usize = 4096;
+
+/// The capacity to reserve for a sequence of `hint` elements, where the hint
+/// comes from the input
+#[cfg(feature = "alloc")]
+pub(crate) fn cautious_capacity(hint: Option<usize>) -> usize {
+    hint.unwrap_or(0).min(MAX_PREALLOC)
+}
+
+/// Largest number of nested composites a value may have
+pub(crate) const MAX_DEPTH: u16 = 256;
 
 /// Align the given address `addr` upwards to alignment `align`.
 ///
@@ -47,20 +70,164 @@ pub const fn align_up(addr: usize, align: usize) -> usize {
     (addr + align - 1) & !(align - 1)
 }
 
+pub mod adapters;
+#[cfg(feature = "bumpalo")]
+pub mod arena;
+#[cfg(feature = "alloc")]
+mod babybear;
+#[cfg(any(feature = "bincode", feature = "postcard"))]
+pub mod bridge;
+#[cfg(feature = "alloc")]
+mod buffer;
+#[cfg(feature = "alloc")]
+mod checksum;
+#[cfg(feature = "alloc")]
+mod chunked;
+#[cfg(feature = "compress")]
+mod compress;
+mod config;
+#[cfg(feature = "alloc")]
+mod costs;
+#[cfg(feature = "alloc")]
+pub mod delta;
+#[cfg(feature = "alloc")]
 mod deserializer;
+#[cfg(feature = "alloc")]
+pub mod direct;
+#[cfg(feature = "encrypt")]
+mod encrypt;
+#[cfg(feature = "erased-serde")]
+mod erased;
 mod err;
+#[cfg(feature = "alloc")]
+mod extract;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(any(feature = "heapless", feature = "arrayvec"))]
+mod fixed;
+#[cfg(feature = "alloc")]
+mod framed;
+#[cfg(feature = "fuzz")]
+pub mod fuzz;
+#[cfg(feature = "alloc")]
+pub mod golden;
+#[cfg(feature = "guest")]
+pub mod guest;
+#[cfg(feature = "alloc")]
+mod manifest;
+mod marker;
+#[cfg(feature = "alloc")]
+pub mod merkle;
+#[cfg(feature = "rayon")]
+mod parallel;
+#[cfg(feature = "alloc")]
+mod pool;
+#[cfg(feature = "alloc")]
+mod reader;
+mod routing;
 mod serializer;
+#[cfg(feature = "alloc")]
+mod session;
+pub mod sized;
+#[cfg(feature = "alloc")]
+pub mod sorted;
+mod stats;
+#[cfg(feature = "test-utils")]
+pub mod test_utils;
+#[cfg(feature = "tracing")]
+mod trace;
+#[cfg(feature = "alloc")]
+mod transport;
+#[cfg(feature = "alloc")]
+pub mod versioned;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(feature = "alloc")]
+pub mod word_value;
+#[cfg(feature = "alloc")]
+pub mod words;
+
+#[cfg(feature = "alloc")]
+pub use babybear::{
+    decode_babybear, encode_babybear, from_slice_babybear, to_vec_babybear, BabyBearWriter,
+    BABYBEAR_PRIME,
+};
+#[cfg(feature = "alloc")]
+pub use checksum::{from_slice_checksummed, to_vec_checksummed};
+#[cfg(feature = "alloc")]
+pub use chunked::{from_slice_chunked, to_vec_chunked, ChunkedReader, ChunkedWriter};
+#[cfg(feature = "compress")]
+pub use compress::{from_slice_compressed, to_vec_compressed, CompressedReader, CompressedWriter};
+pub use config::{Config, VariantTag};
+#[cfg(feature = "alloc")]
+pub use costs::{FieldCost, FieldCosts};
+#[cfg(feature = "alloc")]
+pub use deserializer::{
+    from_slice_compact, from_slice_compact_in_place, from_slice_compact_in_place_with_config,
+    from_slice_compact_with_config, from_words, from_words_with_config, validate, Deserializer,
+    IterReader,
+};
+#[cfg(feature = "unchecked")]
+pub use deserializer::{from_slice_unchecked, from_slice_unchecked_with_config};
+#[cfg(feature = "alloc")]
+pub use direct::{WordDeserialize, WordSerialize};
+#[cfg(feature = "encrypt")]
+pub use encrypt::{from_slice_encrypted, to_vec_encrypted, EncryptedReader, EncryptedWriter};
+#[cfg(feature = "erased-serde")]
+pub use erased::{to_vec_erased, to_vec_erased_with_config};
+pub use err::{Error, Message, Result};
+#[cfg(feature = "alloc")]
+pub use extract::extract_field;
+#[cfg(feature = "arrayvec")]
+pub use fixed::{to_array_vec, ArrayVecWriter};
+#[cfg(feature = "heapless")]
+pub use fixed::{to_heapless_vec, HeaplessWriter};
+#[cfg(feature = "alloc")]
+pub use framed::{read_frame, read_frame_with_config, to_vec_framed, to_vec_framed_with_config};
+#[cfg(feature = "alloc")]
+pub use manifest::{FieldRegion, Manifest};
+#[cfg(feature = "rayon")]
+pub use parallel::{to_vec_compact_par, to_vec_compact_par_with_config};
+#[cfg(feature = "alloc")]
+pub use pool::BufferPool;
+#[cfg(feature = "alloc")]
+pub use reader::{for_each_entry, for_each_entry_with_config, MapReader, SeqReader};
+pub use routing::{Route, RoutingWriter};
+pub use serializer::{
+    to_slice_compact, to_slice_compact_with_config, Serializer, SliceWriter, TeeWriter,
+};
+#[cfg(feature = "alloc")]
+pub use serializer::{
+    to_vec_compact, to_vec_compact_with_capacity, to_vec_compact_with_config,
+    to_vec_compact_with_manifest, to_vec_from_iter, to_vec_into, to_vec_into_with_config,
+};
+#[cfg(feature = "alloc")]
+pub use session::{Progress, Session};
+pub use sized::WordSized;
+pub use stats::{encoding_stats, EncodingStats};
+#[cfg(feature = "alloc")]
+pub use transport::{decode_base64, decode_hex, encode_base64, encode_hex};
+#[cfg(feature = "alloc")]
+pub use versioned::{from_slice_versioned, to_vec_versioned};
 
-pub use deserializer::{from_slice_compact, Deserializer};
-pub use err::{Error, Result};
-pub use serializer::{to_vec_compact, to_vec_compact_with_capacity, Serializer};
+#[cfg(feature = "derive")]
+pub use l2r0_small_serde_derive::{WordDeserialize, WordSerde, WordSerialize, WordSized};
+
+#[doc(hidden)]
+pub mod __private {
+    pub use risc0_zkvm::serde::{WordRead, WordWrite};
+    pub use serde;
+}
 
 #[cfg(test)]
 mod tests {
     use alloc::{collections::BTreeMap, string::String, vec, vec::Vec};
     use serde::{Deserialize, Serialize};
 
-    use crate::{from_slice_compact, to_vec_compact};
+    use crate::{
+        from_slice_compact, from_slice_compact_in_place, from_slice_compact_with_config,
+        to_vec_compact, to_vec_compact_with_config, validate, Config, VariantTag,
+    };
 
     #[test]
     fn test_vec_round_trip() {
@@ -98,6 +265,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::field_reassign_with_default)]
     fn test_mixed_struct() {
         #[derive(Debug, Serialize, PartialEq, Eq, Deserialize)]
         struct WrappedU8(pub u8);
@@ -154,6 +322,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::field_reassign_with_default)]
     fn test_edge_cases() {
         #[derive(PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
         struct U8SeqThenU32(Vec<u8>, u32);
@@ -176,4 +345,817 @@ mod tests {
             assert_eq!(input, output);
         }
     }
+
+    #[test]
+    fn test_byte_variant_tags() {
+        #[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+        enum Op {
+            Nop,
+            Push(u8),
+            Jump { offset: u32 },
+            Pair(u8, u8),
+        }
+
+        let config = Config::new().variant_tag(VariantTag::Byte);
+        let input = vec![
+            Op::Nop,
+            Op::Push(9),
+            Op::Nop,
+            Op::Pair(1, 2),
+            Op::Jump { offset: 5 },
+        ];
+        let data = to_vec_compact_with_config(&input, config).unwrap();
+        assert_eq!([5, 0x00090100, 0x02020103, 5].as_slice(), data);
+        let output: Vec<Op> = from_slice_compact_with_config(&data, config).unwrap();
+        assert_eq!(input, output);
+
+        let data = to_vec_compact_with_config(&Op::Nop, config).unwrap();
+        assert_eq!([0].as_slice(), data);
+        let output: Op = from_slice_compact_with_config(&data, config).unwrap();
+        assert_eq!(Op::Nop, output);
+
+        // A top-level tag packs with the byte it holds.
+        let data = to_vec_compact_with_config(&Op::Push(9), config).unwrap();
+        assert_eq!([0x0901].as_slice(), data);
+        let output: Op = from_slice_compact_with_config(&data, config).unwrap();
+        assert_eq!(Op::Push(9), output);
+
+        assert_eq!(9, to_vec_compact(&input).unwrap().len());
+    }
+
+    #[test]
+    fn test_pack_top_level() {
+        let config = Config::new().pack_top_level(true);
+        let mut data = Vec::new();
+        let mut serializer = crate::Serializer::with_config(&mut data, config);
+        1u8.serialize(&mut serializer).unwrap();
+        true.serialize(&mut serializer).unwrap();
+        [2u8, 3].serialize(&mut serializer).unwrap();
+        7u32.serialize(&mut serializer).unwrap();
+        4u8.serialize(&mut serializer).unwrap();
+        serializer.finish().unwrap();
+        assert_eq!([0x03020101, 7, 4].as_slice(), data);
+
+        let mut deserializer = crate::Deserializer::from_words_with_config(&data, config);
+        assert_eq!(1u8, u8::deserialize(&mut deserializer).unwrap());
+        assert!(bool::deserialize(&mut deserializer).unwrap());
+        assert_eq!([2u8, 3], <[u8; 2]>::deserialize(&mut deserializer).unwrap());
+        assert_eq!(7u32, u32::deserialize(&mut deserializer).unwrap());
+        assert_eq!(4u8, u8::deserialize(&mut deserializer).unwrap());
+        deserializer.finish().unwrap();
+
+        // A single value encodes as it does by default.
+        let input = (5u8, vec![true, false], 6u8);
+        let data = to_vec_compact_with_config(&input, config).unwrap();
+        assert_eq!(to_vec_compact(&input).unwrap(), data);
+        let output: (u8, Vec<bool>, u8) = from_slice_compact_with_config(&data, config).unwrap();
+        assert_eq!(input, output);
+    }
+
+    #[test]
+    fn test_top_level_scalars() {
+        use crate::{Error, WordDeserialize};
+
+        fn write(config: Config) -> Vec<u32> {
+            let mut data = Vec::new();
+            let mut serializer = crate::Serializer::with_config(&mut data, config);
+            1u8.serialize(&mut serializer).unwrap();
+            true.serialize(&mut serializer).unwrap();
+            (2u8, 3u8).serialize(&mut serializer).unwrap();
+            4u8.serialize(&mut serializer).unwrap();
+            5u16.serialize(&mut serializer).unwrap();
+            Some(6u8).serialize(&mut serializer).unwrap();
+            7u8.serialize(&mut serializer).unwrap();
+            serializer.finish().unwrap();
+            data
+        }
+
+        let packed = Config::new().pack_top_level(true);
+        for (config, expected) in [
+            (Config::new(), [1, 1, 0x0302, 4, 5, 1, 6, 7].as_slice()),
+            (packed, [0x03020101, 4, 5, 1, 0x0706].as_slice()),
+        ] {
+            let data = write(config);
+            assert_eq!(expected, data);
+
+            let mut deserializer = crate::Deserializer::from_words_with_config(&data, config);
+            assert_eq!(1u8, u8::deserialize(&mut deserializer).unwrap());
+            assert!(bool::deserialize(&mut deserializer).unwrap());
+            assert_eq!((2, 3), <(u8, u8)>::deserialize(&mut deserializer).unwrap());
+            assert_eq!(4u8, u8::deserialize(&mut deserializer).unwrap());
+            assert_eq!(5u16, u16::deserialize(&mut deserializer).unwrap());
+            assert_eq!(
+                Some(6),
+                Option::<u8>::deserialize(&mut deserializer).unwrap()
+            );
+            assert_eq!(7u8, u8::deserialize(&mut deserializer).unwrap());
+            deserializer.finish().unwrap();
+
+            // Decoding without serde reads the same words.
+            let mut deserializer = crate::Deserializer::from_words_with_config(&data, config);
+            assert_eq!(1u8, u8::word_deserialize(&mut deserializer).unwrap());
+            assert!(bool::word_deserialize(&mut deserializer).unwrap());
+            let pair = <[u8; 2]>::word_deserialize(&mut deserializer).unwrap();
+            assert_eq!([2, 3], pair);
+            assert_eq!(4u8, u8::word_deserialize(&mut deserializer).unwrap());
+            assert_eq!(5u16, u16::word_deserialize(&mut deserializer).unwrap());
+            let option = Option::<u8>::word_deserialize(&mut deserializer).unwrap();
+            assert_eq!(Some(6), option);
+            assert_eq!(7u8, u8::word_deserialize(&mut deserializer).unwrap());
+            deserializer.finish().unwrap();
+        }
+
+        // A top-level byte owns the rest of its word.
+        let mut deserializer = crate::Deserializer::from_words(&[0x0201]);
+        assert_eq!(
+            Err(Error::DeserializeBadByte),
+            u8::deserialize(&mut deserializer)
+        );
+        let mut deserializer = crate::Deserializer::from_words_with_config(&[0x0201], packed);
+        assert_eq!(1u8, u8::deserialize(&mut deserializer).unwrap());
+        assert_eq!(2u8, u8::deserialize(&mut deserializer).unwrap());
+    }
+
+    #[test]
+    fn test_human_readable() {
+        use std::net::Ipv4Addr;
+
+        let addr = Ipv4Addr::new(192, 168, 0, 1);
+        assert_eq!([0x0100a8c0].as_slice(), to_vec_compact(&addr).unwrap());
+        let config = Config::new().human_readable(true);
+        let data = to_vec_compact_with_config(&addr, config).unwrap();
+        assert_eq!(to_vec_compact(&"192.168.0.1").unwrap(), data);
+        let output: Ipv4Addr = from_slice_compact_with_config(&data, config).unwrap();
+        assert_eq!(addr, output);
+
+        /// A word in binary formats and its hex digits with `collect_str`
+        /// otherwise
+        struct Id(u32);
+
+        impl Serialize for Id {
+            fn serialize<S: serde::Serializer>(
+                &self,
+                serializer: S,
+            ) -> core::result::Result<S::Ok, S::Error> {
+                match serializer.is_human_readable() {
+                    true => serializer.collect_str(&format_args!("{:x}", self.0)),
+                    false => serializer.serialize_u32(self.0),
+                }
+            }
+        }
+
+        // Sequences written in bulk see the flag too.
+        let ids = vec![Id(0xab), Id(0xcd)];
+        assert_eq!([2, 0xab, 0xcd].as_slice(), to_vec_compact(&ids).unwrap());
+        let data = to_vec_compact_with_config(&ids, config).unwrap();
+        assert_eq!(to_vec_compact(&vec!["ab", "cd"]).unwrap(), data);
+    }
+
+    #[test]
+    fn test_big_endian() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Block {
+            id: u32,
+            flags: (u8, bool, u8),
+            height: u64,
+            hash: u128,
+            name: String,
+            parts: Vec<u8>,
+        }
+
+        let input = Block {
+            id: 0x01020304,
+            flags: (0xaa, true, 0xbb),
+            height: 0x0506070809,
+            hash: 0x0a0b0c0d << 96 | 0x0e,
+            name: String::from("hello"),
+            parts: (6..=11).collect(),
+        };
+        let config = Config::new().big_endian(true);
+        let data = to_vec_compact_with_config(&input, config).unwrap();
+        assert_eq!(
+            [
+                0x01020304,
+                0xaa01bb00,
+                0x05,
+                0x06070809,
+                0x0a0b0c0d,
+                0,
+                0,
+                0x0e,
+                5,
+                u32::from_be_bytes(*b"hell"),
+                u32::from_be_bytes(*b"o\0\0\0"),
+                6,
+                0x06070809,
+                0x0a0b0000,
+            ]
+            .as_slice(),
+            data
+        );
+        let output: Block = from_slice_compact_with_config(&data, config).unwrap();
+        assert_eq!(input, output);
+
+        // Each word is the byte swap of its little-endian counterpart, or the
+        // same value for words written as such.
+        assert_eq!(
+            [0x07000000].as_slice(),
+            to_vec_compact_with_config(&7u8, config).unwrap()
+        );
+        let words = vec![1u64 << 32 | 2, 3];
+        let data = to_vec_compact_with_config(&words, config).unwrap();
+        assert_eq!([2, 1, 2, 0, 3].as_slice(), data);
+        let output: Vec<u64> = from_slice_compact_with_config(&data, config).unwrap();
+        assert_eq!(words, output);
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Words(#[serde(with = "crate::words")] Vec<u64>);
+        let output: Words = from_slice_compact_with_config(&data, config).unwrap();
+        assert_eq!(words, output.0);
+    }
+
+    #[test]
+    fn test_byte_option_tags() {
+        #[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+        enum Flag {
+            Off,
+            On(Option<u8>),
+        }
+
+        let config = Config::new()
+            .byte_option_tags(true)
+            .variant_tag(VariantTag::Byte);
+        let input: Vec<(Option<Option<u8>>, Flag)> = vec![
+            (None, Flag::Off),
+            (Some(None), Flag::On(Some(7))),
+            (Some(Some(5)), Flag::On(None)),
+        ];
+        let data = to_vec_compact_with_config(&input, config).unwrap();
+        assert_eq!([3, 0x00010000, 0x01070101, 0x00010501].as_slice(), data);
+        let output: Vec<(Option<Option<u8>>, Flag)> =
+            from_slice_compact_with_config(&data, config).unwrap();
+        assert_eq!(input, output);
+        assert_eq!(13, to_vec_compact(&input).unwrap().len());
+
+        let data = to_vec_compact_with_config(&Some(Some(5u8)), config).unwrap();
+        assert_eq!([0x050101].as_slice(), data);
+        let output: Option<Option<u8>> = from_slice_compact_with_config(&data, config).unwrap();
+        assert_eq!(Some(Some(5)), output);
+        let data = to_vec_compact_with_config(&Some(7u32), config).unwrap();
+        assert_eq!([1, 7].as_slice(), data);
+
+        assert_eq!(
+            Err(crate::Error::DeserializeBadOption),
+            from_slice_compact_with_config::<Option<u8>, _>(&[2], config)
+        );
+    }
+
+    #[test]
+    fn test_name_hash_variant_tags() {
+        mod v1 {
+            use serde::{Deserialize, Serialize};
+
+            #[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+            pub enum Status {
+                Pending,
+                Done(u32),
+                Failed { code: u32 },
+            }
+        }
+
+        mod v2 {
+            use serde::{Deserialize, Serialize};
+
+            #[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+            pub enum Status {
+                Failed { code: u32 },
+                Cancelled,
+                Done(u32),
+                Pending,
+            }
+        }
+
+        let config = Config::new().variant_tag(VariantTag::NameHash);
+        let input = vec![
+            v1::Status::Done(3),
+            v1::Status::Failed { code: 4 },
+            v1::Status::Pending,
+        ];
+        let data = to_vec_compact_with_config(&input, config).unwrap();
+        assert_eq!(to_vec_compact(&input).unwrap().len(), data.len());
+
+        let output: Vec<v1::Status> = from_slice_compact_with_config(&data, config).unwrap();
+        assert_eq!(input, output);
+
+        let output: Vec<v2::Status> = from_slice_compact_with_config(&data, config).unwrap();
+        assert_eq!(
+            vec![
+                v2::Status::Done(3),
+                v2::Status::Failed { code: 4 },
+                v2::Status::Pending
+            ],
+            output
+        );
+
+        let data = to_vec_compact_with_config(&v2::Status::Cancelled, config).unwrap();
+        assert_eq!(
+            Err(crate::Error::DeserializeBadVariant),
+            from_slice_compact_with_config::<v1::Status, _>(&data, config)
+        );
+    }
+
+    #[test]
+    fn test_field_count_defaults() {
+        #[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+        struct HeaderV1 {
+            pub version: u8,
+            pub len: u32,
+        }
+
+        #[derive(Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+        #[serde(default)]
+        struct HeaderV2 {
+            pub version: u8,
+            pub len: u32,
+            pub tags: Vec<u8>,
+            pub parent: Option<u64>,
+        }
+
+        #[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+        enum Message {
+            Header(HeaderV1),
+            Body { data: Vec<u8> },
+        }
+
+        let config = Config::new().field_count(true);
+        let input = HeaderV1 { version: 1, len: 9 };
+        let data = to_vec_compact_with_config(&input, config).unwrap();
+        assert_eq!([2, 1, 9].as_slice(), data);
+
+        let output: HeaderV2 = from_slice_compact_with_config(&data, config).unwrap();
+        assert_eq!(
+            HeaderV2 {
+                version: 1,
+                len: 9,
+                ..Default::default()
+            },
+            output
+        );
+
+        let data = to_vec_compact_with_config(&output, config).unwrap();
+        assert_eq!(
+            Err(crate::Error::DeserializeTooManyFields),
+            from_slice_compact_with_config::<HeaderV1, _>(&data, config)
+        );
+
+        let input = vec![
+            Message::Header(HeaderV1 { version: 2, len: 3 }),
+            Message::Body { data: vec![7] },
+        ];
+        let data = to_vec_compact_with_config(&input, config).unwrap();
+        let output: Vec<Message> = from_slice_compact_with_config(&data, config).unwrap();
+        assert_eq!(input, output);
+    }
+
+    #[test]
+    fn test_field_sizes_skip_unknown() {
+        #[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+        struct EntryV1 {
+            pub id: u8,
+            pub name: String,
+        }
+
+        #[derive(Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+        #[serde(default)]
+        struct Inner {
+            pub flags: Vec<u8>,
+        }
+
+        #[derive(Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+        #[serde(default)]
+        struct EntryV2 {
+            pub id: u8,
+            pub name: String,
+            pub inner: Inner,
+            pub score: Option<u64>,
+        }
+
+        let config = Config::new().field_sizes(true);
+        let input = vec![
+            EntryV2 {
+                id: 1,
+                name: "a".into(),
+                inner: Inner { flags: vec![1, 2] },
+                score: Some(3),
+            },
+            EntryV2 {
+                id: 2,
+                name: "bc".into(),
+                ..Default::default()
+            },
+        ];
+        let data = to_vec_compact_with_config(&input, config).unwrap();
+        assert_eq!(
+            [
+                2, 4, 1, 1, 2, 1, 0x61, 4, 1, 2, 2, 0x0201, 3, 1, 3, 0, 4, 1, 2, 2, 2, 0x6362, 3,
+                1, 1, 0, 1, 0
+            ]
+            .as_slice(),
+            data
+        );
+
+        let output: Vec<EntryV2> = from_slice_compact_with_config(&data, config).unwrap();
+        assert_eq!(input, output);
+
+        let output: Vec<EntryV1> = from_slice_compact_with_config(&data, config).unwrap();
+        assert_eq!(
+            vec![
+                EntryV1 {
+                    id: 1,
+                    name: "a".into()
+                },
+                EntryV1 {
+                    id: 2,
+                    name: "bc".into()
+                }
+            ],
+            output
+        );
+
+        let mut corrupted = data.clone();
+        corrupted[2] = 2;
+        assert_eq!(
+            Err(crate::Error::DeserializeBadFieldSize),
+            from_slice_compact_with_config::<Vec<EntryV1>, _>(&corrupted, config)
+        );
+    }
+
+    #[test]
+    fn test_intern_strings() {
+        #[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+        struct Transfer {
+            pub token: String,
+            pub amount: u64,
+        }
+
+        let config = Config::new().intern_strings(true);
+        let input = vec![
+            Transfer {
+                token: "USDC".into(),
+                amount: 1,
+            },
+            Transfer {
+                token: "WETH".into(),
+                amount: 2,
+            },
+            Transfer {
+                token: "USDC".into(),
+                amount: 3,
+            },
+        ];
+        let data = to_vec_compact_with_config(&input, config).unwrap();
+        assert_eq!(
+            [3, 0, 4, 0x43445355, 1, 0, 0, 4, 0x48544557, 2, 0, 1, 3, 0].as_slice(),
+            data
+        );
+        let output: Vec<Transfer> = from_slice_compact_with_config(&data, config).unwrap();
+        assert_eq!(input, output);
+
+        let input: BTreeMap<String, Vec<String>> = BTreeMap::from([
+            ("a".into(), vec!["b".into(), "a".into()]),
+            ("b".into(), vec!["a".into()]),
+        ]);
+        let data = to_vec_compact_with_config(&input, config).unwrap();
+        let output: BTreeMap<String, Vec<String>> =
+            from_slice_compact_with_config(&data, config).unwrap();
+        assert_eq!(input, output);
+
+        assert_eq!(
+            Err(crate::Error::DeserializeBadStringRef),
+            from_slice_compact_with_config::<String, _>(&[1], config)
+        );
+    }
+
+    #[test]
+    fn test_pad_to() {
+        let config = Config::new().pad_to(4);
+        let input = (1u8, 2u32, [3u8, 4u8]);
+        let data = to_vec_compact_with_config(&input, config).unwrap();
+        assert_eq!([1, 2, 0x0403, 0].as_slice(), data);
+        let output: (u8, u32, [u8; 2]) = from_slice_compact_with_config(&data, config).unwrap();
+        assert_eq!(input, output);
+
+        let data = to_vec_compact_with_config(&[1u32; 4], config).unwrap();
+        assert_eq!(4, data.len());
+
+        assert_eq!(
+            Err(crate::Error::DeserializeBadPadding),
+            from_slice_compact_with_config::<u32, _>(&[1, 0, 1, 0], config)
+        );
+        assert_eq!(
+            Err(crate::Error::DeserializeUnexpectedEnd {
+                needed: 3,
+                available: Some(1)
+            }),
+            from_slice_compact_with_config::<u32, _>(&[1, 0], config)
+        );
+    }
+
+    #[test]
+    fn test_self_describing() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        enum Kind {
+            Plain,
+            Scaled(u16, f32),
+            Named { label: String },
+        }
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        #[serde(untagged)]
+        enum Amount {
+            Small(u8),
+            Large(u64),
+            Text(String),
+        }
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Entry {
+            flag: bool,
+            kinds: Vec<Kind>,
+            amounts: [Amount; 3],
+            extra: Option<(i8, char)>,
+            table: BTreeMap<u32, Vec<u8>>,
+        }
+
+        let config = Config::new().self_describing(true);
+        let input = Entry {
+            flag: true,
+            kinds: vec![
+                Kind::Plain,
+                Kind::Scaled(3, 0.5),
+                Kind::Named { label: "n".into() },
+            ],
+            amounts: [
+                Amount::Small(1),
+                Amount::Large(1 << 40),
+                Amount::Text("ten".into()),
+            ],
+            extra: Some((-1, 'x')),
+            table: BTreeMap::from([(1, vec![1, 2]), (2, vec![])]),
+        };
+        let data = to_vec_compact_with_config(&input, config).unwrap();
+        let output: Entry = from_slice_compact_with_config(&data, config).unwrap();
+        assert_eq!(input, output);
+
+        for config in [
+            config,
+            config.variant_tag(VariantTag::Byte).field_sizes(true),
+        ] {
+            let data = to_vec_compact_with_config(&input.kinds, config).unwrap();
+            let output: Vec<Kind> = from_slice_compact_with_config(&data, config).unwrap();
+            assert_eq!(input.kinds, output);
+        }
+
+        let data = to_vec_compact_with_config(&(7u32, input, 8u8), config).unwrap();
+        let output: (u32, serde::de::IgnoredAny, u8) =
+            from_slice_compact_with_config(&data, config).unwrap();
+        assert_eq!((7, 8), (output.0, output.2));
+
+        let data = to_vec_compact_with_config(&7u32, config).unwrap();
+        assert_eq!([6, 7].as_slice(), data);
+        assert_eq!(
+            Err(crate::Error::DeserializeBadMarker),
+            from_slice_compact_with_config::<i32, _>(&data, config)
+        );
+        assert_eq!(
+            Err(crate::Error::NotSupported),
+            from_slice_compact::<Amount, _>(&to_vec_compact(&Amount::Small(1)).unwrap())
+        );
+    }
+
+    #[test]
+    fn test_in_place() {
+        let input = (vec![1u32, 2, 3], String::from("hello"), vec![vec![4u8; 3]]);
+        let data = to_vec_compact(&input).unwrap();
+
+        let mut place = (
+            Vec::with_capacity(16),
+            String::with_capacity(16),
+            vec![Vec::with_capacity(16)],
+        );
+        place.0.push(9);
+        let buffers = (place.0.as_ptr(), place.1.as_ptr(), place.2[0].as_ptr());
+        from_slice_compact_in_place(&data, &mut place).unwrap();
+        assert_eq!(input, place);
+        assert_eq!(
+            buffers,
+            (place.0.as_ptr(), place.1.as_ptr(), place.2[0].as_ptr())
+        );
+
+        let input = (
+            vec![5u32],
+            String::from("a much longer string than before"),
+            vec![],
+        );
+        let data = to_vec_compact(&input).unwrap();
+        from_slice_compact_in_place(&data, &mut place).unwrap();
+        assert_eq!(input, place);
+    }
+
+    #[test]
+    fn test_validate() {
+        let input = (String::from("proof"), vec![1u8, 2, 3], Some(4u64));
+        let data = to_vec_compact(&input).unwrap();
+        assert_eq!(
+            Ok(()),
+            validate::<(String, Vec<u8>, Option<u64>), _>(&data, Config::new())
+        );
+
+        let bytes: Vec<u8> = data.iter().flat_map(|w| w.to_le_bytes()).collect();
+        assert_eq!(
+            Ok(()),
+            validate::<(String, Vec<u8>, Option<u64>), _>(&bytes, Config::new())
+        );
+
+        assert_eq!(
+            Err(crate::Error::DeserializeUnexpectedEnd {
+                needed: 1,
+                available: Some(0)
+            }),
+            validate::<(String, Vec<u8>, Option<u64>), _>(&data[..data.len() - 1], Config::new())
+        );
+        assert_eq!(
+            Err(crate::Error::DeserializeTrailingWords { count: 3 }),
+            validate::<(String, Vec<u8>), _>(&data, Config::new())
+        );
+        let mut corrupted = data.clone();
+        corrupted[data.len() - 3] = 2;
+        assert_eq!(
+            Err(crate::Error::DeserializeBadOption),
+            validate::<(String, Vec<u8>, Option<u64>), _>(&corrupted, Config::new())
+        );
+    }
+
+    #[test]
+    fn test_error_context() {
+        let mut data = to_vec_compact("ab\u{e9}").unwrap();
+        // Replace the continuation byte of the last char.
+        data[1] = data[1] & 0x00ff_ffff | 0x41 << 24;
+        assert_eq!(
+            Err(crate::Error::DeserializeBadUtf8 { offset: 2 }),
+            from_slice_compact::<String, _>(&data)
+        );
+
+        let data = [u32::MAX, 0];
+        let err = from_slice_compact::<String, _>(&data).unwrap_err();
+        assert_eq!(
+            crate::Error::DeserializeLengthOverflow {
+                requested: u32::MAX as usize
+            },
+            err
+        );
+        assert_eq!(
+            "Found a length of 4294967295 that exceeds the input",
+            std::format!("{err}")
+        );
+
+        let err = from_slice_compact::<(u32, u64), _>(&[1, 2]).unwrap_err();
+        assert_eq!(
+            "Unexpected end during deserialization: needed 1 words, 0 available",
+            std::format!("{err}")
+        );
+    }
+
+    #[cfg(feature = "derive")]
+    #[test]
+    fn test_option_bitmap() {
+        use crate::WordSerde;
+
+        #[derive(Debug, PartialEq, Eq, WordSerde)]
+        #[word_serde(option_bitmap)]
+        struct Sparse {
+            pub id: u32,
+            pub a: Option<u32>,
+            pub b: Option<u64>,
+            pub c: Option<Vec<u8>>,
+            pub d: Option<u32>,
+        }
+
+        let input = Sparse {
+            id: 7,
+            a: None,
+            b: Some(9),
+            c: Some(vec![1, 2]),
+            d: None,
+        };
+        let data = to_vec_compact(&input).unwrap();
+        assert_eq!([0b0110, 7, 9, 0, 2, 513].as_slice(), data);
+        let output: Sparse = from_slice_compact(&data).unwrap();
+        assert_eq!(input, output);
+
+        let empty = Sparse {
+            id: 1,
+            a: None,
+            b: None,
+            c: None,
+            d: None,
+        };
+        let data = to_vec_compact(&empty).unwrap();
+        assert_eq!([0, 1].as_slice(), data);
+        let output: Sparse = from_slice_compact(&data).unwrap();
+        assert_eq!(empty, output);
+    }
+
+    #[cfg(feature = "derive")]
+    #[test]
+    fn test_pack_fields() {
+        use crate::WordSerde;
+
+        #[derive(Debug, PartialEq, Eq, WordSerde)]
+        #[word_serde(pack_fields)]
+        struct Header {
+            pub ready: bool,
+            pub height: u32,
+            pub kind: u8,
+            pub root: [u32; 2],
+            pub done: bool,
+        }
+
+        #[derive(Debug, PartialEq, Eq, WordSerde)]
+        #[word_serde(option_bitmap, pack_fields)]
+        struct Sparse(Option<u32>, Option<u8>, u64, Option<bool>);
+
+        let input = Header {
+            ready: true,
+            height: 7,
+            kind: 3,
+            root: [8, 9],
+            done: true,
+        };
+        let data = to_vec_compact(&input).unwrap();
+        assert_eq!([0x01_03_01, 7, 8, 9].as_slice(), data);
+        let output: Header = from_slice_compact(&data).unwrap();
+        assert_eq!(input, output);
+
+        let input = Sparse(Some(1), Some(2), 3, Some(false));
+        let data = to_vec_compact(&input).unwrap();
+        // The bitmap follows the encoded order too.
+        assert_eq!([0b111, 0x00_02, 1, 3, 0].as_slice(), data);
+        let output: Sparse = from_slice_compact(&data).unwrap();
+        assert_eq!(input, output);
+    }
+
+    #[cfg(feature = "derive")]
+    #[test]
+    #[allow(clippy::field_reassign_with_default)]
+    fn test_option_bitmap_many_fields() {
+        use crate::WordSerde;
+
+        #[derive(Debug, Default, PartialEq, Eq, WordSerde)]
+        #[word_serde(option_bitmap)]
+        struct Wide<T>(
+            Option<T>,
+            [Option<u8>; 2],
+            Option<u8>,
+            Option<u8>,
+            Option<u8>,
+            Option<u8>,
+            Option<u8>,
+            Option<u8>,
+            Option<u8>,
+            Option<u8>,
+            Option<u8>,
+            Option<u8>,
+            Option<u8>,
+            Option<u8>,
+            Option<u8>,
+            Option<u8>,
+            Option<u8>,
+            Option<u8>,
+            Option<u8>,
+            Option<u8>,
+            Option<u8>,
+            Option<u8>,
+            Option<u8>,
+            Option<u8>,
+            Option<u8>,
+            Option<u8>,
+            Option<u8>,
+            Option<u8>,
+            Option<u8>,
+            Option<u8>,
+            Option<u8>,
+            Option<u8>,
+            Option<u8>,
+            Option<u8>,
+        );
+
+        let mut input = Wide::<String>::default();
+        input.0 = Some("abc".into());
+        input.1 = [Some(1), None];
+        input.33 = Some(5);
+        let data = to_vec_compact(&input).unwrap();
+        assert_eq!([1, 1, 3, 0x00636261, 1, 1, 0, 5].as_slice(), data);
+        let output: Wide<String> = from_slice_compact(&data).unwrap();
+        assert_eq!(input, output);
+    }
 }
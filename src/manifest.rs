@@ -0,0 +1,166 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::vec::Vec;
+use core::ops::Range;
+
+/// Where a top-level struct field lies in the output
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct FieldRegion {
+    /// Name of the field
+    pub name: &'static str,
+    /// Index of the first word holding the field
+    pub offset: usize,
+    /// Number of words from `offset` holding the field
+    pub len: usize,
+}
+
+impl FieldRegion {
+    /// The indices of the words holding the field
+    pub fn range(&self) -> Range<usize> {
+        self.offset..self.offset + self.len
+    }
+}
+
+/// The regions of the output holding each top-level struct field
+///
+/// A field is top-level if it belongs to the outermost value, which is a
+/// struct or struct variant, possibly wrapped in newtype structs. Offsets
+/// count the words written by the [Serializer](crate::Serializer) since it was
+/// constructed.
+///
+/// A region includes the size word of the field with
+/// [Config::field_sizes](crate::Config::field_sizes). Without it, a field that
+/// starts with a byte can pack into the word holding the last byte of the
+/// previous field, so the regions of neighbouring fields may share a word.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Manifest {
+    fields: Vec<FieldRegion>,
+}
+
+impl Manifest {
+    /// The region of the first field called `name`, if it was serialized
+    pub fn get(&self, name: &str) -> Option<FieldRegion> {
+        self.fields.iter().find(|field| field.name == name).copied()
+    }
+
+    /// The words of the first field called `name` within `words`
+    ///
+    /// Returns `None` if the field wasn't serialized or `words` is too short.
+    pub fn words_of<'a>(&self, name: &str, words: &'a [u32]) -> Option<&'a [u32]> {
+        words.get(self.get(name)?.range())
+    }
+
+    /// Iterate over the fields in the order they were serialized
+    pub fn iter(&self) -> impl Iterator<Item = &FieldRegion> {
+        self.fields.iter()
+    }
+
+    pub(crate) fn push(&mut self, field: FieldRegion) {
+        self.fields.push(field);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{vec, vec::Vec};
+
+    use serde::Serialize;
+
+    use super::FieldRegion;
+    use crate::{to_vec_compact_with_manifest, Config, Serializer};
+
+    #[derive(Serialize)]
+    struct Root {
+        hash: [u32; 8],
+        len: u8,
+    }
+
+    #[derive(Serialize)]
+    struct Block {
+        height: u64,
+        flag: bool,
+        version: u8,
+        roots: Vec<Root>,
+        seal: Option<[u32; 2]>,
+        nonce: u32,
+    }
+
+    fn block() -> Block {
+        Block {
+            height: 7,
+            flag: true,
+            version: 2,
+            roots: vec![Root {
+                hash: [9; 8],
+                len: 1,
+            }],
+            seal: Some([3, 4]),
+            nonce: 5,
+        }
+    }
+
+    fn region(name: &'static str, offset: usize, len: usize) -> FieldRegion {
+        FieldRegion { name, offset, len }
+    }
+
+    #[test]
+    fn test_manifest() {
+        let (data, manifest) = to_vec_compact_with_manifest(&block(), Config::new()).unwrap();
+        assert_eq!(
+            vec![
+                region("height", 0, 2),
+                region("flag", 2, 1),
+                // Packs into the word of `flag`
+                region("version", 2, 1),
+                region("roots", 3, 10),
+                // Starts after the word holding the byte of `roots`
+                region("seal", 13, 3),
+                region("nonce", 16, 1),
+            ],
+            manifest.iter().copied().collect::<Vec<_>>()
+        );
+        assert_eq!(17, data.len());
+        assert_eq!(Some([5].as_slice()), manifest.words_of("nonce", &data));
+        assert_eq!(Some([1, 3, 4].as_slice()), manifest.words_of("seal", &data));
+        // Nested fields aren't recorded.
+        assert_eq!(None, manifest.get("hash"));
+    }
+
+    #[test]
+    fn test_field_sizes() {
+        let config = Config::new().field_sizes(true);
+        let (data, manifest) = to_vec_compact_with_manifest(&block(), config).unwrap();
+        let regions: Vec<_> = manifest.iter().collect();
+        // After the field count, the regions tile the output.
+        assert_eq!(1, regions[0].offset);
+        for pair in regions.windows(2) {
+            assert_eq!(pair[0].range().end, pair[1].offset);
+        }
+        assert_eq!(data.len(), regions[5].range().end);
+        assert_eq!(Some([1, 5].as_slice()), manifest.words_of("nonce", &data));
+    }
+
+    #[test]
+    fn test_offsets_continue() {
+        let mut data = Vec::new();
+        let mut serializer = Serializer::new(&mut data);
+        serializer.record_manifest();
+        7u32.serialize(&mut serializer).unwrap();
+        block().serialize(&mut serializer).unwrap();
+        let manifest = serializer.manifest().unwrap().clone();
+        assert_eq!(Some(region("height", 1, 2)), manifest.get("height"));
+        assert!(Serializer::new(Vec::new()).manifest().is_none());
+    }
+}
@@ -0,0 +1,64 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Node markers of the self-describing encoding
+//!
+//! With [Config::self_describing](crate::Config::self_describing), every node
+//! of the encoding is preceded by a marker word. The low byte of the marker
+//! identifies the kind of node, and the upper 24 bits hold the number of
+//! elements of tuples and structs, which have no length prefix otherwise.
+
+use super::err::{Error, Result};
+
+pub(crate) const UNIT: u32 = 0;
+pub(crate) const BOOL: u32 = 1;
+pub(crate) const U8: u32 = 2;
+pub(crate) const I8: u32 = 3;
+pub(crate) const U16: u32 = 4;
+pub(crate) const I16: u32 = 5;
+pub(crate) const U32: u32 = 6;
+pub(crate) const I32: u32 = 7;
+pub(crate) const U64: u32 = 8;
+pub(crate) const I64: u32 = 9;
+pub(crate) const U128: u32 = 10;
+pub(crate) const I128: u32 = 11;
+pub(crate) const F32: u32 = 12;
+pub(crate) const F64: u32 = 13;
+pub(crate) const CHAR: u32 = 14;
+pub(crate) const STR: u32 = 15;
+pub(crate) const BYTES: u32 = 16;
+pub(crate) const OPTION: u32 = 17;
+pub(crate) const SEQ: u32 = 18;
+pub(crate) const MAP: u32 = 19;
+pub(crate) const TUPLE: u32 = 20;
+pub(crate) const STRUCT: u32 = 21;
+pub(crate) const VARIANT: u32 = 22;
+
+/// Marker of a tuple or struct node with `len` elements
+pub(crate) fn with_len(kind: u32, len: usize) -> Result<u32> {
+    match u32::try_from(len) {
+        Ok(len) if len < 1 << 24 => Ok(kind | len << 8),
+        _ => Err(Error::SerializeLengthOverflow { requested: len }),
+    }
+}
+
+#[cfg(feature = "alloc")]
+pub(crate) const fn kind(marker: u32) -> u32 {
+    marker & 0xff
+}
+
+#[cfg(feature = "alloc")]
+pub(crate) const fn len(marker: u32) -> usize {
+    (marker >> 8) as usize
+}
@@ -0,0 +1,321 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Merkle trees over the serialized word stream
+//!
+//! A [MerkleWriter] passes the words written to it through to an inner stream
+//! and hashes them in leaves of a fixed number of words as it goes, so that
+//! the root of the output is known as soon as serialization ends. A single
+//! leaf can later be revealed along with its [MerkleTree::proof] and checked
+//! against the root with [verify].
+//! ```rust
+//! use l2r0_small_serde::{merkle::{self, MerkleWriter, Sha256Hash}, Serializer};
+//! use serde::Serialize;
+//!
+//! let mut words = Vec::new();
+//! let mut writer = MerkleWriter::<_, Sha256Hash>::new(&mut words, 8);
+//! (0..100u32).collect::<Vec<_>>().serialize(&mut Serializer::new(&mut writer)).unwrap();
+//! let (words, tree) = writer.finish();
+//!
+//! let leaf = &words[16..24];
+//! let proof = tree.proof(2).unwrap();
+//! assert!(merkle::verify::<Sha256Hash>(&tree.root(), 2, tree.leaf_count(), leaf, &proof));
+//! ```
+//!
+//! A level with an odd number of nodes moves its last node up unchanged, so
+//! that node has no sibling in the proof.
+
+use alloc::{vec, vec::Vec};
+
+use risc0_zkvm::{
+    serde::WordWrite,
+    sha::{Digest, Impl, Sha256},
+};
+
+/// A hash for the leaves and inner nodes of a Merkle tree
+///
+/// Leaves and nodes should be hashed apart from each other, so that a node
+/// can't pass for a leaf.
+pub trait MerkleHash {
+    /// The hash of a leaf or node
+    type Digest: Clone + Eq;
+
+    /// Hash the words of a leaf
+    fn hash_leaf(words: &[u32]) -> Self::Digest;
+
+    /// Hash the children of a node
+    fn hash_node(left: &Self::Digest, right: &Self::Digest) -> Self::Digest;
+}
+
+/// SHA-256 with a prefix byte of 0 for leaves and 1 for nodes
+///
+/// Uses the accelerated implementation in the zkVM.
+pub struct Sha256Hash;
+
+impl MerkleHash for Sha256Hash {
+    type Digest = Digest;
+
+    fn hash_leaf(words: &[u32]) -> Digest {
+        let mut bytes = vec![0u8];
+        bytes.extend(words.iter().flat_map(|word| word.to_le_bytes()));
+        *Impl::hash_bytes(&bytes)
+    }
+
+    fn hash_node(left: &Digest, right: &Digest) -> Digest {
+        let mut bytes = vec![1u8];
+        bytes.extend_from_slice(left.as_bytes());
+        bytes.extend_from_slice(right.as_bytes());
+        *Impl::hash_bytes(&bytes)
+    }
+}
+
+/// A WordWrite that builds a Merkle tree over the words written to it
+///
+/// Every `leaf_words` words form a leaf, and the last leaf holds what is left
+/// over; nothing written means a single empty leaf. Writes reach the inner
+/// stream right away, and only the last leaf is held in memory besides the
+/// hashes.
+pub struct MerkleWriter<W: WordWrite, H: MerkleHash> {
+    inner: W,
+    leaf_words: usize,
+    leaf: Vec<u32>,
+    leaves: Vec<H::Digest>,
+}
+
+impl<W: WordWrite, H: MerkleHash> MerkleWriter<W, H> {
+    /// Construct a MerkleWriter over leaves of `leaf_words` words that writes
+    /// to `inner`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `leaf_words` is zero.
+    pub fn new(inner: W, leaf_words: usize) -> Self {
+        assert!(leaf_words > 0, "leaves must hold a word");
+        MerkleWriter {
+            inner,
+            leaf_words,
+            leaf: Vec::with_capacity(leaf_words),
+            leaves: Vec::new(),
+        }
+    }
+
+    /// Hash the last leaf and build the tree
+    ///
+    /// Returns the inner stream and the tree.
+    pub fn finish(mut self) -> (W, MerkleTree<H>) {
+        if !self.leaf.is_empty() || self.leaves.is_empty() {
+            self.leaves.push(H::hash_leaf(&self.leaf));
+        }
+        (self.inner, MerkleTree::from_leaves(self.leaves))
+    }
+
+    fn hash_words(&mut self, mut words: &[u32]) {
+        while !words.is_empty() {
+            let len = words.len().min(self.leaf_words - self.leaf.len());
+            self.leaf.extend_from_slice(&words[..len]);
+            words = &words[len..];
+            if self.leaf.len() == self.leaf_words {
+                self.leaves.push(H::hash_leaf(&self.leaf));
+                self.leaf.clear();
+            }
+        }
+    }
+}
+
+impl<W: WordWrite, H: MerkleHash> WordWrite for MerkleWriter<W, H> {
+    fn write_words(&mut self, words: &[u32]) -> risc0_zkvm::serde::Result<()> {
+        self.inner.write_words(words)?;
+        self.hash_words(words);
+        Ok(())
+    }
+
+    fn write_padded_bytes(&mut self, bytes: &[u8]) -> risc0_zkvm::serde::Result<()> {
+        let mut words = Vec::new();
+        words.write_padded_bytes(bytes)?;
+        self.write_words(&words)
+    }
+}
+
+/// The hashes of a Merkle tree, from the leaves up to the root
+pub struct MerkleTree<H: MerkleHash> {
+    levels: Vec<Vec<H::Digest>>,
+}
+
+impl<H: MerkleHash> MerkleTree<H> {
+    /// Build the tree over the hashes of its leaves, of which there must be
+    /// at least one
+    fn from_leaves(leaves: Vec<H::Digest>) -> Self {
+        let mut levels = vec![leaves];
+        while let [.., level] = &levels[..] {
+            if level.len() == 1 {
+                break;
+            }
+            let next = level
+                .chunks(2)
+                .map(|pair| match pair {
+                    [left, right] => H::hash_node(left, right),
+                    [last] => last.clone(),
+                    _ => unreachable!(),
+                })
+                .collect();
+            levels.push(next);
+        }
+        MerkleTree { levels }
+    }
+
+    /// The root of the tree
+    pub fn root(&self) -> H::Digest {
+        self.levels.last().unwrap()[0].clone()
+    }
+
+    /// The number of leaves
+    pub fn leaf_count(&self) -> usize {
+        self.levels[0].len()
+    }
+
+    /// The hashes of the leaves
+    pub fn leaves(&self) -> &[H::Digest] {
+        &self.levels[0]
+    }
+
+    /// The siblings of the nodes on the path from leaf `index` to the root,
+    /// from the bottom up
+    ///
+    /// Returns `None` if there is no such leaf.
+    pub fn proof(&self, mut index: usize) -> Option<Vec<H::Digest>> {
+        if index >= self.leaf_count() {
+            return None;
+        }
+        let mut proof = Vec::new();
+        for level in &self.levels[..self.levels.len() - 1] {
+            if let Some(sibling) = level.get(index ^ 1) {
+                proof.push(sibling.clone());
+            }
+            index /= 2;
+        }
+        Some(proof)
+    }
+}
+
+/// Check that `words` are leaf `index` of a tree with `leaf_count` leaves and
+/// the given `root`
+///
+/// `proof` must have been produced by [MerkleTree::proof].
+pub fn verify<H: MerkleHash>(
+    root: &H::Digest,
+    mut index: usize,
+    mut leaf_count: usize,
+    words: &[u32],
+    proof: &[H::Digest],
+) -> bool {
+    if index >= leaf_count {
+        return false;
+    }
+    let mut hash = H::hash_leaf(words);
+    let mut siblings = proof.iter();
+    while leaf_count > 1 {
+        // The last node of an odd level has no sibling.
+        if index ^ 1 < leaf_count {
+            let Some(sibling) = siblings.next() else {
+                return false;
+            };
+            hash = match index % 2 {
+                0 => H::hash_node(&hash, sibling),
+                _ => H::hash_node(sibling, &hash),
+            };
+        }
+        index /= 2;
+        leaf_count = leaf_count.div_ceil(2);
+    }
+    siblings.next().is_none() && hash == *root
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::String, vec::Vec};
+
+    use serde::Serialize;
+
+    use super::*;
+    use crate::{to_vec_compact, Serializer};
+
+    /// Sums instead of hashing, to make trees easy to check by hand
+    struct Sum;
+
+    impl MerkleHash for Sum {
+        type Digest = u64;
+
+        fn hash_leaf(words: &[u32]) -> u64 {
+            words.iter().map(|&word| word as u64).sum()
+        }
+
+        fn hash_node(left: &u64, right: &u64) -> u64 {
+            left * 1000 + right
+        }
+    }
+
+    fn merkleize<H: MerkleHash, T: Serialize>(
+        value: &T,
+        leaf_words: usize,
+    ) -> (Vec<u32>, MerkleTree<H>) {
+        let mut writer = MerkleWriter::<_, H>::new(Vec::new(), leaf_words);
+        value.serialize(&mut Serializer::new(&mut writer)).unwrap();
+        writer.finish()
+    }
+
+    #[test]
+    fn test_tree() {
+        let (words, tree) = merkleize::<Sum, _>(&[1u32, 2, 3, 4, 5], 2);
+        assert_eq!(to_vec_compact(&[1u32, 2, 3, 4, 5]).unwrap(), words);
+        assert_eq!(&[3, 7, 5], tree.leaves());
+        assert_eq!(3007 * 1000 + 5, tree.root());
+        assert_eq!(Some(vec![7, 5]), tree.proof(0));
+        // The last leaf only has a sibling on the level of the root.
+        assert_eq!(Some(vec![3007]), tree.proof(2));
+        assert_eq!(None, tree.proof(3));
+
+        let (_, tree) = merkleize::<Sum, _>(&(), 2);
+        assert_eq!(0, tree.root());
+        assert_eq!(Some(vec![]), tree.proof(0));
+    }
+
+    #[test]
+    fn test_verify() {
+        let value = (String::from("partially revealed"), [7u64; 9], true);
+        for leaf_words in [1, 3, 8, 64] {
+            let (words, tree) = merkleize::<Sha256Hash, _>(&value, leaf_words);
+            let root = tree.root();
+            let count = tree.leaf_count();
+            assert_eq!(words.len().div_ceil(leaf_words), count);
+            for (index, leaf) in words.chunks(leaf_words).enumerate() {
+                let proof = tree.proof(index).unwrap();
+                assert!(verify::<Sha256Hash>(&root, index, count, leaf, &proof));
+                let mut altered = leaf.to_vec();
+                altered[0] ^= 1;
+                assert!(!verify::<Sha256Hash>(&root, index, count, &altered, &proof));
+                if count > 1 {
+                    let other = (index + 1) % count;
+                    assert!(!verify::<Sha256Hash>(&root, other, count, leaf, &proof));
+                    assert!(!verify::<Sha256Hash>(
+                        &root,
+                        index,
+                        count,
+                        leaf,
+                        &proof[1..]
+                    ));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,111 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::vec::Vec;
+
+use rayon::prelude::*;
+use serde::ser::SerializeSeq;
+
+use super::{config::Config, err::Result, to_vec_compact_with_config, Serializer};
+
+/// Number of elements serialized by each task
+const CHUNK_LEN: usize = 1024;
+
+/// Serialize a slice to a vector of u32 words, using multiple threads
+///
+/// The output is identical to that of [to_vec_compact](crate::to_vec_compact)
+/// for the same slice. Chunks of elements are serialized in parallel and
+/// spliced together. Elements ending in a partially filled word of packed
+/// bytes share that word with the next element, so when a chunk doesn't end
+/// on a word boundary, the slice is serialized sequentially instead.
+pub fn to_vec_compact_par<T>(values: &[T]) -> Result<Vec<u32>>
+where
+    T: serde::Serialize + Sync,
+{
+    to_vec_compact_par_with_config(values, Config::default())
+}
+
+/// Serialize a slice to a vector of u32 words using the given configuration,
+/// using multiple threads
+///
+/// See [to_vec_compact_par]. Interned strings refer to earlier strings across
+/// chunk boundaries, so [Config::intern_strings] always serializes
+/// sequentially.
+pub fn to_vec_compact_par_with_config<T>(values: &[T], config: Config) -> Result<Vec<u32>>
+where
+    T: serde::Serialize + Sync,
+{
+    if config.intern_strings || values.len() <= CHUNK_LEN {
+        return to_vec_compact_with_config(values, config);
+    }
+    let chunks = values
+        .par_chunks(CHUNK_LEN)
+        .map(|chunk| {
+            let mut words = Vec::new();
+            let partial = Serializer::with_config(&mut words, config).serialize_elements(chunk)?;
+            Ok((words, partial))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    if chunks[..chunks.len() - 1]
+        .iter()
+        .any(|(_, partial)| *partial)
+    {
+        return to_vec_compact_with_config(values, config);
+    }
+
+    let mut vec =
+        Vec::with_capacity(1 + chunks.iter().map(|(words, _)| words.len()).sum::<usize>());
+    let mut serializer = Serializer::with_config(&mut vec, config);
+    serde::Serializer::serialize_seq(&mut serializer, Some(values.len()))?.end()?;
+    for (words, _) in chunks {
+        vec.extend_from_slice(&words);
+    }
+    if config.pad_to > 1 {
+        vec.resize(vec.len().next_multiple_of(config.pad_to), 0);
+    }
+    Ok(vec)
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::String, vec::Vec};
+
+    use super::*;
+    use crate::to_vec_compact;
+
+    #[test]
+    fn test_matches_sequential() {
+        let values: Vec<(u32, String, u8)> =
+            (0..5000).map(|i| (i, "witness".into(), i as u8)).collect();
+        assert_eq!(
+            to_vec_compact(&values).unwrap(),
+            to_vec_compact_par(&values).unwrap()
+        );
+
+        let config = Config::new().self_describing(true).pad_to(8);
+        assert_eq!(
+            to_vec_compact_with_config(&values, config).unwrap(),
+            to_vec_compact_par_with_config(&values, config).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_packed_bytes() {
+        let values: Vec<[u8; 3]> = (0..5000).map(|i| [i as u8; 3]).collect();
+        assert_eq!(
+            to_vec_compact(&values).unwrap(),
+            to_vec_compact_par(&values).unwrap()
+        );
+    }
+}
@@ -0,0 +1,149 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::vec::Vec;
+
+use super::{err::Result, to_vec_into_with_config, Config};
+
+/// A pool of word buffers reused across serializations
+///
+/// [BufferPool::to_vec] serializes into a buffer taken from the pool, and
+/// [BufferPool::recycle] hands it back once the caller is done with it, so a
+/// host encoding many values only allocates until the pool holds buffers
+/// large enough for them. The pool isn't synchronized; a multithreaded host
+/// keeps one per thread or behind a lock.
+/// ```rust
+/// use l2r0_small_serde::BufferPool;
+///
+/// let mut pool = BufferPool::new(4);
+/// for i in 0..1000u64 {
+///     let words = pool.to_vec(&(i, "input")).unwrap();
+///     assert_eq!(i as u32, words[0]);
+///     pool.recycle(words);
+/// }
+/// assert_eq!(1, pool.idle());
+/// ```
+#[derive(Debug, Default)]
+pub struct BufferPool {
+    buffers: Vec<Vec<u32>>,
+    max_idle: usize,
+}
+
+impl BufferPool {
+    /// Construct a pool that keeps at most `max_idle` buffers between uses
+    pub fn new(max_idle: usize) -> Self {
+        BufferPool {
+            buffers: Vec::new(),
+            max_idle,
+        }
+    }
+
+    /// Serialize to a buffer from the pool
+    ///
+    /// Allocates a new buffer if the pool is empty.
+    pub fn to_vec<T>(&mut self, value: &T) -> Result<Vec<u32>>
+    where
+        T: serde::Serialize + ?Sized,
+    {
+        self.to_vec_with_config(value, Config::default())
+    }
+
+    /// Serialize to a buffer from the pool using the given configuration
+    pub fn to_vec_with_config<T>(&mut self, value: &T, config: Config) -> Result<Vec<u32>>
+    where
+        T: serde::Serialize + ?Sized,
+    {
+        let mut vec = self.buffers.pop().unwrap_or_default();
+        match to_vec_into_with_config(value, &mut vec, config) {
+            Ok(()) => Ok(vec),
+            Err(err) => {
+                self.recycle(vec);
+                Err(err)
+            }
+        }
+    }
+
+    /// Return a buffer to the pool
+    ///
+    /// The buffer is dropped if the pool already holds `max_idle` buffers.
+    pub fn recycle(&mut self, mut vec: Vec<u32>) {
+        if self.buffers.len() < self.max_idle {
+            vec.clear();
+            self.buffers.push(vec);
+        }
+    }
+
+    /// The number of buffers waiting in the pool
+    pub fn idle(&self) -> usize {
+        self.buffers.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::String, vec, vec::Vec};
+
+    use super::*;
+    use crate::{to_vec_compact, to_vec_compact_with_config, to_vec_into};
+
+    /// A sequence of unknown length, which can't be serialized
+    struct Unsized;
+
+    impl serde::Serialize for Unsized {
+        fn serialize<S: serde::Serializer>(
+            &self,
+            serializer: S,
+        ) -> core::result::Result<S::Ok, S::Error> {
+            serializer.collect_seq((0..3u32).filter(|i| i % 2 == 0))
+        }
+    }
+
+    #[test]
+    fn test_to_vec_into() {
+        let mut vec = vec![9; 64];
+        to_vec_into(&(1u32, String::from("reused")), &mut vec).unwrap();
+        assert_eq!(
+            to_vec_compact(&(1u32, String::from("reused"))).unwrap(),
+            vec
+        );
+        assert!(vec.capacity() >= 64);
+    }
+
+    #[test]
+    fn test_pool() {
+        let mut pool = BufferPool::new(2);
+        let first = pool.to_vec(&[7u64; 16]).unwrap();
+        let ptr = first.as_ptr();
+        pool.recycle(first);
+        assert_eq!(1, pool.idle());
+
+        // The buffer comes back for the next value.
+        let config = Config::new().pad_to(8);
+        let second = pool.to_vec_with_config(&3u8, config).unwrap();
+        assert_eq!(ptr, second.as_ptr());
+        assert_eq!(to_vec_compact_with_config(&3u8, config).unwrap(), second);
+        assert_eq!(0, pool.idle());
+
+        let buffers: Vec<_> = (0..3).map(|i| pool.to_vec(&i).unwrap()).collect();
+        for vec in buffers {
+            pool.recycle(vec);
+        }
+        assert_eq!(2, pool.idle());
+
+        // A failed serialization keeps its buffer in the pool.
+        let mut small = BufferPool::new(1);
+        assert!(small.to_vec(&Unsized).is_err());
+        assert_eq!(1, small.idle());
+    }
+}
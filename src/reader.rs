@@ -0,0 +1,316 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Element-by-element decoding of sequences and maps
+//!
+//! A [SeqReader] or [MapReader] decodes a sequence or map one element at a
+//! time, so that the whole collection never has to be held in memory. The
+//! collection must be a top-level value, such as a sequence committed to the
+//! journal on its own.
+//! ```rust
+//! use l2r0_small_serde::{to_vec_compact, Deserializer};
+//!
+//! let data = to_vec_compact(&vec![(1u32, 2u64), (3, 4)]).unwrap();
+//! let mut deserializer = Deserializer::from_words(&data);
+//! let mut total = 0;
+//! for row in deserializer.seq_reader::<(u32, u64)>().unwrap() {
+//!     let (a, b) = row.unwrap();
+//!     total += a as u64 * b;
+//! }
+//! assert_eq!(14, total);
+//! ```
+//!
+//! [for_each_entry] does the same for a map held in a slice, passing each
+//! entry to a closure.
+
+use core::marker::PhantomData;
+
+use risc0_zkvm::serde::WordRead;
+use serde::Deserialize;
+
+use super::{err::Result, marker, Config, Deserializer};
+
+/// An iterator over the elements of an encoded sequence
+///
+/// Yields `len` elements, where `len` is the length of the sequence, and stops
+/// after the first error.
+pub struct SeqReader<'a, 'de, R: WordRead + 'de, T> {
+    deserializer: &'a mut Deserializer<'de, R>,
+    remaining: usize,
+    phantom: PhantomData<T>,
+}
+
+impl<'a, 'de, R: WordRead + 'de, T> SeqReader<'a, 'de, R, T> {
+    /// The number of elements not yet decoded
+    pub fn remaining(&self) -> usize {
+        self.remaining
+    }
+}
+
+impl<'de, R: WordRead + 'de, T: Deserialize<'de>> Iterator for SeqReader<'_, 'de, R, T> {
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Result<T>> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        let res = T::deserialize(&mut *self.deserializer).and_then(|value| {
+            if self.remaining == 0 {
+                self.deserializer.exit()?;
+            }
+            Ok(value)
+        });
+        if res.is_err() {
+            self.remaining = 0;
+        }
+        Some(res)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.remaining))
+    }
+}
+
+/// An iterator over the entries of an encoded map
+///
+/// Yields `len` key-value pairs, where `len` is the length of the map, and
+/// stops after the first error.
+pub struct MapReader<'a, 'de, R: WordRead + 'de, K, V> {
+    deserializer: &'a mut Deserializer<'de, R>,
+    remaining: usize,
+    phantom: PhantomData<(K, V)>,
+}
+
+impl<'a, 'de, R: WordRead + 'de, K, V> MapReader<'a, 'de, R, K, V> {
+    /// The number of entries not yet decoded
+    pub fn remaining(&self) -> usize {
+        self.remaining
+    }
+}
+
+impl<'de, R, K, V> Iterator for MapReader<'_, 'de, R, K, V>
+where
+    R: WordRead + 'de,
+    K: Deserialize<'de>,
+    V: Deserialize<'de>,
+{
+    type Item = Result<(K, V)>;
+
+    fn next(&mut self) -> Option<Result<(K, V)>> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        let res = K::deserialize(&mut *self.deserializer).and_then(|key| {
+            let value = V::deserialize(&mut *self.deserializer)?;
+            if self.remaining == 0 {
+                self.deserializer.exit()?;
+            }
+            Ok((key, value))
+        });
+        if res.is_err() {
+            self.remaining = 0;
+        }
+        Some(res)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.remaining))
+    }
+}
+
+impl<'de, R: WordRead + 'de> Deserializer<'de, R> {
+    /// Start decoding a sequence element by element
+    ///
+    /// Reads the length of the sequence, which must be a top-level value. Once
+    /// the reader is exhausted, the deserializer continues after the sequence.
+    pub fn seq_reader<T: Deserialize<'de>>(&mut self) -> Result<SeqReader<'_, 'de, R, T>> {
+        let remaining = self.read_len(marker::SEQ)?;
+        self.enter()?;
+        if remaining == 0 {
+            self.exit()?;
+        }
+        Ok(SeqReader {
+            deserializer: self,
+            remaining,
+            phantom: PhantomData,
+        })
+    }
+
+    /// Start decoding a map entry by entry
+    ///
+    /// Reads the length of the map, which must be a top-level value. Once the
+    /// reader is exhausted, the deserializer continues after the map.
+    pub fn map_reader<K, V>(&mut self) -> Result<MapReader<'_, 'de, R, K, V>>
+    where
+        K: Deserialize<'de>,
+        V: Deserialize<'de>,
+    {
+        let remaining = self.read_len(marker::MAP)?;
+        self.enter()?;
+        if remaining == 0 {
+            self.exit()?;
+        }
+        Ok(MapReader {
+            deserializer: self,
+            remaining,
+            phantom: PhantomData,
+        })
+    }
+}
+
+/// Decode the map held in `words` entry by entry, passing each to `f`
+///
+/// Unlike decoding a `BTreeMap`, no entry is kept once `f` returns. As with
+/// [from_words](crate::from_words), keys and values may borrow from `words`.
+/// Entries before the first error have been passed to `f` by the time it is
+/// returned.
+pub fn for_each_entry<'de, K, V>(words: &'de [u32], f: impl FnMut(K, V)) -> Result<()>
+where
+    K: Deserialize<'de>,
+    V: Deserialize<'de>,
+{
+    for_each_entry_with_config(words, Config::default(), f)
+}
+
+/// Decode the map held in `words` entry by entry using the given
+/// configuration, passing each to `f`
+///
+/// `config` must match the configuration `words` was serialized with.
+pub fn for_each_entry_with_config<'de, K, V>(
+    words: &'de [u32],
+    config: Config,
+    mut f: impl FnMut(K, V),
+) -> Result<()>
+where
+    K: Deserialize<'de>,
+    V: Deserialize<'de>,
+{
+    let mut deserializer = Deserializer::from_words_with_config(words, config);
+    for entry in deserializer.map_reader()? {
+        let (key, value) = entry?;
+        f(key, value);
+    }
+    deserializer.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{collections::BTreeMap, string::String, vec::Vec};
+
+    use super::*;
+    use crate::{err::Error, to_vec_compact, to_vec_compact_with_config, Config};
+
+    #[test]
+    fn test_seq() {
+        let input: Vec<(u32, String)> = (0..1000).map(|i| (i, "row".into())).collect();
+        let data = [
+            to_vec_compact(&input).unwrap(),
+            to_vec_compact(&7u32).unwrap(),
+        ]
+        .concat();
+        let mut deserializer = Deserializer::from_words(&data);
+        let mut reader = deserializer.seq_reader::<(u32, String)>().unwrap();
+        assert_eq!(1000, reader.remaining());
+        for (i, row) in reader.by_ref().enumerate() {
+            assert_eq!((i as u32, String::from("row")), row.unwrap());
+        }
+        assert_eq!(0, reader.remaining());
+        assert_eq!(7, u32::deserialize(&mut deserializer).unwrap());
+        deserializer.finish().unwrap();
+    }
+
+    #[test]
+    fn test_packed_bytes() {
+        let data = [
+            to_vec_compact(&alloc::vec![1u8, 2, 3, 4, 5]).unwrap(),
+            to_vec_compact(&6u8).unwrap(),
+        ]
+        .concat();
+        let mut deserializer = Deserializer::from_words(&data);
+        let bytes: Vec<u8> = deserializer
+            .seq_reader()
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!([1, 2, 3, 4, 5], bytes.as_slice());
+        assert_eq!(6, u8::deserialize(&mut deserializer).unwrap());
+    }
+
+    #[test]
+    fn test_map() {
+        let input: BTreeMap<String, u64> = [("a".into(), 1), ("b".into(), 2)].into();
+        let config = Config::new().self_describing(true);
+        let data = to_vec_compact_with_config(&input, config).unwrap();
+        let mut deserializer = Deserializer::from_words_with_config(&data, config);
+        let output: BTreeMap<String, u64> = deserializer
+            .map_reader()
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(input, output);
+
+        let mut deserializer = Deserializer::from_words_with_config(&data, config);
+        assert_eq!(
+            Err(Error::DeserializeBadMarker),
+            deserializer.seq_reader::<u64>().map(|_| ())
+        );
+    }
+
+    #[test]
+    fn test_for_each_entry() {
+        let input: BTreeMap<String, (u32, bool)> = (0..100)
+            .map(|i| (alloc::format!("key {i}"), (i, i % 3 == 0)))
+            .collect();
+        let config = Config::new().intern_strings(true);
+        let data = to_vec_compact_with_config(&input, config).unwrap();
+        let mut selected = Vec::new();
+        for_each_entry_with_config(&data, config, |key: String, (i, flag): (u32, bool)| {
+            if flag {
+                selected.push((key, i));
+            }
+        })
+        .unwrap();
+        assert_eq!(34, selected.len());
+        assert_eq!((String::from("key 99"), 99), selected[33]);
+
+        let data = to_vec_compact(&BTreeMap::from([("a", 1u64), ("b", 2)])).unwrap();
+        let mut values = Vec::new();
+        for_each_entry(&data, |_: String, value: u64| values.push(value)).unwrap();
+        assert_eq!([1, 2], values.as_slice());
+
+        let mut count = 0;
+        assert_eq!(
+            Err(Error::DeserializeUnexpectedEnd {
+                needed: 1,
+                available: Some(0)
+            }),
+            for_each_entry(&data[..data.len() - 1], |_: String, _: u64| count += 1)
+        );
+        assert_eq!(1, count);
+    }
+
+    #[test]
+    fn test_stops_after_error() {
+        let data = to_vec_compact(&alloc::vec![Some(1u32), None, Some(3)]).unwrap();
+        let mut corrupted = data.clone();
+        corrupted[3] = 2;
+        let mut deserializer = Deserializer::from_words(&corrupted);
+        let mut reader = deserializer.seq_reader::<Option<u32>>().unwrap();
+        assert_eq!(Some(Ok(Some(1))), reader.next());
+        assert_eq!(Some(Err(Error::DeserializeBadOption)), reader.next());
+        assert_eq!(None, reader.next());
+    }
+}
@@ -0,0 +1,329 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use risc0_zkvm::serde::WordWrite;
+
+use crate::{
+    err::{Error, Result},
+    serializer::PendingBytes,
+    Config, Serializer,
+};
+
+/// The stream a [RoutingWriter] writes a field to
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Route {
+    /// The first stream, which also receives everything outside the fields
+    First,
+    /// The second stream
+    Second,
+}
+
+/// A WordWrite that writes each top-level struct field to one of two streams
+///
+/// The policy picks the [Route] of every field of the top-level struct from
+/// its name, so that a single serialization pass can, for example, commit the
+/// public fields to the journal and send the private ones to the host. Each
+/// stream receives its fields encoded as in a struct of their own, so the
+/// bytes of fields routed to the same stream still pack together. The
+/// top-level struct may be a struct variant or be wrapped in newtype structs;
+/// everything outside its fields goes to the first stream.
+///
+/// The writer must be passed to [Serializer::with_routing], which reports the
+/// fields to it. Configurations that write struct headers, intern strings or
+/// pad the output can't be split, and fail with [Error::NotSupported] on the
+/// first field.
+/// ```rust
+/// use l2r0_small_serde::{Config, Route, RoutingWriter, Serializer};
+/// use serde::Serialize;
+///
+/// #[derive(Serialize)]
+/// struct Output {
+///     id: u32,
+///     witness: u64,
+///     ok: bool,
+/// }
+///
+/// let (mut journal, mut host) = (Vec::new(), Vec::new());
+/// let policy = |field: &str| match field {
+///     "witness" => Route::Second,
+///     _ => Route::First,
+/// };
+/// let writer = RoutingWriter::new(&mut journal, &mut host, policy);
+/// let mut serializer = Serializer::with_routing(writer, Config::new());
+/// let output = Output { id: 7, witness: 9, ok: true };
+/// output.serialize(&mut serializer).unwrap();
+/// assert_eq!([7, 1], journal.as_slice());
+/// assert_eq!([9, 0], host.as_slice());
+/// ```
+pub struct RoutingWriter<A: WordWrite, B: WordWrite, P> {
+    first: A,
+    second: B,
+    policy: P,
+    route: Route,
+    /// The partially filled word of bytes of the stream not being written
+    parked: PendingBytes,
+}
+
+impl<A: WordWrite, B: WordWrite, P> RoutingWriter<A, B, P> {
+    /// Construct a RoutingWriter that writes to `first` and `second` as
+    /// `policy` picks
+    pub fn new(first: A, second: B, policy: P) -> Self {
+        RoutingWriter {
+            first,
+            second,
+            policy,
+            route: Route::First,
+            parked: PendingBytes::default(),
+        }
+    }
+
+    /// Get back the two streams
+    pub fn into_inner(self) -> (A, B) {
+        (self.first, self.second)
+    }
+}
+
+impl<A: WordWrite, B: WordWrite, P> WordWrite for RoutingWriter<A, B, P> {
+    fn write_words(&mut self, words: &[u32]) -> risc0_zkvm::serde::Result<()> {
+        match self.route {
+            Route::First => self.first.write_words(words),
+            Route::Second => self.second.write_words(words),
+        }
+    }
+
+    fn write_padded_bytes(&mut self, bytes: &[u8]) -> risc0_zkvm::serde::Result<()> {
+        match self.route {
+            Route::First => self.first.write_padded_bytes(bytes),
+            Route::Second => self.second.write_padded_bytes(bytes),
+        }
+    }
+}
+
+impl<A, B, P> Serializer<RoutingWriter<A, B, P>>
+where
+    A: WordWrite,
+    B: WordWrite,
+    P: FnMut(&'static str) -> Route,
+{
+    /// Construct a Serializer that splits the top-level struct fields between
+    /// the streams of `writer`
+    pub fn with_routing(writer: RoutingWriter<A, B, P>, config: Config) -> Self {
+        let mut serializer = Self::with_config(writer, config);
+        serializer.set_field_hook(route_field::<A, B, P>);
+        serializer
+    }
+}
+
+/// Switch to the stream of `field`, or back to the first stream once the
+/// top-level struct has been written
+fn route_field<A, B, P>(
+    serializer: &mut Serializer<RoutingWriter<A, B, P>>,
+    field: Option<&'static str>,
+) -> Result<()>
+where
+    A: WordWrite,
+    B: WordWrite,
+    P: FnMut(&'static str) -> Route,
+{
+    let Some(field) = field else {
+        return end_routes(serializer);
+    };
+    let config = serializer.config();
+    if config.self_describing
+        || config.counts_fields()
+        || config.intern_strings
+        || config.pad_to > 1
+    {
+        return Err(Error::NotSupported);
+    }
+    let writer = serializer.writer_mut();
+    let route = (writer.policy)(field);
+    if route != writer.route {
+        writer.route = route;
+        let mut parked = writer.parked;
+        serializer.swap_pending(&mut parked);
+        serializer.writer_mut().parked = parked;
+    }
+    Ok(())
+}
+
+/// End the bytes of the second stream, and those of the first unless they
+/// pack with the top-level values that follow
+fn end_routes<A, B, P>(serializer: &mut Serializer<RoutingWriter<A, B, P>>) -> Result<()>
+where
+    A: WordWrite,
+    B: WordWrite,
+{
+    let mut current = PendingBytes::default();
+    serializer.swap_pending(&mut current);
+    let writer = serializer.writer_mut();
+    let parked = core::mem::take(&mut writer.parked);
+    let (mut first, second) = match writer.route {
+        Route::First => (current, parked),
+        Route::Second => (parked, current),
+    };
+    writer.route = Route::Second;
+    serializer.write_pending(second)?;
+    serializer.writer_mut().route = Route::First;
+    match serializer.config().pack_top_level {
+        true => serializer.swap_pending(&mut first),
+        false => serializer.write_pending(first)?,
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::String, vec, vec::Vec};
+
+    use serde::Serialize;
+
+    use super::{Route, RoutingWriter};
+    use crate::{to_vec_compact_with_config, Config, Error, Serializer, VariantTag};
+
+    #[derive(Serialize)]
+    struct Pair {
+        a: u8,
+        b: u8,
+    }
+
+    #[derive(Serialize)]
+    struct Commit {
+        id: u32,
+        flag: bool,
+        secret: u64,
+        tag: u8,
+        note: String,
+        pair: Pair,
+        last: u8,
+    }
+
+    #[derive(Serialize)]
+    struct Public {
+        id: u32,
+        flag: bool,
+        note: String,
+        last: u8,
+    }
+
+    #[derive(Serialize)]
+    struct Private {
+        secret: u64,
+        tag: u8,
+        pair: Pair,
+    }
+
+    fn policy(field: &str) -> Route {
+        match field {
+            "secret" | "tag" | "pair" => Route::Second,
+            _ => Route::First,
+        }
+    }
+
+    fn route<T: Serialize>(value: &T, config: Config) -> Result<(Vec<u32>, Vec<u32>), Error> {
+        let (mut first, mut second) = (Vec::new(), Vec::new());
+        let writer = RoutingWriter::new(&mut first, &mut second, policy);
+        let mut serializer = Serializer::with_routing(writer, config);
+        value.serialize(&mut serializer)?;
+        serializer.finish()?;
+        Ok((first, second))
+    }
+
+    #[test]
+    fn test_routing() {
+        let commit = Commit {
+            id: 7,
+            flag: true,
+            secret: 1 << 40,
+            tag: 2,
+            note: String::from("ok"),
+            pair: Pair { a: 3, b: 4 },
+            last: 5,
+        };
+        let public = Public {
+            id: 7,
+            flag: true,
+            note: String::from("ok"),
+            last: 5,
+        };
+        let private = Private {
+            secret: 1 << 40,
+            tag: 2,
+            pair: Pair { a: 3, b: 4 },
+        };
+        for config in [
+            Config::new(),
+            Config::new().big_endian(true),
+            Config::new().pack_top_level(true),
+            Config::new()
+                .variant_tag(VariantTag::Byte)
+                .byte_option_tags(true),
+        ] {
+            let (first, second) = route(&commit, config).unwrap();
+            assert_eq!(to_vec_compact_with_config(&public, config).unwrap(), first);
+            assert_eq!(
+                to_vec_compact_with_config(&private, config).unwrap(),
+                second
+            );
+        }
+    }
+
+    #[test]
+    fn test_outside_fields() {
+        #[derive(Serialize)]
+        enum Event {
+            Spent { id: u32, secret: u64 },
+        }
+
+        let config = Config::new();
+        let event = Event::Spent { id: 1, secret: 2 };
+        assert_eq!(Ok((vec![0, 1], vec![2, 0])), route(&event, config));
+        // Only the fields of the top-level struct are routed.
+        let nested = (
+            9u8,
+            Private {
+                secret: 1,
+                tag: 2,
+                pair: Pair { a: 3, b: 4 },
+            },
+        );
+        assert_eq!(
+            Ok((to_vec_compact_with_config(&nested, config).unwrap(), vec![])),
+            route(&nested, config)
+        );
+
+        // Values after the struct go to the first stream.
+        let (mut first, mut second) = (Vec::new(), Vec::new());
+        let writer = RoutingWriter::new(&mut first, &mut second, policy);
+        let mut serializer = Serializer::with_routing(writer, config);
+        Pair { a: 1, b: 2 }.serialize(&mut serializer).unwrap();
+        6u8.serialize(&mut serializer).unwrap();
+        assert_eq!([0x0201, 6].as_slice(), first);
+        assert!(second.is_empty());
+    }
+
+    #[test]
+    fn test_unsupported() {
+        let value = Pair { a: 1, b: 2 };
+        for config in [
+            Config::new().self_describing(true),
+            Config::new().field_count(true),
+            Config::new().field_sizes(true),
+            Config::new().intern_strings(true),
+            Config::new().pad_to(4),
+        ] {
+            assert_eq!(Err(Error::NotSupported), route(&value, config));
+        }
+    }
+}
@@ -12,21 +12,40 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use alloc::vec::Vec;
+#[cfg(feature = "alloc")]
+use alloc::{collections::BTreeMap, string::String, vec::Vec};
 use risc0_zkvm::serde::WordWrite;
-
-use super::err::{Error, Result};
+use serde::ser::Impossible;
+
+#[cfg(feature = "alloc")]
+use super::costs::{FieldCosts, Recorder};
+#[cfg(feature = "alloc")]
+use super::manifest::{FieldRegion, Manifest};
+use super::stats::{EncodingStats, StatsRecorder, Usage};
+#[cfg(feature = "tracing")]
+use super::trace::{self, StructSpans};
+use super::{
+    align_up,
+    config::{variant_name_hash, Config, VariantTag},
+    err::{Error, Result},
+    marker, MAX_DEPTH, WORD_SIZE,
+};
 
 /// Serialize to a vector of u32 words
+#[cfg(feature = "alloc")]
 pub fn to_vec_compact<T>(value: &T) -> Result<Vec<u32>>
 where
     T: serde::Serialize + ?Sized,
 {
     // Use the in-memory size of the value as a guess for the length
     // of the serialized value.
+    #[cfg(feature = "tracing")]
+    let span = trace::serialize_span(value).entered();
     let mut vec: Vec<u32> = Vec::with_capacity(core::mem::size_of_val(value));
     let mut serializer = Serializer::new(&mut vec);
     value.serialize(&mut serializer)?;
+    #[cfg(feature = "tracing")]
+    span.record("words", vec.len());
     Ok(vec)
 }
 
@@ -34,35 +53,255 @@ where
 ///
 /// Includes a caller-provided hint `cap` giving the capacity of u32 words
 /// necessary to serialize `value`.
+#[cfg(feature = "alloc")]
 pub fn to_vec_compact_with_capacity<T>(value: &T, cap: usize) -> Result<Vec<u32>>
 where
     T: serde::Serialize + ?Sized,
 {
+    #[cfg(feature = "tracing")]
+    let span = trace::serialize_span(value).entered();
     let mut vec: Vec<u32> = Vec::with_capacity(cap);
     let mut serializer = Serializer::new(&mut vec);
     value.serialize(&mut serializer)?;
+    #[cfg(feature = "tracing")]
+    span.record("words", vec.len());
+    Ok(vec)
+}
+
+/// Serialize to a vector of u32 words using the given configuration
+#[cfg(feature = "alloc")]
+pub fn to_vec_compact_with_config<T>(value: &T, config: Config) -> Result<Vec<u32>>
+where
+    T: serde::Serialize + ?Sized,
+{
+    #[cfg(feature = "tracing")]
+    let span = trace::serialize_span(value).entered();
+    let mut vec: Vec<u32> = Vec::with_capacity(core::mem::size_of_val(value));
+    let mut serializer = Serializer::with_config(&mut vec, config);
+    value.serialize(&mut serializer)?;
+    serializer.finish()?;
+    #[cfg(feature = "tracing")]
+    span.record("words", vec.len());
+    Ok(vec)
+}
+
+/// Serialize into `vec`, replacing its contents
+///
+/// The capacity of `vec` is kept, so a buffer reused across values stops
+/// allocating once it has grown to fit them. The contents are unspecified if
+/// serialization fails.
+#[cfg(feature = "alloc")]
+pub fn to_vec_into<T>(value: &T, vec: &mut Vec<u32>) -> Result<()>
+where
+    T: serde::Serialize + ?Sized,
+{
+    to_vec_into_with_config(value, vec, Config::default())
+}
+
+/// Serialize into `vec` using the given configuration, replacing its contents
+#[cfg(feature = "alloc")]
+pub fn to_vec_into_with_config<T>(value: &T, vec: &mut Vec<u32>, config: Config) -> Result<()>
+where
+    T: serde::Serialize + ?Sized,
+{
+    #[cfg(feature = "tracing")]
+    let span = trace::serialize_span(value).entered();
+    vec.clear();
+    let mut serializer = Serializer::with_config(&mut *vec, config);
+    value.serialize(&mut serializer)?;
+    serializer.finish()?;
+    #[cfg(feature = "tracing")]
+    span.record("words", vec.len());
+    Ok(())
+}
+
+/// Serialize to a vector of u32 words, along with the regions of its
+/// top-level struct fields
+///
+/// See [Manifest] for the fields recorded.
+#[cfg(feature = "alloc")]
+pub fn to_vec_compact_with_manifest<T>(value: &T, config: Config) -> Result<(Vec<u32>, Manifest)>
+where
+    T: serde::Serialize + ?Sized,
+{
+    let mut vec: Vec<u32> = Vec::new();
+    let mut serializer = Serializer::with_config(&mut vec, config);
+    serializer.record_manifest();
+    value.serialize(&mut serializer)?;
+    serializer.finish()?;
+    let manifest = serializer.manifest.take().unwrap_or_default();
+    Ok((vec, manifest))
+}
+
+/// Serialize the items of an iterator to a vector of u32 words
+///
+/// Produces the same words as serializing a collection of the `len` items,
+/// without collecting them first. See [Serializer::serialize_iter].
+#[cfg(feature = "alloc")]
+pub fn to_vec_from_iter<I>(len: usize, iter: I) -> Result<Vec<u32>>
+where
+    I: IntoIterator,
+    I::Item: serde::Serialize,
+{
+    let mut vec: Vec<u32> = Vec::new();
+    let mut serializer = Serializer::new(&mut vec);
+    serializer.serialize_iter(len, iter)?;
     Ok(vec)
 }
 
+/// Serialize to a fixed buffer of u32 words
+///
+/// Returns the number of words written, or [Error::SerializeBufferFull] if
+/// `buf` is too small. This doesn't allocate, so it is available without the
+/// `alloc` feature.
+pub fn to_slice_compact<T>(value: &T, buf: &mut [u32]) -> Result<usize>
+where
+    T: serde::Serialize + ?Sized,
+{
+    to_slice_compact_with_config(value, buf, Config::default())
+}
+
+/// Serialize to a fixed buffer of u32 words using the given configuration
+///
+/// Returns the number of words written. Without the `alloc` feature,
+/// [Config::field_sizes] and [Config::intern_strings] are not supported.
+pub fn to_slice_compact_with_config<T>(value: &T, buf: &mut [u32], config: Config) -> Result<usize>
+where
+    T: serde::Serialize + ?Sized,
+{
+    #[cfg(feature = "tracing")]
+    let span = trace::serialize_span(value).entered();
+    let mut writer = SliceWriter::new(buf);
+    let mut serializer = Serializer::with_config(&mut writer, config);
+    value.serialize(&mut serializer)?;
+    serializer.finish()?;
+    #[cfg(feature = "tracing")]
+    span.record("words", writer.position);
+    Ok(writer.position)
+}
+
+/// A WordWrite over a fixed buffer
+///
+/// Writing past the end of the buffer fails with
+/// [SerializeBufferFull](risc0_zkvm::serde::Error::SerializeBufferFull).
+pub struct SliceWriter<'a> {
+    buf: &'a mut [u32],
+    position: usize,
+}
+
+impl<'a> SliceWriter<'a> {
+    /// Construct a SliceWriter that writes to `buf`
+    pub fn new(buf: &'a mut [u32]) -> Self {
+        SliceWriter { buf, position: 0 }
+    }
+
+    /// The number of words written so far
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    fn reserve(&mut self, len: usize) -> risc0_zkvm::serde::Result<&mut [u32]> {
+        let out = self
+            .buf
+            .get_mut(self.position..self.position + len)
+            .ok_or(risc0_zkvm::serde::Error::SerializeBufferFull)?;
+        self.position += len;
+        Ok(out)
+    }
+}
+
+impl WordWrite for SliceWriter<'_> {
+    fn write_words(&mut self, words: &[u32]) -> risc0_zkvm::serde::Result<()> {
+        self.reserve(words.len())?.copy_from_slice(words);
+        Ok(())
+    }
+
+    fn write_padded_bytes(&mut self, bytes: &[u8]) -> risc0_zkvm::serde::Result<()> {
+        let out = self.reserve(align_up(bytes.len(), WORD_SIZE) / WORD_SIZE)?;
+        for (word, chunk) in out.iter_mut().zip(bytes.chunks(WORD_SIZE)) {
+            let mut word_bytes = [0u8; WORD_SIZE];
+            word_bytes[..chunk.len()].copy_from_slice(chunk);
+            *word = u32::from_le_bytes(word_bytes);
+        }
+        Ok(())
+    }
+}
+
+/// A WordWrite that writes everything to two streams
+///
+/// This lets a single serialization pass feed both, for example, the guest
+/// journal and a hasher or log. Each write goes to the first stream and then to
+/// the second, and fails without reaching the second stream if the first one
+/// fails.
+/// ```rust
+/// use l2r0_small_serde::{Serializer, TeeWriter};
+///
+/// let (mut journal, mut log) = (Vec::new(), Vec::new());
+/// let mut serializer = Serializer::new(TeeWriter::new(&mut journal, &mut log));
+/// serde::Serialize::serialize(&(1u32, "abc"), &mut serializer).unwrap();
+/// assert_eq!(journal, log);
+/// ```
+pub struct TeeWriter<A: WordWrite, B: WordWrite> {
+    first: A,
+    second: B,
+}
+
+impl<A: WordWrite, B: WordWrite> TeeWriter<A, B> {
+    /// Construct a TeeWriter that writes to `first` and `second`
+    pub fn new(first: A, second: B) -> Self {
+        TeeWriter { first, second }
+    }
+
+    /// Get back the two streams
+    pub fn into_inner(self) -> (A, B) {
+        (self.first, self.second)
+    }
+}
+
+impl<A: WordWrite, B: WordWrite> WordWrite for TeeWriter<A, B> {
+    fn write_words(&mut self, words: &[u32]) -> risc0_zkvm::serde::Result<()> {
+        self.first.write_words(words)?;
+        self.second.write_words(words)
+    }
+
+    fn write_padded_bytes(&mut self, bytes: &[u8]) -> risc0_zkvm::serde::Result<()> {
+        self.first.write_padded_bytes(bytes)?;
+        self.second.write_padded_bytes(bytes)
+    }
+}
+
 #[derive(Default)]
 struct ByteHandler {
     pub status: u8,
-    pub depth: u8,
+    pub depth: u16,
     pub byte_holder: u32,
+    /// Whether bytes pack at depth 0 as well, as with [Config::pack_top_level]
+    pub pack_top_level: bool,
+    /// Whether bytes pack from the most significant byte, as with
+    /// [Config::big_endian]
+    pub big_endian: bool,
+    /// Whether to note if the next write packs into the current word
+    pub watching: bool,
+    /// Whether the first write since watching started packed into the word
+    pub joined: bool,
 }
 
 impl ByteHandler {
     #[inline]
     fn increase_depth(&mut self) -> Result<()> {
-        self.depth += 1;
+        self.depth = self
+            .depth
+            .checked_add(1)
+            .filter(|&depth| depth <= MAX_DEPTH)
+            .ok_or(Error::SerializeTooDeep)?;
         Ok(())
     }
 
     #[inline]
     fn decrease_depth<W: WordWrite>(&mut self, stream: &mut W) -> Result<()> {
         self.depth -= 1;
-        if self.depth == 0 && self.status != 0 {
-            stream.write_words(&[self.byte_holder])?;
+        if self.depth == 0 && !self.pack_top_level && self.status != 0 {
+            stream.write_words(&[self.ordered(self.byte_holder)])?;
             self.status = 0;
         }
         Ok(())
@@ -70,17 +309,22 @@ impl ByteHandler {
 
     #[inline(always)]
     fn reset<W: WordWrite>(&mut self, stream: &mut W) -> Result<()> {
+        self.watching = false;
         if self.status != 0 {
-            stream.write_words(&[self.byte_holder])?;
+            stream.write_words(&[self.ordered(self.byte_holder)])?;
         }
         self.status = 0;
         Ok(())
     }
 
     fn handle<W: WordWrite>(&mut self, stream: &mut W, v: u8) -> Result<()> {
-        if self.depth == 0 {
-            stream.write_words(&[v as u32])?;
+        if self.depth == 0 && !self.pack_top_level {
+            stream.write_words(&[self.ordered(v as u32)])?;
         } else {
+            if self.watching {
+                self.watching = false;
+                self.joined = self.status != 0;
+            }
             if self.status == 0 {
                 self.byte_holder = v as u32;
                 self.status = 1;
@@ -88,18 +332,141 @@ impl ByteHandler {
                 self.byte_holder |= (v as u32) << (self.status as usize * 8);
                 self.status = (self.status + 1) % 4;
                 if self.status == 0 {
-                    stream.write_words(&[self.byte_holder])?;
+                    stream.write_words(&[self.ordered(self.byte_holder)])?;
                 }
             }
         }
         Ok(())
     }
+
+    /// A word of packed bytes, held least significant byte first, in the
+    /// configured byte order
+    #[inline(always)]
+    fn ordered(&self, word: u32) -> u32 {
+        match self.big_endian {
+            true => word.swap_bytes(),
+            false => word,
+        }
+    }
+
+    /// Handle a run of bytes, as [ByteHandler::handle] would one at a time
+    ///
+    /// Only the bytes that complete the partially filled word and those left
+    /// over at the end go through `handle`. The whole words in between are
+    /// written at once.
+    fn handle_bytes<W: WordWrite>(&mut self, stream: &mut W, bytes: &[u8]) -> Result<()> {
+        if self.depth == 0 && !self.pack_top_level {
+            for &v in bytes {
+                self.handle(stream, v)?;
+            }
+            return Ok(());
+        }
+        let head = ((WORD_SIZE - self.status as usize) % WORD_SIZE).min(bytes.len());
+        let (head, rest) = bytes.split_at(head);
+        for &v in head {
+            self.handle(stream, v)?;
+        }
+        if rest.is_empty() {
+            return Ok(());
+        }
+        if self.watching {
+            self.watching = false;
+            self.joined = false;
+        }
+        let (words, tail) = rest.split_at(rest.len() - rest.len() % WORD_SIZE);
+        write_padded_bytes(stream, words, self.big_endian)?;
+        for &v in tail {
+            self.handle(stream, v)?;
+        }
+        Ok(())
+    }
 }
 
+/// Write `bytes` padded to whole words, packed from the most significant byte
+/// of each word if `big_endian`
+fn write_padded_bytes<W: WordWrite>(
+    stream: &mut W,
+    bytes: &[u8],
+    big_endian: bool,
+) -> risc0_zkvm::serde::Result<()> {
+    if !big_endian {
+        return stream.write_padded_bytes(bytes);
+    }
+    let mut buffer = [0u32; 64];
+    for chunk in bytes.chunks(buffer.len() * WORD_SIZE) {
+        let len = chunk.len().div_ceil(WORD_SIZE);
+        for (word, bytes) in buffer.iter_mut().zip(chunk.chunks(WORD_SIZE)) {
+            let mut word_bytes = [0u8; WORD_SIZE];
+            word_bytes[..bytes.len()].copy_from_slice(bytes);
+            *word = u32::from_be_bytes(word_bytes);
+        }
+        stream.write_words(&buffer[..len])?;
+    }
+    Ok(())
+}
+
+/// Output of a Serializer
+///
+/// Writes go to the innermost pending buffer if there is one, and to the
+/// underlying stream otherwise. Buffers hold values whose size has to be known
+/// before they can be written, such as sized struct fields.
+struct Sink<W: WordWrite> {
+    stream: W,
+    #[cfg(feature = "alloc")]
+    buffers: Vec<Vec<u32>>,
+    /// Number of words written to the underlying stream
+    position: usize,
+}
+
+impl<W: WordWrite> WordWrite for Sink<W> {
+    fn write_words(&mut self, words: &[u32]) -> risc0_zkvm::serde::Result<()> {
+        #[cfg(feature = "alloc")]
+        if let Some(buffer) = self.buffers.last_mut() {
+            return buffer.write_words(words);
+        }
+        self.stream.write_words(words)?;
+        self.position += words.len();
+        Ok(())
+    }
+
+    fn write_padded_bytes(&mut self, bytes: &[u8]) -> risc0_zkvm::serde::Result<()> {
+        #[cfg(feature = "alloc")]
+        if let Some(buffer) = self.buffers.last_mut() {
+            return buffer.write_padded_bytes(bytes);
+        }
+        self.stream.write_padded_bytes(bytes)?;
+        self.position += align_up(bytes.len(), WORD_SIZE) / WORD_SIZE;
+        Ok(())
+    }
+}
+
+/// A partially filled word of packed bytes
+#[derive(Clone, Copy, Default)]
+pub(crate) struct PendingBytes {
+    status: u8,
+    byte_holder: u32,
+}
+
+/// Called with the name of every top-level struct field before it is written,
+/// and with `None` once the top-level struct has been written
+pub(crate) type FieldHook<W> = fn(&mut Serializer<W>, Option<&'static str>) -> Result<()>;
+
 /// Enables serializing to a stream
 pub struct Serializer<W: WordWrite> {
-    stream: W,
+    stream: Sink<W>,
     byte_handler: ByteHandler,
+    config: Config,
+    /// Index of every string written out in full when interning
+    #[cfg(feature = "alloc")]
+    strings: BTreeMap<String, u32>,
+    #[cfg(feature = "alloc")]
+    costs: Option<Recorder>,
+    #[cfg(feature = "alloc")]
+    manifest: Option<Manifest>,
+    stats: Option<StatsRecorder>,
+    field_hook: Option<FieldHook<W>>,
+    #[cfg(feature = "tracing")]
+    spans: StructSpans,
 }
 
 impl<W: WordWrite> Serializer<W> {
@@ -107,14 +474,540 @@ impl<W: WordWrite> Serializer<W> {
     ///
     /// Creates a serializer that writes to `stream`.
     pub fn new(stream: W) -> Self {
+        Self::with_config(stream, Config::default())
+    }
+
+    /// Construct a Serializer with a configuration
+    ///
+    /// Creates a serializer that writes to `stream` using `config`.
+    pub fn with_config(stream: W, config: Config) -> Self {
         Serializer {
-            stream,
-            byte_handler: ByteHandler::default(),
+            stream: Sink {
+                stream,
+                #[cfg(feature = "alloc")]
+                buffers: Vec::new(),
+                position: 0,
+            },
+            byte_handler: ByteHandler {
+                pack_top_level: config.pack_top_level,
+                big_endian: config.big_endian,
+                ..ByteHandler::default()
+            },
+            config,
+            #[cfg(feature = "alloc")]
+            strings: BTreeMap::new(),
+            #[cfg(feature = "alloc")]
+            costs: None,
+            #[cfg(feature = "alloc")]
+            manifest: None,
+            stats: None,
+            field_hook: None,
+            #[cfg(feature = "tracing")]
+            spans: StructSpans::default(),
+        }
+    }
+
+    /// Record the words contributed by every struct field from now on
+    ///
+    /// The costs are reported by [Serializer::field_costs].
+    #[cfg(feature = "alloc")]
+    pub fn record_field_costs(&mut self) {
+        self.costs.get_or_insert_with(Recorder::default);
+    }
+
+    /// The words contributed by the struct fields serialized so far
+    ///
+    /// Returns `None` unless [Serializer::record_field_costs] was called.
+    #[cfg(feature = "alloc")]
+    pub fn field_costs(&self) -> Option<&FieldCosts> {
+        self.costs.as_ref().map(Recorder::costs)
+    }
+
+    /// Record where each top-level struct field lies in the output from now on
+    ///
+    /// The regions are reported by [Serializer::manifest].
+    #[cfg(feature = "alloc")]
+    pub fn record_manifest(&mut self) {
+        self.manifest.get_or_insert_with(Manifest::default);
+    }
+
+    /// The regions of the top-level struct fields serialized so far
+    ///
+    /// Returns `None` unless [Serializer::record_manifest] was called.
+    #[cfg(feature = "alloc")]
+    pub fn manifest(&self) -> Option<&Manifest> {
+        self.manifest.as_ref()
+    }
+
+    /// Record how the bytes of the output are spent from now on
+    ///
+    /// The statistics are reported by [Serializer::encoding_stats]. Sequences
+    /// are then written element by element, which produces the same output
+    /// more slowly.
+    pub fn record_encoding_stats(&mut self) {
+        if self.stats.is_none() {
+            self.stats = Some(StatsRecorder::new(self.words_emitted()));
+        }
+    }
+
+    /// How the bytes of the output so far are spent
+    ///
+    /// Returns `None` unless [Serializer::record_encoding_stats] was called.
+    /// Call [Serializer::finish] first to count the padding it adds.
+    pub fn encoding_stats(&self) -> Option<EncodingStats> {
+        let end = self.words_emitted();
+        self.stats.as_ref().map(|stats| stats.stats(end))
+    }
+
+    /// Count `bytes` of output towards `usage`
+    #[inline(always)]
+    fn note(&mut self, usage: Usage, bytes: usize) {
+        if let Some(stats) = &mut self.stats {
+            stats.add(usage, bytes);
+        }
+    }
+
+    /// Count a byte about to be handled by the ByteHandler towards `usage`
+    #[inline(always)]
+    fn note_byte(&mut self, usage: Usage) {
+        if let Some(stats) = &mut self.stats {
+            stats.add(usage, 1);
+            if self.byte_handler.depth == 0 && !self.byte_handler.pack_top_level {
+                stats.add(Usage::Widening, WORD_SIZE - 1);
+            }
+        }
+    }
+
+    /// The underlying stream
+    #[cfg(feature = "alloc")]
+    pub(crate) fn stream_mut(&mut self) -> &mut W {
+        &mut self.stream.stream
+    }
+
+    /// Number of words of output so far, including buffered words and a
+    /// partially filled word of bytes
+    fn words_emitted(&self) -> usize {
+        #[cfg(feature = "alloc")]
+        let buffered: usize = self.stream.buffers.iter().map(Vec::len).sum();
+        #[cfg(not(feature = "alloc"))]
+        let buffered = 0;
+        self.stream.position + buffered + (self.byte_handler.status != 0) as usize
+    }
+
+    /// Pad the output as configured with [Config::pad_to]
+    ///
+    /// Call this once after the last value has been serialized.
+    pub fn finish(&mut self) -> Result<()> {
+        self.byte_handler.reset(&mut self.stream)?;
+        if self.config.pad_to > 1 {
+            let rem = self.stream.position % self.config.pad_to;
+            if rem != 0 {
+                for _ in rem..self.config.pad_to {
+                    self.stream.write_words(&[0])?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Serialize `elements` as part of an enclosing sequence
+    ///
+    /// Returns whether the output ends in a word only partially filled with
+    /// packed bytes, which the elements following in the sequence would have
+    /// shared.
+    #[cfg(feature = "rayon")]
+    pub(crate) fn serialize_elements<T: serde::Serialize>(
+        &mut self,
+        elements: &[T],
+    ) -> Result<bool> {
+        self.byte_handler.increase_depth()?;
+        for element in elements {
+            element.serialize(&mut *self)?;
+        }
+        let partial = self.byte_handler.status != 0;
+        self.byte_handler.decrease_depth(&mut self.stream)?;
+        Ok(partial)
+    }
+
+    fn write_word(&mut self, v: u32) -> Result<()> {
+        self.byte_handler.reset(&mut self.stream)?;
+        Ok(self.stream.write_words(&[v])?)
+    }
+
+    /// Serialize the items of `iter` as a sequence of `len` elements
+    ///
+    /// Unlike serializing a collection, this doesn't need the items to be
+    /// collected first, and the iterator doesn't need to know its length. Fails
+    /// with [Error::SerializeLengthMismatch] if `iter` doesn't yield exactly
+    /// `len` items, in which case the output is incomplete. Items past `len`
+    /// aren't counted: `found` is then `len + 1`.
+    pub fn serialize_iter<I>(&mut self, len: usize, iter: I) -> Result<()>
+    where
+        I: IntoIterator,
+        I::Item: serde::Serialize,
+    {
+        let mut iter = iter.into_iter();
+        let seq = serde::Serializer::serialize_seq(&mut *self, Some(len))?;
+        let found = seq.write_elements(iter.by_ref().take(len))?;
+        // Only one extra item is looked for, as the iterator may be unbounded.
+        let found = found + usize::from(iter.next().is_some());
+        if found != len {
+            return Err(Error::SerializeLengthMismatch {
+                expected: len,
+                found,
+            });
+        }
+        serde::ser::SerializeSeq::end(seq)
+    }
+
+    /// Serialize the items of `iter` as elements of an enclosing sequence
+    ///
+    /// Returns the number of items. Words of scalar items are collected and
+    /// written in bulk, while any other item goes through the serializer after
+    /// the words before it.
+    fn write_elements<I>(&mut self, iter: I) -> Result<usize>
+    where
+        I: Iterator,
+        I::Item: serde::Serialize,
+    {
+        let mut count = 0;
+        // Elements carry their own markers in the self-describing encoding,
+        // and are counted one by one for the statistics.
+        if self.config.self_describing || self.stats.is_some() {
+            for value in iter {
+                serde::Serialize::serialize(&value, &mut *self)?;
+                count += 1;
+            }
+            return Ok(count);
+        }
+        let mut iter = iter.peekable();
+        if let Some(first) = iter.peek() {
+            if serde::Serialize::serialize(first, self.byte_probe()).is_ok() {
+                return self.write_byte_elements(iter);
+            }
+        }
+        let mut buffer = [0u32; 256];
+        let mut filled = 0;
+        for value in iter {
+            match serde::Serialize::serialize(&value, self.word_probe()) {
+                Ok((words, n)) => {
+                    if filled + n > buffer.len() {
+                        self.write_words(&buffer[..filled])?;
+                        filled = 0;
+                    }
+                    buffer[filled..filled + n].copy_from_slice(&words[..n]);
+                    filled += n;
+                }
+                Err(_) => {
+                    self.write_words(&buffer[..filled])?;
+                    filled = 0;
+                    serde::Serialize::serialize(&value, &mut *self)?;
+                }
+            }
+            count += 1;
+        }
+        self.write_words(&buffer[..filled])?;
+        Ok(count)
+    }
+
+    fn word_probe(&self) -> WordProbe {
+        WordProbe {
+            human_readable: self.config.human_readable,
+            big_endian: self.config.big_endian,
+        }
+    }
+
+    fn byte_probe(&self) -> ByteProbe {
+        ByteProbe {
+            human_readable: self.config.human_readable,
+        }
+    }
+
+    /// Write the elements of a sequence that starts with a byte
+    ///
+    /// Bytes are gathered in a buffer and packed a word at a time. Any other
+    /// element is serialized as usual after the bytes before it.
+    fn write_byte_elements<I>(&mut self, mut iter: I) -> Result<usize>
+    where
+        I: Iterator,
+        I::Item: serde::Serialize,
+    {
+        let mut count = 0;
+        let mut buffer = [0u8; 1024];
+        loop {
+            let mut filled = 0;
+            let mut other = None;
+            for (slot, value) in buffer.iter_mut().zip(iter.by_ref()) {
+                match serde::Serialize::serialize(&value, self.byte_probe()) {
+                    Ok(byte) => *slot = byte,
+                    Err(_) => {
+                        other = Some(value);
+                        break;
+                    }
+                }
+                filled += 1;
+            }
+            count += filled;
+            self.byte_handler
+                .handle_bytes(&mut self.stream, &buffer[..filled])?;
+            match other {
+                Some(value) => {
+                    serde::Serialize::serialize(&value, &mut *self)?;
+                    count += 1;
+                }
+                None if filled < buffer.len() => break,
+                None => {}
+            }
+        }
+        Ok(count)
+    }
+
+    /// Write a run of whole words
+    fn write_words(&mut self, words: &[u32]) -> Result<()> {
+        if words.is_empty() {
+            return Ok(());
+        }
+        self.byte_handler.reset(&mut self.stream)?;
+        Ok(self.stream.write_words(words)?)
+    }
+
+    fn write_dword(&mut self, v: u64) -> Result<()> {
+        let (low, high) = ((v & 0xFFFFFFFF) as u32, ((v >> 32) & 0xFFFFFFFF) as u32);
+        match self.config.big_endian {
+            true => self.write_words(&[high, low]),
+            false => self.write_words(&[low, high]),
+        }
+    }
+
+    /// Write the four words of a 128-bit integer
+    fn write_qword(&mut self, v: u128) -> Result<()> {
+        self.byte_handler.reset(&mut self.stream)?;
+        let bytes = match self.config.big_endian {
+            true => v.to_be_bytes(),
+            false => v.to_le_bytes(),
+        };
+        Ok(write_padded_bytes(
+            &mut self.stream,
+            &bytes,
+            self.config.big_endian,
+        )?)
+    }
+
+    /// Write a length prefix
+    fn write_len(&mut self, len: usize) -> Result<()> {
+        let len =
+            u32::try_from(len).map_err(|_| Error::SerializeLengthOverflow { requested: len })?;
+        self.note(Usage::Length, WORD_SIZE);
+        self.write_word(len)
+    }
+
+    fn write_bytes(&mut self, v: &[u8]) -> Result<()> {
+        self.write_len(v.len())?;
+        self.note(Usage::Data, v.len());
+        Ok(write_padded_bytes(
+            &mut self.stream,
+            v,
+            self.config.big_endian,
+        )?)
+    }
+
+    /// Write the tag of an `Option`, which a present value follows
+    pub(crate) fn write_option_tag(&mut self, some: bool) -> Result<()> {
+        self.write_marker(marker::OPTION)?;
+        match self.config.byte_option_tags {
+            true => {
+                self.note_byte(Usage::Tag);
+                self.byte_handler.handle(&mut self.stream, some as u8)
+            }
+            false => {
+                self.note(Usage::Tag, WORD_SIZE);
+                self.write_word(some as u32)
+            }
+        }
+    }
+
+    /// Write a present `Option` holding the value written by `write`
+    pub(crate) fn write_some<F>(&mut self, write: F) -> Result<()>
+    where
+        F: FnOnce(&mut Self) -> Result<()>,
+    {
+        self.write_tagged(self.config.byte_option_tags, |serializer| {
+            serializer.write_option_tag(true)?;
+            write(serializer)
+        })
+    }
+
+    /// Write a tag and the value following it with `write`
+    ///
+    /// A byte tag forms a composite with the value, so that it packs with the
+    /// value as the deserializer expects, even at the top level.
+    fn write_tagged<F>(&mut self, byte_tag: bool, write: F) -> Result<()>
+    where
+        F: FnOnce(&mut Self) -> Result<()>,
+    {
+        if !byte_tag {
+            return write(self);
+        }
+        self.byte_handler.increase_depth()?;
+        write(self)?;
+        self.byte_handler.decrease_depth(&mut self.stream)
+    }
+
+    /// Write the marker of a node when the encoding is self-describing
+    fn write_marker(&mut self, marker: u32) -> Result<()> {
+        if self.config.self_describing {
+            self.note(Usage::Marker, WORD_SIZE);
+            self.write_word(marker)?;
+        }
+        Ok(())
+    }
+
+    /// Write the marker of a tuple or struct node with `len` elements, whose
+    /// length only has to fit the marker in the self-describing encoding
+    fn write_len_marker(&mut self, kind: u32, len: usize) -> Result<()> {
+        match self.config.self_describing {
+            true => self.write_marker(marker::with_len(kind, len)?),
+            false => Ok(()),
+        }
+    }
+
+    /// Call `hook` around the top-level struct fields from now on
+    pub(crate) fn set_field_hook(&mut self, hook: FieldHook<W>) {
+        self.field_hook = Some(hook);
+    }
+
+    /// The configuration the output is encoded with
+    pub(crate) fn config(&self) -> Config {
+        self.config
+    }
+
+    /// The underlying stream
+    pub(crate) fn writer_mut(&mut self) -> &mut W {
+        &mut self.stream.stream
+    }
+
+    /// Exchange the partially filled word of bytes with `pending`, so that
+    /// bytes written from now on pack with those of `pending`
+    pub(crate) fn swap_pending(&mut self, pending: &mut PendingBytes) {
+        core::mem::swap(&mut self.byte_handler.status, &mut pending.status);
+        core::mem::swap(&mut self.byte_handler.byte_holder, &mut pending.byte_holder);
+    }
+
+    /// Write the partially filled word of `pending`, if it holds any bytes
+    pub(crate) fn write_pending(&mut self, pending: PendingBytes) -> Result<()> {
+        if pending.status != 0 {
+            let word = self.byte_handler.ordered(pending.byte_holder);
+            self.stream.write_words(&[word])?;
+        }
+        Ok(())
+    }
+
+    /// Call the field hook at the end of a struct, if it was a top-level one
+    fn end_struct(&mut self) -> Result<()> {
+        match self.field_hook {
+            Some(hook) if self.byte_handler.depth == 0 => hook(self, None),
+            _ => Ok(()),
+        }
+    }
+
+    /// Write a struct field written by `write`
+    pub(crate) fn serialize_struct_field<F>(&mut self, key: &'static str, write: F) -> Result<()>
+    where
+        F: FnOnce(&mut Self) -> Result<()>,
+    {
+        if let (Some(hook), 1) = (self.field_hook, self.byte_handler.depth) {
+            hook(self, Some(key))?;
+        }
+        #[cfg(feature = "alloc")]
+        if self.costs.is_some() || self.manifest.is_some() {
+            return self.record_struct_field(key, write);
+        }
+        #[cfg(not(feature = "alloc"))]
+        let _ = key;
+        self.write_struct_field(write)
+    }
+
+    /// Write a struct field, recording its cost and region as enabled
+    #[cfg(feature = "alloc")]
+    fn record_struct_field<F>(&mut self, key: &'static str, write: F) -> Result<()>
+    where
+        F: FnOnce(&mut Self) -> Result<()>,
+    {
+        let record_region = self.manifest.is_some() && self.byte_handler.depth == 1;
+        let start = self.words_emitted();
+        let partial = self.byte_handler.status != 0;
+        if record_region {
+            // Note whether the field starts in the partially filled word of
+            // bytes before it.
+            self.byte_handler.watching = true;
+            self.byte_handler.joined = false;
+        }
+        if let Some(costs) = &mut self.costs {
+            costs.enter(key);
+        }
+        let res = self.write_struct_field(write);
+        let end = self.words_emitted();
+        if let Some(costs) = &mut self.costs {
+            costs.exit(end - start);
+        }
+        if let (true, Some(manifest)) = (record_region, &mut self.manifest) {
+            // A partially filled word at the start belongs to the field only
+            // if the field packed into it.
+            let offset = start - (partial && self.byte_handler.joined) as usize;
+            self.byte_handler.watching = false;
+            manifest.push(FieldRegion {
+                name: key,
+                offset,
+                len: end - offset,
+            });
+        }
+        res
+    }
+
+    fn write_struct_field<F>(&mut self, write: F) -> Result<()>
+    where
+        F: FnOnce(&mut Self) -> Result<()>,
+    {
+        if !self.config.field_sizes {
+            return write(self);
+        }
+        #[cfg(not(feature = "alloc"))]
+        return Err(Error::NotSupported);
+        // Sized fields start and end on a word boundary so that they can be
+        // skipped as a whole.
+        #[cfg(feature = "alloc")]
+        {
+            self.byte_handler.reset(&mut self.stream)?;
+            self.stream.buffers.push(Vec::new());
+            let res = write(&mut *self).and_then(|_| self.byte_handler.reset(&mut self.stream));
+            let buffer = self.stream.buffers.pop().unwrap();
+            res?;
+            self.write_len(buffer.len())?;
+            Ok(self.stream.write_words(&buffer)?)
+        }
+    }
+
+    fn serialize_variant_tag(&mut self, variant_index: u32, variant: &'static str) -> Result<()> {
+        self.write_marker(marker::VARIANT)?;
+        match self.config.variant_tag {
+            VariantTag::Word => {
+                self.note(Usage::Tag, WORD_SIZE);
+                self.write_word(variant_index)
+            }
+            VariantTag::Byte => {
+                let tag = u8::try_from(variant_index).map_err(|_| Error::NotSupported)?;
+                self.note_byte(Usage::Tag);
+                self.byte_handler.handle(&mut self.stream, tag)
+            }
+            VariantTag::NameHash => {
+                self.note(Usage::Tag, WORD_SIZE);
+                self.write_word(variant_name_hash(variant))
+            }
         }
     }
 }
 
-impl<'a, W: WordWrite> serde::ser::Serializer for &'a mut Serializer<W> {
+impl<W: WordWrite> serde::ser::Serializer for &mut Serializer<W> {
     type Ok = ();
     type Error = Error;
     type SerializeSeq = Self;
@@ -125,90 +1018,140 @@ impl<'a, W: WordWrite> serde::ser::Serializer for &'a mut Serializer<W> {
     type SerializeStruct = Self;
     type SerializeStructVariant = Self;
     fn is_human_readable(&self) -> bool {
-        false
+        self.config.human_readable
     }
+    #[cfg(feature = "alloc")]
+    fn collect_str<T>(self, value: &T) -> Result<()>
+    where
+        T: core::fmt::Display + ?Sized,
+    {
+        use alloc::string::ToString;
+        self.serialize_str(&value.to_string())
+    }
+    #[cfg(not(feature = "alloc"))]
     fn collect_str<T>(self, _: &T) -> Result<()>
     where
         T: core::fmt::Display + ?Sized,
     {
-        panic!("collect_str")
+        Err(Error::NotSupported)
+    }
+    fn collect_seq<I>(self, iter: I) -> Result<()>
+    where
+        I: IntoIterator,
+        I::Item: serde::Serialize,
+    {
+        let iter = iter.into_iter();
+        let len = match iter.size_hint() {
+            (lo, Some(hi)) if lo == hi => Some(lo),
+            _ => None,
+        };
+        let seq = self.serialize_seq(len)?;
+        seq.write_elements(iter)?;
+        serde::ser::SerializeSeq::end(seq)
     }
     fn serialize_bool(self, v: bool) -> Result<()> {
-        self.serialize_u8(if v { 1 } else { 0 })
+        self.write_marker(marker::BOOL)?;
+        self.note_byte(Usage::Data);
+        self.byte_handler.handle(&mut self.stream, v as u8)
     }
     fn serialize_i8(self, v: i8) -> Result<()> {
-        self.serialize_i32(v as i32)
+        self.write_marker(marker::I8)?;
+        self.note(Usage::Data, 1);
+        self.note(Usage::Widening, 3);
+        self.write_word(v as i32 as u32)
     }
     fn serialize_i16(self, v: i16) -> Result<()> {
-        self.serialize_i32(v as i32)
+        self.write_marker(marker::I16)?;
+        self.note(Usage::Data, 2);
+        self.note(Usage::Widening, 2);
+        self.write_word(v as i32 as u32)
     }
     fn serialize_i32(self, v: i32) -> Result<()> {
-        self.serialize_u32(v as u32)
+        self.write_marker(marker::I32)?;
+        self.note(Usage::Data, 4);
+        self.write_word(v as u32)
     }
     fn serialize_i64(self, v: i64) -> Result<()> {
-        self.serialize_u64(v as u64)
+        self.write_marker(marker::I64)?;
+        self.note(Usage::Data, 8);
+        self.write_dword(v as u64)
     }
     fn serialize_i128(self, v: i128) -> Result<()> {
-        self.serialize_u128(v as u128)
+        self.write_marker(marker::I128)?;
+        self.note(Usage::Data, 16);
+        self.write_qword(v as u128)
     }
 
     fn serialize_u8(self, v: u8) -> Result<()> {
+        self.write_marker(marker::U8)?;
+        self.note_byte(Usage::Data);
         self.byte_handler.handle(&mut self.stream, v)
     }
 
     fn serialize_u16(self, v: u16) -> Result<()> {
-        self.serialize_u32(v as u32)
+        self.write_marker(marker::U16)?;
+        self.note(Usage::Data, 2);
+        self.note(Usage::Widening, 2);
+        self.write_word(v as u32)
     }
 
     fn serialize_u32(self, v: u32) -> Result<()> {
-        self.byte_handler.reset(&mut self.stream)?;
-        let res = self.stream.write_words(&[v]);
-
-        if res.is_err() {
-            return Err(Error::from(res.unwrap_err()));
-        } else {
-            return Ok(res.unwrap());
-        }
+        self.write_marker(marker::U32)?;
+        self.note(Usage::Data, 4);
+        self.write_word(v)
     }
 
     fn serialize_u64(self, v: u64) -> Result<()> {
-        self.serialize_u32((v & 0xFFFFFFFF) as u32)?;
-        self.serialize_u32(((v >> 32) & 0xFFFFFFFF) as u32)
+        self.write_marker(marker::U64)?;
+        self.note(Usage::Data, 8);
+        self.write_dword(v)
     }
 
     fn serialize_u128(self, v: u128) -> Result<()> {
-        self.byte_handler.reset(&mut self.stream)?;
-        let res = self.stream.write_padded_bytes(&v.to_le_bytes());
-
-        if res.is_err() {
-            return Err(Error::from(res.unwrap_err()));
-        } else {
-            return Ok(res.unwrap());
-        }
+        self.write_marker(marker::U128)?;
+        self.note(Usage::Data, 16);
+        self.write_qword(v)
     }
 
     fn serialize_f32(self, v: f32) -> Result<()> {
-        self.serialize_u32(v.to_bits())
+        self.write_marker(marker::F32)?;
+        self.note(Usage::Data, 4);
+        self.write_word(v.to_bits())
     }
 
     fn serialize_f64(self, v: f64) -> Result<()> {
-        self.serialize_u64(f64::to_bits(v))
+        self.write_marker(marker::F64)?;
+        self.note(Usage::Data, 8);
+        self.write_dword(f64::to_bits(v))
     }
 
     fn serialize_char(self, v: char) -> Result<()> {
-        self.serialize_u32(v as u32)
+        self.write_marker(marker::CHAR)?;
+        self.note(Usage::Data, 4);
+        self.write_word(v as u32)
     }
 
     fn serialize_str(self, v: &str) -> Result<()> {
-        let bytes = v.as_bytes();
-        self.serialize_u32(bytes.len() as u32)?;
-        let res = self.stream.write_padded_bytes(bytes);
-
-        if res.is_err() {
-            return Err(Error::from(res.unwrap_err()));
-        } else {
-            return Ok(res.unwrap());
+        self.write_marker(marker::STR)?;
+        if self.config.intern_strings {
+            #[cfg(not(feature = "alloc"))]
+            return Err(Error::NotSupported);
+            #[cfg(feature = "alloc")]
+            if let Some(&index) = self.strings.get(v) {
+                self.note(Usage::Tag, WORD_SIZE);
+                return self.write_word(index + 1);
+            }
+            #[cfg(feature = "alloc")]
+            {
+                let len = self.strings.len();
+                let index = u32::try_from(len)
+                    .map_err(|_| Error::SerializeLengthOverflow { requested: len })?;
+                self.strings.insert(v.into(), index);
+                self.note(Usage::Tag, WORD_SIZE);
+                self.write_word(0)?;
+            }
         }
+        self.write_bytes(v.as_bytes())
     }
 
     // NOTE: Serializing byte slices _does not_ currently call serialize_bytes. This
@@ -220,44 +1163,41 @@ impl<'a, W: WordWrite> serde::ser::Serializer for &'a mut Serializer<W> {
     //    serialized as bytes.
     // B) Use the experimental Rust specialization
     //    features.
+    // collect_seq now takes a variant of A) for slices of word-sized scalars,
+    // whose words are written in bulk, and for slices of bytes, which are
+    // packed a word at a time.
     fn serialize_bytes(self, v: &[u8]) -> Result<()> {
-        self.serialize_u32(v.len() as u32)?;
-        let res = self.stream.write_padded_bytes(v);
-
-        if res.is_err() {
-            return Err(Error::from(res.unwrap_err()));
-        } else {
-            return Ok(res.unwrap());
-        }
+        self.write_marker(marker::BYTES)?;
+        self.write_bytes(v)
     }
 
     fn serialize_none(self) -> Result<()> {
-        self.serialize_u32(0)
+        self.write_option_tag(false)
     }
 
     fn serialize_some<T>(self, value: &T) -> Result<()>
     where
         T: serde::Serialize + ?Sized,
     {
-        self.serialize_u32(1)?;
-        value.serialize(self)
+        self.write_some(|serializer| value.serialize(serializer))
     }
 
     fn serialize_unit(self) -> Result<()> {
-        Ok(())
+        self.write_marker(marker::UNIT)
     }
 
     fn serialize_unit_struct(self, _name: &'static str) -> Result<()> {
-        Ok(())
+        self.write_marker(marker::UNIT)
     }
 
     fn serialize_unit_variant(
         self,
         _name: &'static str,
         variant_index: u32,
-        _variant: &'static str,
+        variant: &'static str,
     ) -> Result<()> {
-        self.serialize_u32(variant_index)
+        self.serialize_variant_tag(variant_index, variant)?;
+        self.write_marker(marker::UNIT)
     }
 
     fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<()>
@@ -271,37 +1211,43 @@ impl<'a, W: WordWrite> serde::ser::Serializer for &'a mut Serializer<W> {
         self,
         _name: &'static str,
         variant_index: u32,
-        _variant: &'static str,
+        variant: &'static str,
         value: &T,
     ) -> Result<()>
     where
         T: serde::Serialize + ?Sized,
     {
-        self.serialize_u32(variant_index)?;
-        value.serialize(self)
+        let byte_tag = self.config.variant_tag == VariantTag::Byte;
+        self.write_tagged(byte_tag, |serializer| {
+            serializer.serialize_variant_tag(variant_index, variant)?;
+            value.serialize(serializer)
+        })
     }
     fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq> {
         match len {
             Some(val) => {
                 self.byte_handler.increase_depth()?;
-                self.serialize_u32(val.try_into().unwrap())?;
+                self.write_marker(marker::SEQ)?;
+                self.write_len(val)?;
                 Ok(self)
             }
             None => Err(Error::NotSupported),
         }
     }
 
-    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple> {
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple> {
         self.byte_handler.increase_depth()?;
+        self.write_len_marker(marker::TUPLE, len)?;
         Ok(self)
     }
 
     fn serialize_tuple_struct(
         self,
         _name: &'static str,
-        _len: usize,
+        len: usize,
     ) -> Result<Self::SerializeTupleStruct> {
         self.byte_handler.increase_depth()?;
+        self.write_len_marker(marker::TUPLE, len)?;
         Ok(self)
     }
 
@@ -309,11 +1255,12 @@ impl<'a, W: WordWrite> serde::ser::Serializer for &'a mut Serializer<W> {
         self,
         _name: &'static str,
         variant_index: u32,
-        _variant: &'static str,
-        _len: usize,
+        variant: &'static str,
+        len: usize,
     ) -> Result<Self::SerializeTupleVariant> {
         self.byte_handler.increase_depth()?;
-        self.serialize_u32(variant_index)?;
+        self.serialize_variant_tag(variant_index, variant)?;
+        self.write_len_marker(marker::TUPLE, len)?;
         Ok(self)
     }
 
@@ -321,15 +1268,22 @@ impl<'a, W: WordWrite> serde::ser::Serializer for &'a mut Serializer<W> {
         match len {
             Some(val) => {
                 self.byte_handler.increase_depth()?;
-                self.serialize_u32(val.try_into().unwrap())?;
+                self.write_marker(marker::MAP)?;
+                self.write_len(val)?;
                 Ok(self)
             }
             None => Err(Error::NotSupported),
         }
     }
 
-    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct> {
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<Self::SerializeStruct> {
+        #[cfg(feature = "tracing")]
+        self.spans.enter(_name, len, self.words_emitted());
         self.byte_handler.increase_depth()?;
+        self.write_len_marker(marker::STRUCT, len)?;
+        if self.config.counts_fields() {
+            self.write_len(len)?;
+        }
         Ok(self)
     }
 
@@ -337,16 +1291,20 @@ impl<'a, W: WordWrite> serde::ser::Serializer for &'a mut Serializer<W> {
         self,
         _name: &'static str,
         variant_index: u32,
-        _variant: &'static str,
-        _len: usize,
+        variant: &'static str,
+        len: usize,
     ) -> Result<Self::SerializeStructVariant> {
         self.byte_handler.increase_depth()?;
-        self.serialize_u32(variant_index)?;
+        self.serialize_variant_tag(variant_index, variant)?;
+        self.write_len_marker(marker::STRUCT, len)?;
+        if self.config.counts_fields() {
+            self.write_len(len)?;
+        }
         Ok(self)
     }
 }
 
-impl<'a, W: WordWrite> serde::ser::SerializeSeq for &'a mut Serializer<W> {
+impl<W: WordWrite> serde::ser::SerializeSeq for &mut Serializer<W> {
     type Ok = ();
     type Error = Error;
 
@@ -362,7 +1320,7 @@ impl<'a, W: WordWrite> serde::ser::SerializeSeq for &'a mut Serializer<W> {
     }
 }
 
-impl<'a, W: WordWrite> serde::ser::SerializeTuple for &'a mut Serializer<W> {
+impl<W: WordWrite> serde::ser::SerializeTuple for &mut Serializer<W> {
     type Ok = ();
     type Error = Error;
 
@@ -378,7 +1336,7 @@ impl<'a, W: WordWrite> serde::ser::SerializeTuple for &'a mut Serializer<W> {
     }
 }
 
-impl<'a, W: WordWrite> serde::ser::SerializeTupleStruct for &'a mut Serializer<W> {
+impl<W: WordWrite> serde::ser::SerializeTupleStruct for &mut Serializer<W> {
     type Ok = ();
     type Error = Error;
 
@@ -394,7 +1352,7 @@ impl<'a, W: WordWrite> serde::ser::SerializeTupleStruct for &'a mut Serializer<W
     }
 }
 
-impl<'a, W: WordWrite> serde::ser::SerializeTupleVariant for &'a mut Serializer<W> {
+impl<W: WordWrite> serde::ser::SerializeTupleVariant for &mut Serializer<W> {
     type Ok = ();
     type Error = Error;
 
@@ -410,7 +1368,7 @@ impl<'a, W: WordWrite> serde::ser::SerializeTupleVariant for &'a mut Serializer<
     }
 }
 
-impl<'a, W: WordWrite> serde::ser::SerializeMap for &'a mut Serializer<W> {
+impl<W: WordWrite> serde::ser::SerializeMap for &mut Serializer<W> {
     type Ok = ();
     type Error = Error;
 
@@ -433,47 +1391,280 @@ impl<'a, W: WordWrite> serde::ser::SerializeMap for &'a mut Serializer<W> {
     }
 }
 
-impl<'a, W: WordWrite> serde::ser::SerializeStruct for &'a mut Serializer<W> {
+impl<W: WordWrite> serde::ser::SerializeStruct for &mut Serializer<W> {
     type Ok = ();
     type Error = Error;
 
-    fn serialize_field<T>(&mut self, _key: &'static str, value: &T) -> Result<()>
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
     where
         T: serde::Serialize + ?Sized,
     {
-        value.serialize(&mut **self)
+        self.serialize_struct_field(key, |serializer| value.serialize(serializer))
     }
 
     fn end(self) -> Result<()> {
-        self.byte_handler.decrease_depth(&mut self.stream)
+        self.byte_handler.decrease_depth(&mut self.stream)?;
+        #[cfg(feature = "tracing")]
+        self.spans.exit(self.words_emitted());
+        self.end_struct()
     }
 }
 
-impl<'a, W: WordWrite> serde::ser::SerializeStructVariant for &'a mut Serializer<W> {
+impl<W: WordWrite> serde::ser::SerializeStructVariant for &mut Serializer<W> {
     type Ok = ();
     type Error = Error;
 
-    fn serialize_field<T>(&mut self, _key: &'static str, value: &T) -> Result<()>
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
     where
         T: serde::Serialize + ?Sized,
     {
-        value.serialize(&mut **self)
+        self.serialize_struct_field(key, |serializer| value.serialize(serializer))
     }
 
     fn end(self) -> Result<()> {
-        self.byte_handler.decrease_depth(&mut self.stream)
+        self.byte_handler.decrease_depth(&mut self.stream)?;
+        self.end_struct()
+    }
+}
+
+/// Encoder of the scalars that serialize to whole words
+///
+/// Used by `collect_seq` to batch the words of sequence elements instead of
+/// writing every element through the [Serializer]. Produces the same words the
+/// Serializer would, and fails without side effects on every other value.
+struct WordProbe {
+    human_readable: bool,
+    big_endian: bool,
+}
+
+macro_rules! reject {
+    ($($method:ident($($arg:ty),*) -> $ok:ty;)*) => {
+        $(
+            fn $method(self, $(_: $arg),*) -> Result<$ok> {
+                Err(Error::NotSupported)
+            }
+        )*
+    };
+}
+
+impl serde::ser::Serializer for WordProbe {
+    type Ok = ([u32; 2], usize);
+    type Error = Error;
+    type SerializeSeq = Impossible<Self::Ok, Error>;
+    type SerializeTuple = Impossible<Self::Ok, Error>;
+    type SerializeTupleStruct = Impossible<Self::Ok, Error>;
+    type SerializeTupleVariant = Impossible<Self::Ok, Error>;
+    type SerializeMap = Impossible<Self::Ok, Error>;
+    type SerializeStruct = Impossible<Self::Ok, Error>;
+    type SerializeStructVariant = Impossible<Self::Ok, Error>;
+
+    fn is_human_readable(&self) -> bool {
+        self.human_readable
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Self::Ok> {
+        Ok(([v as i32 as u32, 0], 1))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Self::Ok> {
+        Ok(([v as i32 as u32, 0], 1))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Self::Ok> {
+        Ok(([v as u32, 0], 1))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Self::Ok> {
+        self.serialize_u64(v as u64)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Self::Ok> {
+        Ok(([v as u32, 0], 1))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Self::Ok> {
+        Ok(([v, 0], 1))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Self::Ok> {
+        let (low, high) = (v as u32, (v >> 32) as u32);
+        match self.big_endian {
+            true => Ok(([high, low], 2)),
+            false => Ok(([low, high], 2)),
+        }
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Self::Ok> {
+        Ok(([v.to_bits(), 0], 1))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Self::Ok> {
+        self.serialize_u64(v.to_bits())
+    }
+
+    fn serialize_char(self, v: char) -> Result<Self::Ok> {
+        Ok(([v as u32, 0], 1))
+    }
+
+    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<Self::Ok>
+    where
+        T: serde::Serialize + ?Sized,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_some<T>(self, _value: &T) -> Result<Self::Ok>
+    where
+        T: serde::Serialize + ?Sized,
+    {
+        Err(Error::NotSupported)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<Self::Ok>
+    where
+        T: serde::Serialize + ?Sized,
+    {
+        Err(Error::NotSupported)
+    }
+
+    reject! {
+        serialize_bool(bool) -> Self::Ok;
+        serialize_u8(u8) -> Self::Ok;
+        serialize_i128(i128) -> Self::Ok;
+        serialize_u128(u128) -> Self::Ok;
+        serialize_str(&str) -> Self::Ok;
+        serialize_bytes(&[u8]) -> Self::Ok;
+        serialize_none() -> Self::Ok;
+        serialize_unit() -> Self::Ok;
+        serialize_unit_struct(&'static str) -> Self::Ok;
+        serialize_unit_variant(&'static str, u32, &'static str) -> Self::Ok;
+        serialize_seq(Option<usize>) -> Self::SerializeSeq;
+        serialize_tuple(usize) -> Self::SerializeTuple;
+        serialize_tuple_struct(&'static str, usize) -> Self::SerializeTupleStruct;
+        serialize_tuple_variant(&'static str, u32, &'static str, usize) -> Self::SerializeTupleVariant;
+        serialize_map(Option<usize>) -> Self::SerializeMap;
+        serialize_struct(&'static str, usize) -> Self::SerializeStruct;
+        serialize_struct_variant(&'static str, u32, &'static str, usize) -> Self::SerializeStructVariant;
+    }
+
+    fn collect_str<T>(self, _: &T) -> Result<Self::Ok>
+    where
+        T: core::fmt::Display + ?Sized,
+    {
+        Err(Error::NotSupported)
+    }
+}
+
+/// Encoder of the scalars that serialize to a single packed byte
+///
+/// Used by `collect_seq` to pack sequences of bytes a word at a time. Fails
+/// without side effects on every other value.
+struct ByteProbe {
+    human_readable: bool,
+}
+
+impl serde::ser::Serializer for ByteProbe {
+    type Ok = u8;
+    type Error = Error;
+    type SerializeSeq = Impossible<Self::Ok, Error>;
+    type SerializeTuple = Impossible<Self::Ok, Error>;
+    type SerializeTupleStruct = Impossible<Self::Ok, Error>;
+    type SerializeTupleVariant = Impossible<Self::Ok, Error>;
+    type SerializeMap = Impossible<Self::Ok, Error>;
+    type SerializeStruct = Impossible<Self::Ok, Error>;
+    type SerializeStructVariant = Impossible<Self::Ok, Error>;
+
+    fn is_human_readable(&self) -> bool {
+        self.human_readable
+    }
+
+    fn serialize_bool(self, v: bool) -> Result<Self::Ok> {
+        Ok(v as u8)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Self::Ok> {
+        Ok(v)
+    }
+
+    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<Self::Ok>
+    where
+        T: serde::Serialize + ?Sized,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_some<T>(self, _value: &T) -> Result<Self::Ok>
+    where
+        T: serde::Serialize + ?Sized,
+    {
+        Err(Error::NotSupported)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<Self::Ok>
+    where
+        T: serde::Serialize + ?Sized,
+    {
+        Err(Error::NotSupported)
+    }
+
+    reject! {
+        serialize_i8(i8) -> Self::Ok;
+        serialize_i16(i16) -> Self::Ok;
+        serialize_i32(i32) -> Self::Ok;
+        serialize_i64(i64) -> Self::Ok;
+        serialize_i128(i128) -> Self::Ok;
+        serialize_u16(u16) -> Self::Ok;
+        serialize_u32(u32) -> Self::Ok;
+        serialize_u64(u64) -> Self::Ok;
+        serialize_u128(u128) -> Self::Ok;
+        serialize_f32(f32) -> Self::Ok;
+        serialize_f64(f64) -> Self::Ok;
+        serialize_char(char) -> Self::Ok;
+        serialize_str(&str) -> Self::Ok;
+        serialize_bytes(&[u8]) -> Self::Ok;
+        serialize_none() -> Self::Ok;
+        serialize_unit() -> Self::Ok;
+        serialize_unit_struct(&'static str) -> Self::Ok;
+        serialize_unit_variant(&'static str, u32, &'static str) -> Self::Ok;
+        serialize_seq(Option<usize>) -> Self::SerializeSeq;
+        serialize_tuple(usize) -> Self::SerializeTuple;
+        serialize_tuple_struct(&'static str, usize) -> Self::SerializeTupleStruct;
+        serialize_tuple_variant(&'static str, u32, &'static str, usize) -> Self::SerializeTupleVariant;
+        serialize_map(Option<usize>) -> Self::SerializeMap;
+        serialize_struct(&'static str, usize) -> Self::SerializeStruct;
+        serialize_struct_variant(&'static str, u32, &'static str, usize) -> Self::SerializeStructVariant;
+    }
+
+    fn collect_str<T>(self, _: &T) -> Result<Self::Ok>
+    where
+        T: core::fmt::Display + ?Sized,
+    {
+        Err(Error::NotSupported)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use alloc::string::String;
+    use alloc::{boxed::Box, string::String};
 
     use serde::Serialize;
 
     use super::*;
 
     #[test]
+    #[allow(clippy::approx_constant)]
     fn test_struct() {
         #[derive(Serialize, PartialEq, Debug)]
         struct Test {
@@ -538,4 +1729,249 @@ mod tests {
         };
         assert_eq!(expected, to_vec_compact(&input).unwrap().as_slice());
     }
+
+    #[test]
+    fn test_slice() {
+        let input = (7u32, "abcde", [1u8, 2, 3]);
+        let expected = to_vec_compact(&input).unwrap();
+        let mut buf = [0u32; 8];
+        let len = to_slice_compact(&input, &mut buf).unwrap();
+        assert_eq!(expected.as_slice(), &buf[..len]);
+
+        let config = Config::new().pad_to(8);
+        assert_eq!(
+            8,
+            to_slice_compact_with_config(&input, &mut buf, config).unwrap()
+        );
+        assert_eq!(
+            Err(Error::SerializeBufferFull),
+            to_slice_compact(&input, &mut buf[..len - 1])
+        );
+    }
+
+    #[test]
+    fn test_tee() {
+        let input = (7u32, "abcde", [1u8, 2, 3], Some(1u64 << 40));
+        let expected = to_vec_compact(&input).unwrap();
+        let mut buf = [0u32; 16];
+        let mut slice = SliceWriter::new(&mut buf);
+        let mut tee = TeeWriter::new(Vec::new(), &mut slice);
+        input.serialize(&mut Serializer::new(&mut tee)).unwrap();
+        let (vec, slice) = tee.into_inner();
+        assert_eq!(expected, vec);
+        let len = slice.position();
+        assert_eq!(expected.as_slice(), &buf[..len]);
+
+        // The first stream failing stops the write before the second one.
+        let mut small = [0u32; 2];
+        let mut vec = Vec::new();
+        let mut serializer =
+            Serializer::new(TeeWriter::new(SliceWriter::new(&mut small), &mut vec));
+        assert_eq!(
+            Err(Error::SerializeBufferFull),
+            input.serialize(&mut serializer)
+        );
+        assert_eq!(2, vec.len());
+    }
+
+    /// Serializes a slice one element at a time
+    struct ElementWise<'a, T>(&'a [T]);
+
+    impl<T: Serialize> Serialize for ElementWise<'_, T> {
+        fn serialize<S: serde::Serializer>(
+            &self,
+            serializer: S,
+        ) -> core::result::Result<S::Ok, S::Error> {
+            use serde::ser::SerializeSeq;
+            let mut seq = serializer.serialize_seq(Some(self.0.len()))?;
+            for value in self.0 {
+                seq.serialize_element(value)?;
+            }
+            seq.end()
+        }
+    }
+
+    fn assert_bulk<T: Serialize>(values: &[T]) {
+        for config in [Config::new(), Config::new().self_describing(true)] {
+            let expected = to_vec_compact_with_config(&(1u8, ElementWise(values)), config).unwrap();
+            assert_eq!(
+                expected,
+                to_vec_compact_with_config(&(1u8, values), config).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_collect_seq() {
+        let words: Vec<u32> = (0..1000u32).map(|i| i.wrapping_mul(0x01010101)).collect();
+        assert_bulk(&words);
+        assert_bulk(
+            &words
+                .iter()
+                .map(|&w| w as u64 | (w as u64) << 32)
+                .collect::<Vec<_>>(),
+        );
+        assert_bulk(&[-1i8, 2, -3]);
+        assert_bulk(&[-1i16, 2, -3]);
+        assert_bulk(&[-1i64, 1 << 40]);
+        assert_bulk(&[0.5f32, -2.0]);
+        assert_bulk(&[0.5f64, -2.0]);
+        assert_bulk(&['a', '\u{1f600}']);
+        assert_bulk::<u32>(&[]);
+        assert_bulk(&[1u8, 2, 3, 4, 5]);
+        assert_bulk(&[Some(1u32), None, Some(3)]);
+
+        #[derive(Serialize)]
+        #[serde(untagged)]
+        enum Mixed {
+            Word(u32),
+            Byte(u8),
+            Text(&'static str),
+        }
+        assert_bulk(&[
+            Mixed::Word(1),
+            Mixed::Byte(2),
+            Mixed::Byte(3),
+            Mixed::Word(4),
+            Mixed::Text("five"),
+            Mixed::Word(6),
+        ]);
+    }
+
+    #[test]
+    fn test_collect_bytes() {
+        let bytes: Vec<u8> = (0..3001u32).map(|i| (i * 7) as u8).collect();
+        for len in [0, 1, 3, 4, 5, 1023, 1024, 1025, 3001] {
+            assert_bulk(&bytes[..len]);
+        }
+        assert_bulk(&[true, false, true, true, false]);
+
+        #[derive(Serialize)]
+        #[serde(untagged)]
+        enum Mixed {
+            Byte(u8),
+            Pair(u8, u8),
+            Word(u32),
+        }
+        // The pair leaves a partially filled word for the bytes after it.
+        let mut values: Vec<_> = (0..10).map(Mixed::Byte).collect();
+        values.insert(1, Mixed::Pair(0xaa, 0xbb));
+        values.insert(7, Mixed::Word(0xcc));
+        assert_bulk(&values);
+
+        // Sized fields are buffered before reaching the stream.
+        #[derive(Serialize)]
+        struct Record<T> {
+            flag: u8,
+            data: T,
+            tail: u8,
+        }
+        let config = Config::new().field_sizes(true);
+        let element_wise = Record {
+            flag: 1,
+            data: ElementWise(&bytes[..9]),
+            tail: 2,
+        };
+        let bulk = Record {
+            flag: 1,
+            data: &bytes[..9],
+            tail: 2,
+        };
+        assert_eq!(
+            to_vec_compact_with_config(&element_wise, config).unwrap(),
+            to_vec_compact_with_config(&bulk, config).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_serialize_iter() {
+        let rows = (0..300u64).map(|i| (i, i % 3 == 0));
+        let expected = to_vec_compact(&rows.clone().collect::<Vec<_>>()).unwrap();
+        assert_eq!(expected, to_vec_from_iter(300, rows.clone()).unwrap());
+        // The length doesn't need to be known by the iterator.
+        let filtered = (0..1000u32).filter(|i| i % 10 == 0);
+        assert_eq!(
+            to_vec_compact(&filtered.clone().collect::<Vec<_>>()).unwrap(),
+            to_vec_from_iter(100, filtered).unwrap()
+        );
+
+        assert_eq!(
+            Err(Error::SerializeLengthMismatch {
+                expected: 301,
+                found: 300
+            }),
+            to_vec_from_iter(301, rows.clone())
+        );
+        assert_eq!(
+            Err(Error::SerializeLengthMismatch {
+                expected: 299,
+                found: 300
+            }),
+            to_vec_from_iter(299, rows)
+        );
+        assert_eq!(
+            Err(Error::SerializeLengthMismatch {
+                expected: 3,
+                found: 4
+            }),
+            to_vec_from_iter(3, 0u32..)
+        );
+    }
+
+    #[test]
+    fn test_deep_options() {
+        #[derive(Debug, PartialEq, Serialize, serde::Deserialize)]
+        struct Node {
+            v: u8,
+            next: Option<Box<Node>>,
+        }
+
+        let list = (0..200).fold(None, |next, v| Some(Box::new(Node { v, next })));
+        let list = *list.unwrap();
+        let data = to_vec_compact(&list).unwrap();
+        assert_eq!(Ok(list), crate::from_slice_compact::<Node, _>(&data));
+
+        // Byte tags pack with the value, so every Option is a level of its own.
+        let config = Config::new().byte_option_tags(true);
+        let list = (0..100).fold(None, |next, v| Some(Box::new(Node { v, next })));
+        let data = to_vec_compact_with_config(&list, config).unwrap();
+        assert_eq!(
+            Ok(list),
+            crate::from_slice_compact_with_config::<Option<Box<Node>>, _>(&data, config)
+        );
+        let deep = (0..200).fold(None, |next, v| Some(Box::new(Node { v, next })));
+        assert_eq!(
+            Err(Error::SerializeTooDeep),
+            to_vec_compact_with_config(&deep, config)
+        );
+    }
+
+    #[test]
+    fn test_wide_nodes() {
+        // Declares more elements than a marker holds, without writing any
+        struct Wide(bool);
+
+        impl Serialize for Wide {
+            fn serialize<S>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error>
+            where
+                S: serde::Serializer,
+            {
+                use serde::ser::{SerializeStruct, SerializeTuple};
+
+                match self.0 {
+                    false => serializer.serialize_tuple(1 << 24)?.end(),
+                    true => serializer.serialize_struct("Wide", 1 << 24)?.end(),
+                }
+            }
+        }
+
+        let config = Config::new().self_describing(true);
+        for wide in [Wide(false), Wide(true)] {
+            assert_eq!(Ok(Vec::new()), to_vec_compact(&wide));
+            assert_eq!(
+                Err(Error::SerializeLengthOverflow { requested: 1 << 24 }),
+                to_vec_compact_with_config(&wide, config)
+            );
+        }
+    }
 }
@@ -0,0 +1,236 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::{collections::VecDeque, vec::Vec};
+
+use risc0_zkvm::serde::WordWrite;
+use serde::Serialize;
+
+use super::{err::Result, Config, Serializer};
+
+/// Whether a [Session] has written all its output
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Progress {
+    /// Every word has reached the stream
+    Done,
+    /// The stream is full, and the given number of words are waiting for
+    /// [Session::resume]
+    Blocked(usize),
+}
+
+/// A serialization session over a stream that can refuse writes
+///
+/// A stream applies back-pressure by failing a write with
+/// [SerializeBufferFull](risc0_zkvm::serde::Error::SerializeBufferFull), which
+/// must leave it unchanged, as a [SliceWriter](crate::SliceWriter) or a bounded
+/// ring buffer does. The session then holds the words the stream refused and
+/// reports [Progress::Blocked], and [Session::resume] writes them once the
+/// stream has been drained. Values serialized in the meantime are queued
+/// behind them, and the state of the serializer carries over from one value
+/// to the next as with a single [Serializer].
+/// ```rust
+/// use l2r0_small_serde::{Progress, Session, SliceWriter};
+///
+/// let mut ring = [0u32; 4];
+/// let mut session = Session::new(SliceWriter::new(&mut ring));
+/// assert_eq!(Progress::Blocked(2), session.serialize(&[1u32; 6]).unwrap());
+/// ```
+///
+/// A value is always encoded in full before it's written, so the words held
+/// are bounded by the largest value rather than by the stream.
+pub struct Session<W: WordWrite> {
+    serializer: Serializer<Backlog<W>>,
+}
+
+impl<W: WordWrite> Session<W> {
+    /// Construct a Session that writes to `stream`
+    pub fn new(stream: W) -> Self {
+        Self::with_config(stream, Config::default())
+    }
+
+    /// Construct a Session that writes to `stream` using `config`
+    pub fn with_config(stream: W, config: Config) -> Self {
+        Session {
+            serializer: Serializer::with_config(
+                Backlog {
+                    stream,
+                    pending: VecDeque::new(),
+                },
+                config,
+            ),
+        }
+    }
+
+    /// Serialize `value` and write as much of the output as the stream takes
+    pub fn serialize<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<Progress> {
+        value.serialize(&mut self.serializer)?;
+        Ok(self.progress())
+    }
+
+    /// Write the words held back to the stream, as far as it takes them
+    pub fn resume(&mut self) -> Result<Progress> {
+        self.serializer.stream_mut().drain()?;
+        Ok(self.progress())
+    }
+
+    /// Pad the output as [Serializer::finish] does, and write as much of it
+    /// as the stream takes
+    ///
+    /// Call this once after the last value has been serialized, and
+    /// [Session::resume] until the session is done.
+    pub fn finish(&mut self) -> Result<Progress> {
+        self.serializer.finish()?;
+        Ok(self.progress())
+    }
+
+    /// The stream written to
+    pub fn stream_mut(&mut self) -> &mut W {
+        &mut self.serializer.stream_mut().stream
+    }
+
+    fn progress(&mut self) -> Progress {
+        match self.serializer.stream_mut().pending.len() {
+            0 => Progress::Done,
+            len => Progress::Blocked(len),
+        }
+    }
+}
+
+/// Holds the words a stream refused until it takes them
+struct Backlog<W: WordWrite> {
+    stream: W,
+    pending: VecDeque<u32>,
+}
+
+impl<W: WordWrite> Backlog<W> {
+    /// Write pending words until the stream refuses one
+    fn drain(&mut self) -> risc0_zkvm::serde::Result<()> {
+        while let Some(&word) = self.pending.front() {
+            match self.stream.write_words(&[word]) {
+                Ok(()) => self.pending.pop_front(),
+                Err(risc0_zkvm::serde::Error::SerializeBufferFull) => return Ok(()),
+                Err(err) => return Err(err),
+            };
+        }
+        Ok(())
+    }
+}
+
+impl<W: WordWrite> WordWrite for Backlog<W> {
+    fn write_words(&mut self, words: &[u32]) -> risc0_zkvm::serde::Result<()> {
+        if self.pending.is_empty() {
+            match self.stream.write_words(words) {
+                Err(risc0_zkvm::serde::Error::SerializeBufferFull) => {}
+                res => return res,
+            }
+        }
+        // Queue the words behind those already refused, and write as many of
+        // them as fit.
+        self.pending.extend(words);
+        self.drain()
+    }
+
+    fn write_padded_bytes(&mut self, bytes: &[u8]) -> risc0_zkvm::serde::Result<()> {
+        let mut words = Vec::new();
+        words.write_padded_bytes(bytes)?;
+        self.write_words(&words)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::String, vec, vec::Vec};
+
+    use risc0_zkvm::serde::WordWrite;
+
+    use super::*;
+    use crate::{to_vec_compact, to_vec_compact_with_config};
+
+    /// A bounded buffer that the test drains by hand
+    struct Ring {
+        words: Vec<u32>,
+        capacity: usize,
+        drained: Vec<u32>,
+    }
+
+    impl Ring {
+        fn drain(&mut self) {
+            self.drained.append(&mut self.words);
+        }
+    }
+
+    impl WordWrite for Ring {
+        fn write_words(&mut self, words: &[u32]) -> risc0_zkvm::serde::Result<()> {
+            if self.words.len() + words.len() > self.capacity {
+                return Err(risc0_zkvm::serde::Error::SerializeBufferFull);
+            }
+            self.words.extend_from_slice(words);
+            Ok(())
+        }
+
+        fn write_padded_bytes(&mut self, bytes: &[u8]) -> risc0_zkvm::serde::Result<()> {
+            let mut words = Vec::new();
+            words.write_padded_bytes(bytes)?;
+            self.write_words(&words)
+        }
+    }
+
+    fn ring(capacity: usize) -> Ring {
+        Ring {
+            words: Vec::new(),
+            capacity,
+            drained: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_resume() {
+        let first = (7u8, vec![true, false, true], String::from("resumed"));
+        let second = [9u64; 3];
+        let mut session = Session::new(ring(3));
+        let Progress::Blocked(_) = session.serialize(&first).unwrap() else {
+            panic!("the ring should be full");
+        };
+        let mut progress = session.serialize(&second).unwrap();
+        while let Progress::Blocked(pending) = progress {
+            assert_eq!(3, session.stream_mut().words.len());
+            session.stream_mut().drain();
+            progress = session.resume().unwrap();
+            if let Progress::Blocked(left) = progress {
+                assert_eq!(pending - 3, left);
+            }
+        }
+        session.stream_mut().drain();
+
+        let mut expected = to_vec_compact(&first).unwrap();
+        expected.extend(to_vec_compact(&second).unwrap());
+        assert_eq!(expected, session.stream_mut().drained);
+    }
+
+    #[test]
+    fn test_finish() {
+        let config = Config::new().pad_to(4);
+        let mut session = Session::with_config(ring(6), config);
+        assert_eq!(Progress::Done, session.serialize(&[1u32; 5]).unwrap());
+        // One word of padding fits, and two are held back.
+        assert_eq!(Progress::Blocked(2), session.finish().unwrap());
+        session.stream_mut().drain();
+        assert_eq!(Progress::Done, session.resume().unwrap());
+        session.stream_mut().drain();
+        assert_eq!(
+            to_vec_compact_with_config(&[1u32; 5], config).unwrap(),
+            session.stream_mut().drained
+        );
+    }
+}
@@ -0,0 +1,204 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Compile-time bounds on the encoded size of values
+//!
+//! [WordSized::MAX_WORDS] is an upper bound on the number of words a value of
+//! the type takes in the standard encoding, so that a guest can serialize into
+//! a fixed buffer on the stack and a host can allocate the output up front.
+//! Bounds exist for types without unbounded collections, and can be derived
+//! for structs and enums with the `derive` feature.
+//! ```rust
+//! use l2r0_small_serde::{to_slice_compact, WordSized};
+//! use serde::Serialize;
+//!
+//! #[derive(Serialize, WordSized)]
+//! struct Header {
+//!     version: u8,
+//!     root: [u32; 8],
+//!     parent: Option<u64>,
+//! }
+//!
+//! let header = Header { version: 1, root: [0; 8], parent: Some(7) };
+//! let mut buf = [0u32; Header::MAX_WORDS];
+//! let len = to_slice_compact(&header, &mut buf).unwrap();
+//! assert_eq!(Header::MAX_WORDS, len);
+//! ```
+//!
+//! The bound is exact for types made of word-sized values only. Bytes and
+//! bools are counted as a word each unless they are the elements of an array,
+//! so the bound exceeds the encoding where neighbouring bytes share a word.
+//! It only holds for the default [Config](crate::Config).
+
+/// A type whose encoding has a bounded size
+pub trait WordSized {
+    /// Upper bound on the number of words of the encoding
+    const MAX_WORDS: usize;
+
+    /// Whether the value is a single byte, which packs with neighbouring
+    /// bytes
+    const IS_BYTE: bool = false;
+}
+
+/// The larger of two bounds, for use in derived impls
+#[doc(hidden)]
+pub const fn max(a: usize, b: usize) -> usize {
+    if a > b {
+        a
+    } else {
+        b
+    }
+}
+
+macro_rules! impl_word_sized {
+    ($($words:literal => $($ty:ty)*;)*) => {
+        $($(
+            impl WordSized for $ty {
+                const MAX_WORDS: usize = $words;
+            }
+        )*)*
+    };
+}
+
+impl_word_sized! {
+    0 => ();
+    1 => i8 i16 i32 u16 u32 f32 char;
+    2 => i64 u64 f64;
+    4 => i128 u128;
+}
+
+impl WordSized for u8 {
+    const MAX_WORDS: usize = 1;
+    const IS_BYTE: bool = true;
+}
+
+impl WordSized for bool {
+    const MAX_WORDS: usize = 1;
+    const IS_BYTE: bool = true;
+}
+
+impl<T: ?Sized> WordSized for core::marker::PhantomData<T> {
+    const MAX_WORDS: usize = 0;
+}
+
+impl<T: WordSized + ?Sized> WordSized for &T {
+    const MAX_WORDS: usize = T::MAX_WORDS;
+    const IS_BYTE: bool = T::IS_BYTE;
+}
+
+#[cfg(feature = "alloc")]
+impl<T: WordSized + ?Sized> WordSized for alloc::boxed::Box<T> {
+    const MAX_WORDS: usize = T::MAX_WORDS;
+    const IS_BYTE: bool = T::IS_BYTE;
+}
+
+impl<T: WordSized> WordSized for Option<T> {
+    // The tag word, followed by the value if present.
+    const MAX_WORDS: usize = 1 + T::MAX_WORDS;
+}
+
+impl<T: WordSized, const N: usize> WordSized for [T; N] {
+    const MAX_WORDS: usize = match T::IS_BYTE {
+        true => N.div_ceil(crate::WORD_SIZE),
+        false => N * T::MAX_WORDS,
+    };
+}
+
+macro_rules! impl_tuple {
+    ($($name:ident)+) => {
+        impl<$($name: WordSized),+> WordSized for ($($name,)+) {
+            const MAX_WORDS: usize = 0 $(+ $name::MAX_WORDS)+;
+        }
+    };
+}
+
+impl_tuple!(A);
+impl_tuple!(A B);
+impl_tuple!(A B C);
+impl_tuple!(A B C D);
+impl_tuple!(A B C D E);
+impl_tuple!(A B C D E F);
+impl_tuple!(A B C D E F G);
+impl_tuple!(A B C D E F G H);
+
+#[cfg(test)]
+mod tests {
+    use core::marker::PhantomData;
+
+    use serde::Serialize;
+
+    use crate::{to_slice_compact, WordSized};
+
+    #[derive(Serialize, WordSized)]
+    struct Point(i64, i64);
+
+    #[derive(Serialize, WordSized)]
+    struct Unit;
+
+    #[derive(Serialize, WordSized)]
+    enum Shape<T> {
+        Empty,
+        Dot(Point),
+        Line { from: Point, to: Point, width: T },
+        Named(u32, [u8; 5]),
+    }
+
+    #[derive(Serialize, WordSized)]
+    struct Scene {
+        flag: bool,
+        shapes: [Shape<u16>; 2],
+        unit: Unit,
+        marker: PhantomData<u8>,
+        id: Option<(u32, char)>,
+    }
+
+    fn encoded_len<T: Serialize + WordSized>(value: &T) -> usize {
+        let mut buf = [0u32; 64];
+        let len = to_slice_compact(value, &mut buf[..T::MAX_WORDS]).unwrap();
+        assert!(len <= T::MAX_WORDS);
+        len
+    }
+
+    #[test]
+    fn test_exact() {
+        assert_eq!(10, Shape::<u16>::MAX_WORDS);
+        let line = Shape::Line {
+            from: Point(1, 2),
+            to: Point(3, 4),
+            width: 5u16,
+        };
+        assert_eq!(10, encoded_len(&line));
+        assert_eq!(5, encoded_len(&Shape::<u16>::Dot(Point(-1, 1))));
+        assert_eq!(3, encoded_len(&Some((1u32, 'a'))));
+        assert_eq!(4, encoded_len(&u128::MAX));
+        assert_eq!(0, encoded_len(&Unit));
+    }
+
+    #[test]
+    fn test_bound() {
+        let scene = Scene {
+            flag: true,
+            shapes: [Shape::Empty, Shape::Named(7, [1, 2, 3, 4, 5])],
+            unit: Unit,
+            marker: PhantomData,
+            id: None,
+        };
+        assert_eq!(1 + 2 * 10 + 3, Scene::MAX_WORDS);
+        assert_eq!(7, encoded_len(&scene));
+        assert_eq!(2, <[bool; 8]>::MAX_WORDS);
+        assert_eq!(2, encoded_len(&[true; 8]));
+        assert_eq!(4, <(u8, u8, u8, u8)>::MAX_WORDS);
+        assert_eq!(1, encoded_len(&(1u8, 2u8, 3u8, 4u8)));
+    }
+}
@@ -0,0 +1,230 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Deterministic encoding of unordered maps
+//!
+//! Iterating a `HashMap` visits its entries in a different order from run to
+//! run, so serializing it directly produces different words for the same
+//! contents, which breaks commitments to the encoding. [SortedMap] writes the
+//! entries of any map ordered by the standard encoding of their keys instead:
+//! ```rust
+//! use std::collections::HashMap;
+//!
+//! use l2r0_small_serde::{sorted::SortedMap, to_vec_compact};
+//!
+//! let a = HashMap::from([(1u32, 'a'), (2, 'b'), (3, 'c')]);
+//! let b = HashMap::from([(3u32, 'c'), (1, 'a'), (2, 'b')]);
+//! assert_eq!(
+//!     to_vec_compact(&SortedMap(a)).unwrap(),
+//!     to_vec_compact(&SortedMap(b)).unwrap(),
+//! );
+//! ```
+//!
+//! The order is that of the standard encoding whatever the [Config] of the
+//! serializer, so it only depends on the keys themselves. Fields can use the
+//! module with `#[serde(with = "l2r0_small_serde::sorted")]`, which accepts
+//! entries in any order when deserializing, or with
+//! `#[serde(with = "l2r0_small_serde::sorted::verified")]`, which rejects data
+//! that isn't canonical with [Error::DeserializeUnsortedMap].
+//!
+//! [Config]: crate::Config
+
+use alloc::vec::Vec;
+use core::{fmt, marker::PhantomData};
+
+use serde::{
+    de::{MapAccess, Visitor},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+
+use super::{err::Error, to_vec_compact};
+
+/// A map serialized with its entries sorted by key
+///
+/// It deserializes from entries in any order. A borrowed map can be written
+/// with [serialize] instead.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct SortedMap<M>(pub M);
+
+impl<M, K, V> Serialize for SortedMap<M>
+where
+    for<'a> &'a M: IntoIterator<Item = (&'a K, &'a V)>,
+    K: Serialize,
+    V: Serialize,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize(&self.0, serializer)
+    }
+}
+
+impl<'de, M, K, V> Deserialize<'de> for SortedMap<M>
+where
+    M: IntoIterator<Item = (K, V)> + FromIterator<(K, V)>,
+    K: Deserialize<'de>,
+    V: Deserialize<'de>,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserialize(deserializer).map(SortedMap)
+    }
+}
+
+/// Serialize the entries of `map` sorted by key
+pub fn serialize<M, K, V, S>(map: &M, serializer: S) -> Result<S::Ok, S::Error>
+where
+    for<'a> &'a M: IntoIterator<Item = (&'a K, &'a V)>,
+    K: Serialize,
+    V: Serialize,
+    S: Serializer,
+{
+    let mut entries = map
+        .into_iter()
+        .map(|(key, value)| Ok((to_vec_compact(key)?, key, value)))
+        .collect::<Result<Vec<_>, Error>>()
+        .map_err(serde::ser::Error::custom)?;
+    entries.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+    serializer.collect_map(entries.into_iter().map(|(_, key, value)| (key, value)))
+}
+
+/// Deserialize a map from entries in any order
+pub fn deserialize<'de, M, K, V, D>(deserializer: D) -> Result<M, D::Error>
+where
+    M: IntoIterator<Item = (K, V)> + FromIterator<(K, V)>,
+    K: Deserialize<'de>,
+    V: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    let entries: Vec<(K, V)> = deserializer.deserialize_map(EntriesVisitor(PhantomData))?;
+    Ok(entries.into_iter().collect())
+}
+
+/// Sorted maps that are checked to be canonical when deserializing
+pub mod verified {
+    use super::*;
+
+    pub use super::serialize;
+
+    /// Deserialize a map, failing unless its keys are sorted and distinct
+    pub fn deserialize<'de, M, K, V, D>(deserializer: D) -> Result<M, D::Error>
+    where
+        M: IntoIterator<Item = (K, V)> + FromIterator<(K, V)>,
+        K: Deserialize<'de> + Serialize,
+        V: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        let entries: Vec<(K, V)> = deserializer.deserialize_map(EntriesVisitor(PhantomData))?;
+        let mut previous = None;
+        for (key, _) in &entries {
+            let key = to_vec_compact(key).map_err(serde::de::Error::custom)?;
+            if previous.is_some_and(|previous| previous >= key) {
+                return Err(serde::de::Error::custom(Error::DeserializeUnsortedMap));
+            }
+            previous = Some(key);
+        }
+        Ok(entries.into_iter().collect())
+    }
+}
+
+/// Collects the entries of a map in the order they were written
+struct EntriesVisitor<K, V>(PhantomData<(K, V)>);
+
+impl<'de, K, V> Visitor<'de> for EntriesVisitor<K, V>
+where
+    K: Deserialize<'de>,
+    V: Deserialize<'de>,
+{
+    type Value = Vec<(K, V)>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a map")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut entries = Vec::with_capacity(crate::cautious_capacity(map.size_hint()));
+        while let Some(entry) = map.next_entry()? {
+            entries.push(entry);
+        }
+        Ok(entries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{
+        string::{String, ToString},
+        vec,
+    };
+    use std::collections::{BTreeMap, HashMap};
+
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+    use crate::{from_slice_compact, to_vec_compact};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Balances {
+        #[serde(with = "crate::sorted")]
+        accounts: HashMap<String, u64>,
+        #[serde(with = "crate::sorted::verified")]
+        nonces: HashMap<(u32, u8), u32>,
+    }
+
+    fn balances() -> Balances {
+        Balances {
+            accounts: (0..32)
+                .map(|i| (alloc::format!("account {i}"), i))
+                .collect(),
+            nonces: (0..32).map(|i| ((i % 4, i as u8), i)).collect(),
+        }
+    }
+
+    #[test]
+    fn test_deterministic() {
+        let data = to_vec_compact(&balances()).unwrap();
+        // Each HashMap is seeded differently.
+        for _ in 0..8 {
+            assert_eq!(data, to_vec_compact(&balances()).unwrap());
+        }
+        assert_eq!(balances(), from_slice_compact(&data).unwrap());
+
+        // The order is that of the encoded keys, not that of Ord.
+        let map = HashMap::from([(String::from("b"), 1u32), (String::from("aa"), 2)]);
+        let btree = BTreeMap::from([(String::from("b"), 1u32), (String::from("aa"), 2)]);
+        let data = to_vec_compact(&SortedMap(map.clone())).unwrap();
+        assert_eq!(to_vec_compact(&SortedMap(btree.clone())).unwrap(), data);
+        assert_ne!(to_vec_compact(&btree).unwrap(), data);
+        let SortedMap(output): SortedMap<HashMap<String, u32>> = from_slice_compact(&data).unwrap();
+        assert_eq!(map, output);
+    }
+
+    #[test]
+    fn test_verified() {
+        #[derive(Debug, Deserialize)]
+        struct Nonces(#[serde(with = "crate::sorted::verified")] BTreeMap<u32, u32>);
+
+        let sorted = to_vec_compact(&BTreeMap::from([(1u32, 0u32), (2, 0)])).unwrap();
+        assert_eq!(2, from_slice_compact::<Nonces, _>(&sorted).unwrap().0.len());
+
+        let reversed = to_vec_compact(&vec![(2u32, 0u32), (1, 0)]).unwrap();
+        assert_eq!(
+            Err(Error::Custom(Error::DeserializeUnsortedMap.to_string())),
+            from_slice_compact::<Nonces, _>(&reversed).map(|_| ())
+        );
+        // Lenient decoding accepts the same data.
+        let SortedMap(map): SortedMap<BTreeMap<u32, u32>> = from_slice_compact(&reversed).unwrap();
+        assert_eq!(2, map.len());
+
+        let repeated = to_vec_compact(&vec![(1u32, 0u32), (1, 1)]).unwrap();
+        assert!(from_slice_compact::<Nonces, _>(&repeated).is_err());
+    }
+}
@@ -0,0 +1,253 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Statistics on how the bytes of an encoding are spent
+
+use core::fmt::{Display, Formatter};
+
+use risc0_zkvm::serde::WordWrite;
+
+use super::{err::Result, Config, Serializer, WORD_SIZE};
+
+/// How the bytes of an encoding are spent
+///
+/// Every byte of the output falls in exactly one category, so that the
+/// categories add up to [EncodingStats::total]. Guest developers can compare
+/// them to decide which options of [Config] are worth enabling: many
+/// `widening` bytes call for packed bytes or smaller scalars, many `tags` bytes
+/// for [Config::byte_option_tags] or byte variant tags, and many `padding` bytes
+/// for [Config::pack_top_level].
+///
+/// The [Display] impl lists the categories with their share of the total.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct EncodingStats {
+    /// Bytes of values: scalars and the contents of strings and byte arrays
+    pub data: usize,
+    /// Bytes of the lengths of sequences, maps, strings and byte arrays, and
+    /// of struct field counts and sizes
+    pub lengths: usize,
+    /// Bytes of variant and option tags, and of references to interned
+    /// strings
+    pub tags: usize,
+    /// Bytes of the markers of the self-describing encoding
+    pub markers: usize,
+    /// Unused bytes of the words holding small scalars: an `i8`, `i16` or
+    /// `u16`, or a byte that doesn't pack at the top level
+    pub widening: usize,
+    /// Unused bytes of packed and padded words, and the words added by
+    /// [Config::pad_to]
+    pub padding: usize,
+}
+
+impl EncodingStats {
+    /// Number of bytes of the encoding
+    pub fn total(&self) -> usize {
+        self.data + self.overhead()
+    }
+
+    /// Number of bytes of the encoding that aren't data
+    pub fn overhead(&self) -> usize {
+        self.lengths + self.tags + self.markers + self.widening + self.padding
+    }
+}
+
+impl Display for EncodingStats {
+    fn fmt(&self, formatter: &mut Formatter) -> core::fmt::Result {
+        let total = self.total();
+        writeln!(
+            formatter,
+            "total: {total} bytes ({} words)",
+            total / WORD_SIZE
+        )?;
+        for (name, bytes) in [
+            ("data", self.data),
+            ("lengths", self.lengths),
+            ("tags", self.tags),
+            ("markers", self.markers),
+            ("widening", self.widening),
+            ("padding", self.padding),
+        ] {
+            let share = match total {
+                0 => 0.0,
+                _ => bytes as f64 * 100.0 / total as f64,
+            };
+            writeln!(formatter, "{name}: {bytes} bytes ({share:.1}%)")?;
+        }
+        Ok(())
+    }
+}
+
+/// Compute the statistics of the encoding of `value`
+///
+/// The encoding is counted as it is produced and then dropped, so this doesn't
+/// allocate.
+pub fn encoding_stats<T>(value: &T, config: Config) -> Result<EncodingStats>
+where
+    T: serde::Serialize + ?Sized,
+{
+    let mut serializer = Serializer::with_config(Discard, config);
+    serializer.record_encoding_stats();
+    value.serialize(&mut serializer)?;
+    serializer.finish()?;
+    Ok(serializer.encoding_stats().unwrap())
+}
+
+/// Categories of [EncodingStats] that are counted as they are written
+#[derive(Clone, Copy)]
+pub(crate) enum Usage {
+    Data,
+    Length,
+    Tag,
+    Marker,
+    Widening,
+}
+
+/// Counts the bytes of each category as a Serializer writes them
+pub(crate) struct StatsRecorder {
+    counted: EncodingStats,
+    /// Number of words emitted before recording started
+    start: usize,
+}
+
+impl StatsRecorder {
+    pub(crate) fn new(start: usize) -> Self {
+        StatsRecorder {
+            counted: EncodingStats::default(),
+            start,
+        }
+    }
+
+    pub(crate) fn add(&mut self, usage: Usage, bytes: usize) {
+        let count = match usage {
+            Usage::Data => &mut self.counted.data,
+            Usage::Length => &mut self.counted.lengths,
+            Usage::Tag => &mut self.counted.tags,
+            Usage::Marker => &mut self.counted.markers,
+            Usage::Widening => &mut self.counted.widening,
+        };
+        *count += bytes;
+    }
+
+    /// The statistics once `end` words have been emitted
+    ///
+    /// Whatever wasn't counted is padding.
+    pub(crate) fn stats(&self, end: usize) -> EncodingStats {
+        let mut stats = self.counted;
+        stats.padding = (end - self.start) * WORD_SIZE - stats.total();
+        stats
+    }
+}
+
+/// A WordWrite that drops everything
+struct Discard;
+
+impl WordWrite for Discard {
+    fn write_words(&mut self, _words: &[u32]) -> risc0_zkvm::serde::Result<()> {
+        Ok(())
+    }
+
+    fn write_padded_bytes(&mut self, _bytes: &[u8]) -> risc0_zkvm::serde::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::ToString, vec};
+
+    use serde::Serialize;
+
+    use super::{encoding_stats, EncodingStats};
+    use crate::{to_vec_compact_with_config, Config, VariantTag};
+
+    #[derive(Serialize)]
+    enum Memo<'a> {
+        None,
+        Text(&'a str),
+    }
+
+    #[derive(Serialize)]
+    struct Transfer<'a> {
+        kind: u8,
+        amount: u64,
+        memo: &'a str,
+        fee: Option<u16>,
+        delta: i8,
+    }
+
+    const TRANSFER: Transfer = Transfer {
+        kind: 2,
+        amount: 1000,
+        memo: "hello",
+        fee: Some(3),
+        delta: -1,
+    };
+
+    #[test]
+    fn test_stats() {
+        let stats = encoding_stats(&TRANSFER, Config::new()).unwrap();
+        assert_eq!(
+            EncodingStats {
+                data: 17,
+                lengths: 4,
+                tags: 4,
+                markers: 0,
+                widening: 5,
+                padding: 6,
+            },
+            stats
+        );
+        assert_eq!(
+            "total: 36 bytes (9 words)\n\
+             data: 17 bytes (47.2%)\n\
+             lengths: 4 bytes (11.1%)\n\
+             tags: 4 bytes (11.1%)\n\
+             markers: 0 bytes (0.0%)\n\
+             widening: 5 bytes (13.9%)\n\
+             padding: 6 bytes (16.7%)\n",
+            stats.to_string()
+        );
+
+        // A byte gets a word of its own at the top level, unless packed.
+        let stats = encoding_stats(&7u8, Config::new()).unwrap();
+        assert_eq!((1, 3, 0), (stats.data, stats.widening, stats.padding));
+        let stats = encoding_stats(&7u8, Config::new().pack_top_level(true)).unwrap();
+        assert_eq!((1, 0, 3), (stats.data, stats.widening, stats.padding));
+    }
+
+    #[test]
+    fn test_total() {
+        let memos = vec![Memo::None, Memo::Text("abc"), Memo::Text("abc")];
+        let value = (TRANSFER, memos, [true; 5], 9u8);
+        for config in [
+            Config::new(),
+            Config::new().self_describing(true),
+            Config::new().byte_option_tags(true),
+            Config::new().variant_tag(VariantTag::Byte),
+            Config::new().field_sizes(true),
+            Config::new().intern_strings(true),
+            Config::new().pad_to(16),
+            Config::new().pack_top_level(true),
+        ] {
+            let data = to_vec_compact_with_config(&value, config).unwrap();
+            let stats = encoding_stats(&value, config).unwrap();
+            assert_eq!(data.len() * 4, stats.total());
+            // Only the first of the repeated strings is data when interning.
+            let repeated = if config.intern_strings { 0 } else { 3 };
+            assert_eq!(17 + 3 + repeated + 5 + 1, stats.data);
+        }
+        let stats = encoding_stats(&value, Config::new().self_describing(true)).unwrap();
+        assert!(stats.markers > 0);
+    }
+}
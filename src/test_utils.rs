@@ -0,0 +1,270 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Property-based checks for types using this encoding
+//!
+//! The runners generate values from a proptest [Strategy] and check each of
+//! them, returning the smallest failing value found, so downstream crates can
+//! exercise their own types in CI:
+//! ```rust
+//! use l2r0_small_serde::test_utils::{arb_config, run_differential, run_round_trip};
+//! use proptest::prelude::*;
+//!
+//! run_round_trip(any::<(u64, Vec<u8>, Option<String>)>(), arb_config()).unwrap();
+//! // Without bytes or bools in composites, the standard encoding is that of
+//! // risc0_zkvm::serde.
+//! run_differential(any::<(u32, Vec<i64>, String)>()).unwrap();
+//! ```
+//!
+//! The two encodings differ in how `u8` and `bool` are packed inside
+//! composites; everything else is written the same way, so
+//! [run_differential] applies to types whose only bytes and bools are top
+//! level values.
+
+use alloc::{format, vec::Vec};
+use core::fmt::{Debug, Display, Formatter};
+
+use proptest::{
+    prelude::*,
+    test_runner::{TestError, TestRunner},
+};
+use serde::{de::DeserializeOwned, Serialize};
+
+use super::{
+    err::Error, from_slice_compact_with_config, to_vec_compact, to_vec_compact_with_config, Config,
+    VariantTag,
+};
+
+/// A check that failed for a value
+#[derive(Clone, Debug, PartialEq)]
+pub enum Failure {
+    /// The value failed to serialize
+    Serialize(Error),
+    /// The encoding of the value failed to deserialize
+    Deserialize(Error),
+    /// The encoding deserialized to a different value
+    RoundTrip,
+    /// The encoding without its last word deserialized
+    Truncated,
+    /// The encoding differs from the one of `risc0_zkvm::serde`
+    Differs {
+        /// The encoding of this crate
+        ours: Vec<u32>,
+        /// The encoding of `risc0_zkvm::serde`
+        risc0: Vec<u32>,
+    },
+    /// `risc0_zkvm::serde` failed where this crate did not
+    Risc0(Error),
+}
+
+impl Display for Failure {
+    fn fmt(&self, formatter: &mut Formatter) -> core::fmt::Result {
+        let msg = match self {
+            Self::Serialize(e) => return write!(formatter, "Failed to serialize: {e}"),
+            Self::Deserialize(e) => return write!(formatter, "Failed to deserialize: {e}"),
+            Self::RoundTrip => "Deserialized a different value",
+            Self::Truncated => "Deserialized a truncated encoding",
+            Self::Differs { ours, risc0 } => {
+                return write!(formatter, "Encoding {ours:?} differs from risc0 {risc0:?}")
+            }
+            Self::Risc0(e) => return write!(formatter, "risc0 serde failed: {e}"),
+        };
+        formatter.write_str(msg)
+    }
+}
+
+/// Generate every combination of the options of [Config]
+pub fn arb_config() -> impl Strategy<Value = Config> {
+    let variant_tag = prop_oneof![
+        Just(VariantTag::Word),
+        Just(VariantTag::Byte),
+        Just(VariantTag::NameHash),
+    ];
+    (
+        variant_tag,
+        any::<[bool; 10]>(),
+        prop_oneof![Just(0usize), 1usize..=8],
+    )
+        .prop_map(
+            |(
+                variant_tag,
+                [
+                    byte_option_tags,
+                    pack_top_level,
+                    field_count,
+                    field_sizes,
+                    intern_strings,
+                    self_describing,
+                    human_readable,
+                    big_endian,
+                    lenient_integers,
+                    replace_invalid_chars,
+                ],
+                pad_to,
+            )| {
+                Config::new()
+                    .variant_tag(variant_tag)
+                    .byte_option_tags(byte_option_tags)
+                    .pack_top_level(pack_top_level)
+                    .field_count(field_count)
+                    .field_sizes(field_sizes)
+                    .intern_strings(intern_strings)
+                    .self_describing(self_describing)
+                    .human_readable(human_readable)
+                    .big_endian(big_endian)
+                    .lenient_integers(lenient_integers)
+                    .replace_invalid_chars(replace_invalid_chars)
+                    .pad_to(pad_to)
+            },
+        )
+}
+
+/// Check that `value` decodes to itself, and that its encoding without the
+/// last word does not decode
+pub fn check_round_trip<T>(value: &T, config: Config) -> Result<(), Failure>
+where
+    T: Serialize + DeserializeOwned + PartialEq,
+{
+    let words = to_vec_compact_with_config(value, config).map_err(Failure::Serialize)?;
+    let output: T = from_slice_compact_with_config(&words, config).map_err(Failure::Deserialize)?;
+    if output != *value {
+        return Err(Failure::RoundTrip);
+    }
+    truncated_fails(&words, |words| {
+        from_slice_compact_with_config::<T, _>(words, config).is_ok()
+    })
+}
+
+/// Check that `value` encodes as with `risc0_zkvm::serde`, and that both
+/// decode each other's encoding and reject it truncated
+pub fn check_differential<T>(value: &T) -> Result<(), Failure>
+where
+    T: Serialize + DeserializeOwned + PartialEq,
+{
+    let ours = to_vec_compact(value).map_err(Failure::Serialize)?;
+    let risc0 = risc0_zkvm::serde::to_vec(value).map_err(|e| Failure::Risc0(e.into()))?;
+    if ours != risc0 {
+        return Err(Failure::Differs { ours, risc0 });
+    }
+    check_round_trip(value, Config::default())?;
+    let output: T = risc0_zkvm::serde::from_slice(&ours).map_err(|e| Failure::Risc0(e.into()))?;
+    if output != *value {
+        return Err(Failure::RoundTrip);
+    }
+    truncated_fails(&ours, |words| {
+        risc0_zkvm::serde::from_slice::<T, _>(words).is_ok()
+    })
+}
+
+fn truncated_fails(words: &[u32], decodes: impl FnOnce(&[u32]) -> bool) -> Result<(), Failure> {
+    match words.split_last() {
+        Some((_, rest)) if decodes(rest) => Err(Failure::Truncated),
+        _ => Ok(()),
+    }
+}
+
+/// Run [check_round_trip] on values generated by `strategy`, each with a
+/// configuration generated by `config`
+///
+/// The number of cases and other settings are taken from the `PROPTEST_`
+/// environment variables.
+pub fn run_round_trip<S, C>(strategy: S, config: C) -> Result<(), TestError<(S::Value, Config)>>
+where
+    S: Strategy,
+    S::Value: Serialize + DeserializeOwned + PartialEq,
+    C: Strategy<Value = Config>,
+{
+    TestRunner::default().run(&(strategy, config), |(value, config)| {
+        check_round_trip(&value, config).map_err(fail)
+    })
+}
+
+/// Run [check_differential] on values generated by `strategy`
+///
+/// The number of cases and other settings are taken from the `PROPTEST_`
+/// environment variables.
+pub fn run_differential<S>(strategy: S) -> Result<(), TestError<S::Value>>
+where
+    S: Strategy,
+    S::Value: Serialize + DeserializeOwned + PartialEq,
+{
+    TestRunner::default().run(&strategy, |value| check_differential(&value).map_err(fail))
+}
+
+fn fail(failure: Failure) -> TestCaseError {
+    TestCaseError::fail(format!("{failure}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{collections::BTreeMap, string::String, vec, vec::Vec};
+
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    enum Op {
+        Nop,
+        Push(i64),
+        Call { target: u32, args: Vec<u32> },
+    }
+
+    fn arb_op() -> impl Strategy<Value = Op> {
+        prop_oneof![
+            Just(Op::Nop),
+            any::<i64>().prop_map(Op::Push),
+            (any::<u32>(), any::<Vec<u32>>()).prop_map(|(target, args)| Op::Call { target, args }),
+        ]
+    }
+
+    #[test]
+    fn test_round_trip() {
+        run_round_trip(
+            (
+                prop::collection::vec(arb_op(), 0..4),
+                any::<BTreeMap<String, (u8, bool)>>(),
+            ),
+            arb_config(),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_differential() {
+        run_differential((
+            prop::collection::vec(arb_op(), 0..4),
+            any::<Option<(u128, char, String)>>(),
+        ))
+        .unwrap();
+        run_differential(any::<u8>()).unwrap();
+    }
+
+    #[test]
+    fn test_failures() {
+        let result = run_differential(any::<[u8; 3]>());
+        let Err(TestError::Fail(_, value)) = result else {
+            panic!("bytes in an array should pack");
+        };
+        assert_eq!([0; 3], value);
+        assert_eq!(
+            Err(Failure::Differs {
+                ours: vec![0x030201],
+                risc0: vec![1, 2, 3]
+            }),
+            check_differential(&[1u8, 2, 3])
+        );
+        assert_eq!(Ok(()), check_differential(&()));
+    }
+}
@@ -0,0 +1,209 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Spans for the `tracing` feature
+//!
+//! Each top-level value is serialized or deserialized in a `debug` span named
+//! `serialize` or `deserialize`, carrying the type name and the number of
+//! words, and the in-memory size of the value being serialized. Each struct is
+//! written or read in a nested `trace` span named `struct`, carrying the name
+//! of the struct, its number of fields and the words it took. Struct variants
+//! get no span of their own, since their deserializer doesn't know the name.
+
+use alloc::vec::Vec;
+
+use tracing::{field::Empty, span::EnteredSpan, Span};
+
+/// The span of serializing `value`, with the words left to record
+pub(crate) fn serialize_span<T: ?Sized>(value: &T) -> Span {
+    tracing::debug_span!(
+        "serialize",
+        ty = core::any::type_name::<T>(),
+        size = core::mem::size_of_val(value),
+        words = Empty,
+    )
+}
+
+/// The span of deserializing a `T` from `words`
+pub(crate) fn deserialize_span<T>(words: usize) -> Span {
+    tracing::debug_span!("deserialize", ty = core::any::type_name::<T>(), words)
+}
+
+/// The span of a struct, with the words left to record
+pub(crate) fn struct_span(name: &'static str, fields: usize) -> Span {
+    tracing::trace_span!("struct", name, fields, words = Empty)
+}
+
+/// The spans of the structs being serialized, innermost last
+///
+/// Serde hands back the Serializer between the start and the end of a struct,
+/// so the spans are kept here instead of in a scope.
+#[derive(Default)]
+pub(crate) struct StructSpans {
+    open: Vec<(EnteredSpan, usize)>,
+}
+
+impl StructSpans {
+    /// Enter the span of a struct starting at word `start`
+    pub(crate) fn enter(&mut self, name: &'static str, fields: usize, start: usize) {
+        self.open.push((struct_span(name, fields).entered(), start));
+    }
+
+    /// Record the words of the innermost struct, ending at word `end`, and
+    /// exit its span
+    pub(crate) fn exit(&mut self, end: usize) {
+        if let Some((span, start)) = self.open.pop() {
+            span.record("words", end - start);
+        }
+    }
+}
+
+impl Drop for StructSpans {
+    fn drop(&mut self) {
+        // Structs left open by an error exit from the innermost out.
+        while self.open.pop().is_some() {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{
+        format,
+        string::{String, ToString},
+        vec,
+        vec::Vec,
+    };
+    use std::sync::Mutex;
+
+    use serde::{Deserialize, Serialize};
+    use tracing::{
+        field::{Field, Visit},
+        span::{Attributes, Id, Record},
+        Event, Metadata, Subscriber,
+    };
+
+    use crate::{from_slice_compact, to_vec_compact_with_config, Config};
+
+    /// The name of a span and its fields
+    type SpanFields = (&'static str, Vec<(String, String)>);
+
+    /// Collects the fields of each span, in the order the spans were created
+    #[derive(Default)]
+    struct Spans(Mutex<Vec<SpanFields>>);
+
+    struct Fields<'a>(&'a mut Vec<(String, String)>);
+
+    impl Visit for Fields<'_> {
+        fn record_debug(&mut self, field: &Field, value: &dyn core::fmt::Debug) {
+            self.0
+                .push((field.name().to_string(), format!("{value:?}")));
+        }
+
+        fn record_str(&mut self, field: &Field, value: &str) {
+            self.0.push((field.name().to_string(), value.to_string()));
+        }
+    }
+
+    impl Subscriber for Spans {
+        fn enabled(&self, _: &Metadata) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &Attributes) -> Id {
+            let mut spans = self.0.lock().unwrap();
+            let mut fields = Vec::new();
+            span.record(&mut Fields(&mut fields));
+            spans.push((span.metadata().name(), fields));
+            Id::from_u64(spans.len() as u64)
+        }
+
+        fn record(&self, span: &Id, values: &Record) {
+            let mut spans = self.0.lock().unwrap();
+            let fields = &mut spans[span.into_u64() as usize - 1].1;
+            values.record(&mut Fields(fields));
+        }
+
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+        fn event(&self, _: &Event) {}
+        fn enter(&self, _: &Id) {}
+        fn exit(&self, _: &Id) {}
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Inner {
+        a: u32,
+        b: u8,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Outer {
+        inner: Inner,
+        tail: u64,
+    }
+
+    fn record<F: FnOnce()>(f: F) -> Vec<SpanFields> {
+        let spans = std::sync::Arc::new(Spans::default());
+        tracing::subscriber::with_default(spans.clone(), f);
+        let spans = spans.0.lock().unwrap();
+        spans.clone()
+    }
+
+    fn fields(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_spans() {
+        let outer = Outer {
+            inner: Inner { a: 1, b: 2 },
+            tail: 3,
+        };
+        let mut data = Vec::new();
+        let spans = record(|| data = to_vec_compact_with_config(&outer, Config::new()).unwrap());
+        let ty = core::any::type_name::<Outer>();
+        assert_eq!(
+            vec![
+                (
+                    "serialize",
+                    fields(&[("ty", ty), ("size", "16"), ("words", "4")])
+                ),
+                (
+                    "struct",
+                    fields(&[("name", "Outer"), ("fields", "2"), ("words", "4")])
+                ),
+                (
+                    "struct",
+                    fields(&[("name", "Inner"), ("fields", "2"), ("words", "2")])
+                ),
+            ],
+            spans
+        );
+
+        let spans = record(|| assert_eq!(outer, from_slice_compact::<Outer, _>(&data).unwrap()));
+        assert_eq!(
+            ("deserialize", fields(&[("ty", ty), ("words", "4")])),
+            spans[0]
+        );
+        assert_eq!(
+            (
+                "struct",
+                fields(&[("name", "Inner"), ("fields", "2"), ("words", "2")])
+            ),
+            spans[2]
+        );
+    }
+}
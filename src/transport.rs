@@ -0,0 +1,180 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::{string::String, vec::Vec};
+
+use super::{
+    buffer::{le_bytes_to_words, words_to_le_bytes},
+    err::{Error, Result},
+    WORD_SIZE,
+};
+
+const HEX_DIGITS: &[u8; 16] = b"0123456789abcdef";
+const BASE64_DIGITS: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn bytes_to_words(bytes: &[u8]) -> Result<Vec<u32>> {
+    le_bytes_to_words(bytes).ok_or(Error::DeserializeBadEncoding)
+}
+
+/// Encode words as a lowercase hex string
+///
+/// Each word is written as its four little-endian bytes, so the output
+/// matches the byte view of the buffer.
+pub fn encode_hex(words: &[u32]) -> String {
+    let mut out = String::with_capacity(words.len() * WORD_SIZE * 2);
+    for byte in words_to_le_bytes(words) {
+        out.push(HEX_DIGITS[(byte >> 4) as usize] as char);
+        out.push(HEX_DIGITS[(byte & 0xf) as usize] as char);
+    }
+    out
+}
+
+/// Decode a hex string produced by [encode_hex]
+///
+/// Accepts upper and lower case digits. The string must describe a whole
+/// number of words.
+pub fn decode_hex(s: &str) -> Result<Vec<u32>> {
+    fn digit(c: u8) -> Result<u8> {
+        match c {
+            b'0'..=b'9' => Ok(c - b'0'),
+            b'a'..=b'f' => Ok(c - b'a' + 10),
+            b'A'..=b'F' => Ok(c - b'A' + 10),
+            _ => Err(Error::DeserializeBadEncoding),
+        }
+    }
+
+    let s = s.as_bytes();
+    if !s.len().is_multiple_of(2) {
+        return Err(Error::DeserializeBadEncoding);
+    }
+    let bytes = s
+        .chunks_exact(2)
+        .map(|pair| Ok(digit(pair[0])? << 4 | digit(pair[1])?))
+        .collect::<Result<Vec<u8>>>()?;
+    bytes_to_words(&bytes)
+}
+
+/// Encode words as a padded base64 string using the standard alphabet
+///
+/// Each word is written as its four little-endian bytes.
+pub fn encode_base64(words: &[u32]) -> String {
+    let bytes = words_to_le_bytes(words);
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = (chunk[0] as u32) << 16
+            | (*chunk.get(1).unwrap_or(&0) as u32) << 8
+            | *chunk.get(2).unwrap_or(&0) as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64_DIGITS[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// Decode a padded base64 string produced by [encode_base64]
+///
+/// The string must describe a whole number of words.
+pub fn decode_base64(s: &str) -> Result<Vec<u32>> {
+    fn digit(c: u8) -> Result<u32> {
+        match c {
+            b'A'..=b'Z' => Ok((c - b'A') as u32),
+            b'a'..=b'z' => Ok((c - b'a' + 26) as u32),
+            b'0'..=b'9' => Ok((c - b'0' + 52) as u32),
+            b'+' => Ok(62),
+            b'/' => Ok(63),
+            _ => Err(Error::DeserializeBadEncoding),
+        }
+    }
+
+    let s = s.as_bytes();
+    if !s.len().is_multiple_of(4) {
+        return Err(Error::DeserializeBadEncoding);
+    }
+    let mut bytes = Vec::with_capacity(s.len() / 4 * 3);
+    for (i, chunk) in s.chunks_exact(4).enumerate() {
+        let last = i == s.len() / 4 - 1;
+        let padding = chunk.iter().rev().take_while(|&&c| c == b'=').count();
+        if padding > 2 || (padding > 0 && !last) {
+            return Err(Error::DeserializeBadEncoding);
+        }
+        let mut n = 0u32;
+        for &c in &chunk[..4 - padding] {
+            n = n << 6 | digit(c)?;
+        }
+        n <<= 6 * padding;
+        let decoded = [(n >> 16) as u8, (n >> 8) as u8, n as u8];
+        // Bits covered by the padding must be zero for a canonical encoding.
+        if decoded[3 - padding..].iter().any(|&b| b != 0) {
+            return Err(Error::DeserializeBadEncoding);
+        }
+        bytes.extend_from_slice(&decoded[..3 - padding]);
+    }
+    bytes_to_words(&bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::String, vec};
+
+    use super::*;
+    use crate::{from_slice_compact, to_vec_compact};
+
+    #[test]
+    fn test_hex() {
+        let words = [0x04030201, 0xdeadbeef];
+        assert_eq!("01020304efbeadde", encode_hex(&words));
+        assert_eq!(words.as_slice(), decode_hex("01020304EFBEADDE").unwrap());
+        assert_eq!(Err(Error::DeserializeBadEncoding), decode_hex("010203"));
+        assert_eq!(Err(Error::DeserializeBadEncoding), decode_hex("0102030g"));
+    }
+
+    #[test]
+    fn test_base64() {
+        for (words, expected) in [
+            (vec![], ""),
+            (vec![0x04030201], "AQIDBA=="),
+            (vec![0x04030201, 0xdeadbeef], "AQIDBO++rd4="),
+            (vec![1, 2, 3], "AQAAAAIAAAADAAAA"),
+        ] {
+            assert_eq!(expected, encode_base64(&words));
+            assert_eq!(words, decode_base64(expected).unwrap());
+        }
+        assert_eq!(Err(Error::DeserializeBadEncoding), decode_base64("AQID"));
+        assert_eq!(
+            Err(Error::DeserializeBadEncoding),
+            decode_base64("AQIDBB==")
+        );
+        assert_eq!(
+            Err(Error::DeserializeBadEncoding),
+            decode_base64("AQ==AQID")
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let input = (String::from("transport"), vec![1u8, 2, 3], 7u64);
+        let data = to_vec_compact(&input).unwrap();
+        let output: (String, Vec<u8>, u64) =
+            from_slice_compact(&decode_hex(&encode_hex(&data)).unwrap()).unwrap();
+        assert_eq!(input, output);
+        let output: (String, Vec<u8>, u64) =
+            from_slice_compact(&decode_base64(&encode_base64(&data)).unwrap()).unwrap();
+        assert_eq!(input, output);
+    }
+}
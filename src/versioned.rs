@@ -0,0 +1,291 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Self-identifying encoding with a versioned header
+//!
+//! [to_vec_versioned] prefixes the encoding with two words: [MAGIC], and the
+//! format [VERSION] in the low half of the second word with the [flags] that
+//! describe the layout in the high half. [from_slice_versioned] reads the
+//! configuration from the header instead of needing it from the caller, and
+//! fails on data from an unknown version or with a layout it doesn't support,
+//! rather than misreading it.
+//! ```rust
+//! use l2r0_small_serde::{from_slice_versioned, to_vec_versioned, Config};
+//!
+//! let config = Config::new().field_count(true);
+//! let data = to_vec_versioned(&(1u32, "one"), config).unwrap();
+//! let output: (u32, String) = from_slice_versioned(&data).unwrap();
+//! assert_eq!((1, "one".to_string()), output);
+//! ```
+//!
+//! [Config::pad_to] isn't recorded. The padding follows the encoding and is
+//! ignored when decoding.
+//!
+//! [Config::pad_to]: crate::Config::pad_to
+
+use alloc::vec::Vec;
+
+use bytemuck::Pod;
+use serde::de::DeserializeOwned;
+
+use super::{
+    err::{Error, Result},
+    from_slice_compact_with_config, to_vec_compact_with_config, Config, VariantTag,
+};
+
+/// First word of a versioned encoding, the bytes `L2R0`
+pub const MAGIC: u32 = u32::from_le_bytes(*b"L2R0");
+
+/// Version of the format written by this crate
+pub const VERSION: u16 = 1;
+
+/// Layout flags of the versioned header
+pub mod flags {
+    /// Bytes and bools pack four per word inside composites
+    ///
+    /// Always set by version 1.
+    pub const COMPACT_U8: u16 = 1 << 0;
+    /// Integers and lengths are variable-length encoded
+    ///
+    /// Reserved for a future encoding, and never set by version 1.
+    pub const VARINT: u16 = 1 << 1;
+    /// Written with [Config::self_describing](crate::Config::self_describing)
+    pub const SELF_DESCRIBING: u16 = 1 << 2;
+    /// Written with [Config::field_count](crate::Config::field_count)
+    pub const FIELD_COUNT: u16 = 1 << 3;
+    /// Written with [Config::field_sizes](crate::Config::field_sizes)
+    pub const FIELD_SIZES: u16 = 1 << 4;
+    /// Written with [Config::intern_strings](crate::Config::intern_strings)
+    pub const INTERN_STRINGS: u16 = 1 << 5;
+    /// Variant tags are written as [VariantTag::Byte](crate::VariantTag::Byte)
+    pub const VARIANT_BYTE: u16 = 1 << 6;
+    /// Variant tags are written as
+    /// [VariantTag::NameHash](crate::VariantTag::NameHash)
+    pub const VARIANT_NAME_HASH: u16 = 1 << 7;
+    /// Written with [Config::byte_option_tags](crate::Config::byte_option_tags)
+    pub const BYTE_OPTION_TAGS: u16 = 1 << 8;
+    /// Written with [Config::pack_top_level](crate::Config::pack_top_level)
+    pub const PACK_TOP_LEVEL: u16 = 1 << 9;
+    /// Written with [Config::human_readable](crate::Config::human_readable)
+    pub const HUMAN_READABLE: u16 = 1 << 10;
+    /// Written with [Config::big_endian](crate::Config::big_endian)
+    pub const BIG_ENDIAN: u16 = 1 << 11;
+}
+
+/// The header of a versioned encoding
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Header {
+    /// The format version
+    pub version: u16,
+    /// The layout [flags]
+    pub flags: u16,
+}
+
+impl Header {
+    /// The header of the encoding of this version with `config`
+    pub fn new(config: Config) -> Self {
+        let mut flags = flags::COMPACT_U8;
+        for (set, flag) in [
+            (config.self_describing, flags::SELF_DESCRIBING),
+            (config.field_count, flags::FIELD_COUNT),
+            (config.field_sizes, flags::FIELD_SIZES),
+            (config.intern_strings, flags::INTERN_STRINGS),
+            (config.variant_tag == VariantTag::Byte, flags::VARIANT_BYTE),
+            (
+                config.variant_tag == VariantTag::NameHash,
+                flags::VARIANT_NAME_HASH,
+            ),
+            (config.byte_option_tags, flags::BYTE_OPTION_TAGS),
+            (config.pack_top_level, flags::PACK_TOP_LEVEL),
+            (config.human_readable, flags::HUMAN_READABLE),
+            (config.big_endian, flags::BIG_ENDIAN),
+        ] {
+            if set {
+                flags |= flag;
+            }
+        }
+        Header {
+            version: VERSION,
+            flags,
+        }
+    }
+
+    /// The configuration to decode the encoding with
+    ///
+    /// Fails with [Error::DeserializeUnsupportedVersion] if the version isn't
+    /// [VERSION], and with [Error::DeserializeBadHeader] if the flags describe
+    /// a layout this version doesn't have.
+    pub fn config(&self) -> Result<Config> {
+        if self.version != VERSION {
+            return Err(Error::DeserializeUnsupportedVersion {
+                version: self.version,
+            });
+        }
+        let has = |flag| self.flags & flag != 0;
+        let variant_tag = match (has(flags::VARIANT_BYTE), has(flags::VARIANT_NAME_HASH)) {
+            (false, false) => VariantTag::Word,
+            (true, false) => VariantTag::Byte,
+            (false, true) => VariantTag::NameHash,
+            (true, true) => return Err(Error::DeserializeBadHeader),
+        };
+        let known = flags::COMPACT_U8
+            | flags::SELF_DESCRIBING
+            | flags::FIELD_COUNT
+            | flags::FIELD_SIZES
+            | flags::INTERN_STRINGS
+            | flags::VARIANT_BYTE
+            | flags::VARIANT_NAME_HASH
+            | flags::BYTE_OPTION_TAGS
+            | flags::PACK_TOP_LEVEL
+            | flags::HUMAN_READABLE
+            | flags::BIG_ENDIAN;
+        if !has(flags::COMPACT_U8) || self.flags & !known != 0 {
+            return Err(Error::DeserializeBadHeader);
+        }
+        Ok(Config::new()
+            .variant_tag(variant_tag)
+            .self_describing(has(flags::SELF_DESCRIBING))
+            .field_count(has(flags::FIELD_COUNT))
+            .field_sizes(has(flags::FIELD_SIZES))
+            .intern_strings(has(flags::INTERN_STRINGS))
+            .byte_option_tags(has(flags::BYTE_OPTION_TAGS))
+            .pack_top_level(has(flags::PACK_TOP_LEVEL))
+            .human_readable(has(flags::HUMAN_READABLE))
+            .big_endian(has(flags::BIG_ENDIAN)))
+    }
+
+    /// The words of the header
+    pub fn to_words(&self) -> [u32; 2] {
+        [MAGIC, self.version as u32 | (self.flags as u32) << 16]
+    }
+
+    /// Read the header at the start of `words`
+    ///
+    /// Fails with [Error::DeserializeBadHeader] if `words` doesn't start with
+    /// [MAGIC].
+    pub fn from_words(words: &[u32]) -> Result<Self> {
+        match words {
+            [MAGIC, word, ..] => Ok(Header {
+                version: *word as u16,
+                flags: (*word >> 16) as u16,
+            }),
+            [_, _, ..] => Err(Error::DeserializeBadHeader),
+            _ => Err(Error::DeserializeUnexpectedEnd {
+                needed: 2,
+                available: Some(words.len()),
+            }),
+        }
+    }
+}
+
+/// Serialize to a vector of u32 words preceded by a versioned header
+pub fn to_vec_versioned<T>(value: &T, config: Config) -> Result<Vec<u32>>
+where
+    T: serde::Serialize + ?Sized,
+{
+    let mut vec = Header::new(config).to_words().to_vec();
+    vec.extend(to_vec_compact_with_config(value, config)?);
+    Ok(vec)
+}
+
+/// Deserialize a slice with a versioned header, using the configuration the
+/// header records.
+///
+/// `slice` must have been produced by [to_vec_versioned].
+pub fn from_slice_versioned<T: DeserializeOwned, P: Pod>(slice: &[P]) -> Result<T> {
+    let words = bytemuck::allocation::pod_collect_to_vec::<P, u32>(slice);
+    let config = Header::from_words(&words)?.config()?;
+    from_slice_compact_with_config(&words[2..], config)
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::String, vec::Vec};
+
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Event {
+        Start,
+        Stop { code: u8, reason: String },
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let input = Vec::from([
+            Event::Start,
+            Event::Stop {
+                code: 3,
+                reason: "done".into(),
+            },
+        ]);
+        for config in [
+            Config::new(),
+            Config::new().self_describing(true).field_sizes(true),
+            Config::new()
+                .variant_tag(VariantTag::NameHash)
+                .intern_strings(true),
+            Config::new().variant_tag(VariantTag::Byte).pad_to(8),
+        ] {
+            let data = to_vec_versioned(&input, config).unwrap();
+            let header = Header::from_words(&data).unwrap();
+            assert_eq!(
+                Config {
+                    pad_to: 0,
+                    ..config
+                },
+                header.config().unwrap()
+            );
+            assert_eq!(
+                to_vec_compact_with_config(&input, config).unwrap(),
+                data[2..]
+            );
+            let output: Vec<Event> = from_slice_versioned(&data).unwrap();
+            assert_eq!(input, output);
+        }
+        let data = to_vec_versioned(&7u32, Config::new()).unwrap();
+        assert_eq!([MAGIC, 1 | 1 << 16, 7], data.as_slice());
+    }
+
+    #[test]
+    fn test_rejected() {
+        let data = to_vec_versioned(&7u32, Config::new()).unwrap();
+        let with_header = |word: u32| [MAGIC, word, 7];
+        assert_eq!(
+            Err(Error::DeserializeUnsupportedVersion { version: 2 }),
+            from_slice_versioned::<u32, _>(&with_header(data[1] + 1))
+        );
+        assert_eq!(
+            Err(Error::DeserializeBadHeader),
+            from_slice_versioned::<u32, _>(&with_header(data[1] | (flags::VARINT as u32) << 16))
+        );
+        assert_eq!(
+            Err(Error::DeserializeBadHeader),
+            from_slice_versioned::<u32, _>(&with_header(1))
+        );
+        assert_eq!(
+            Err(Error::DeserializeBadHeader),
+            from_slice_versioned::<u32, _>(&data[1..])
+        );
+        assert_eq!(
+            Err(Error::DeserializeUnexpectedEnd {
+                needed: 2,
+                available: Some(1)
+            }),
+            from_slice_versioned::<u32, _>(&data[..1])
+        );
+    }
+}
@@ -0,0 +1,119 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Encoding to and from byte buffers for WebAssembly hosts
+//!
+//! JavaScript hands binary data around as a `Uint8Array`, which
+//! `wasm-bindgen` passes to Rust as a `&[u8]` or `Vec<u8>` with no alignment
+//! guarantee. The functions here encode to the little-endian bytes of the
+//! words, and decode from bytes at any alignment, such as a journal fetched by
+//! a browser-based explorer. They are meant to be called from concrete
+//! `#[wasm_bindgen]` exports:
+//! ```ignore
+//! use wasm_bindgen::prelude::*;
+//!
+//! #[wasm_bindgen]
+//! pub fn encode_input(id: u32, name: String) -> Result<Vec<u8>, JsError> {
+//!     Ok(l2r0_small_serde::wasm::to_vec(&Input { id, name })?)
+//! }
+//!
+//! #[wasm_bindgen]
+//! pub fn decode_output(journal: &[u8]) -> Result<u64, JsError> {
+//!     Ok(l2r0_small_serde::wasm::from_slice::<Output>(journal)?.total)
+//! }
+//! ```
+
+use alloc::vec::Vec;
+
+use serde::de::DeserializeOwned;
+
+use super::{
+    buffer::{le_bytes_to_words, words_to_le_bytes},
+    err::{Error, Result},
+    from_slice_compact_with_config, to_vec_compact_with_config, Config, WORD_SIZE,
+};
+
+/// Serialize to the little-endian bytes of the words
+pub fn to_vec<T>(value: &T) -> Result<Vec<u8>>
+where
+    T: serde::Serialize + ?Sized,
+{
+    to_vec_with_config(value, Config::default())
+}
+
+/// Serialize to the little-endian bytes of the words using the given
+/// configuration
+pub fn to_vec_with_config<T>(value: &T, config: Config) -> Result<Vec<u8>>
+where
+    T: serde::Serialize + ?Sized,
+{
+    let words = to_vec_compact_with_config(value, config)?;
+    Ok(words_to_le_bytes(&words))
+}
+
+/// Deserialize the little-endian bytes of words, at any alignment
+///
+/// Fails with [Error::DeserializeUnexpectedEnd] if `bytes` ends with a
+/// partial word.
+pub fn from_slice<T: DeserializeOwned>(bytes: &[u8]) -> Result<T> {
+    from_slice_with_config(bytes, Config::default())
+}
+
+/// Deserialize the little-endian bytes of words, at any alignment, using the
+/// given configuration
+pub fn from_slice_with_config<T: DeserializeOwned>(bytes: &[u8], config: Config) -> Result<T> {
+    let words = le_bytes_to_words(bytes).ok_or(Error::DeserializeUnexpectedEnd {
+        needed: bytes.len().div_ceil(WORD_SIZE),
+        available: Some(bytes.len() / WORD_SIZE),
+    })?;
+    from_slice_compact_with_config(&words, config)
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::String, vec::Vec};
+
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let input = (7u32, String::from("explorer"), [1u8, 2, 3]);
+        let bytes = to_vec(&input).unwrap();
+        let words = crate::to_vec_compact(&input).unwrap();
+        assert_eq!(bytemuck::cast_slice::<u32, u8>(&words), bytes.as_slice());
+
+        // Decode from an offset that isn't word-aligned.
+        let mut buffer = Vec::from([0u8]);
+        buffer.extend_from_slice(&bytes);
+        let output: (u32, String, [u8; 3]) = from_slice(&buffer[1..]).unwrap();
+        assert_eq!(input, output);
+
+        let config = Config::new().self_describing(true);
+        let bytes = to_vec_with_config(&input, config).unwrap();
+        let output: (u32, String, [u8; 3]) = from_slice_with_config(&bytes, config).unwrap();
+        assert_eq!(input, output);
+    }
+
+    #[test]
+    fn test_partial_word() {
+        let bytes = to_vec(&(1u32, 2u32)).unwrap();
+        assert_eq!(
+            Err(Error::DeserializeUnexpectedEnd {
+                needed: 2,
+                available: Some(1)
+            }),
+            from_slice::<(u32, u32)>(&bytes[..7])
+        );
+    }
+}
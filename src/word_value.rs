@@ -0,0 +1,515 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A dynamic representation of encoded data
+//!
+//! [Value] mirrors the layout of the compact encoding rather than a Rust type,
+//! so tools can inspect and modify encoded data and re-emit it without the
+//! type that produced it. Serializing a [Value] writes the same words as the
+//! value it describes.
+//!
+//! The encoding is not self-describing, so decoding needs a [Shape] telling
+//! which layout to expect at every position:
+//! ```rust
+//! use l2r0_small_serde::{
+//!     to_vec_compact,
+//!     word_value::{Shape, Value},
+//! };
+//!
+//! let data = to_vec_compact(&(7u32, vec![1u8, 2])).unwrap();
+//! let shape = Shape::Tuple(vec![Shape::Word, Shape::Seq(Box::new(Shape::Byte))]);
+//! let mut value = Value::from_slice(&data, &shape).unwrap();
+//! if let Value::Tuple(fields) = &mut value {
+//!     fields[0] = Value::Word(8);
+//! }
+//! assert_eq!(to_vec_compact(&(8u32, vec![1u8, 2])).unwrap(), to_vec_compact(&value).unwrap());
+//! ```
+//!
+//! [Value] also implements `Deserialize` for self-describing formats, which
+//! allows loading it from e.g. JSON. The compact [Deserializer] itself does not
+//! support this and needs [Value::from_slice].
+
+use alloc::{boxed::Box, string::String, vec::Vec};
+use core::fmt;
+
+use serde::{
+    de::{DeserializeSeed, MapAccess, SeqAccess, Unexpected, Visitor},
+    ser::{SerializeMap, SerializeSeq, SerializeTuple},
+    Deserialize, Serialize,
+};
+
+use super::{
+    err::{Error, Result},
+    Deserializer,
+};
+
+/// An encoded value of unknown type
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    /// A value that occupies no words, such as `()` or a unit struct
+    Unit,
+    /// A `bool`, packed like a byte
+    Bool(bool),
+    /// A `u8`, packed four per word inside composites
+    Byte(u8),
+    /// Any value encoded as a single word, such as `u32`, `i16` or `char`
+    Word(u32),
+    /// Any value encoded as two words, such as `u64` or `f64`
+    DWord(u64),
+    /// A string
+    Str(String),
+    /// A byte run, as written for `serialize_bytes`
+    Bytes(Vec<u8>),
+    /// An `Option`
+    Option(Option<Box<Value>>),
+    /// A length-prefixed sequence
+    Seq(Vec<Value>),
+    /// A fixed number of values without a length prefix, such as a tuple,
+    /// array or struct
+    Tuple(Vec<Value>),
+    /// A length-prefixed sequence of key-value pairs
+    Map(Vec<(Value, Value)>),
+    /// An enum variant: a word tag followed by its contents
+    ///
+    /// The contents of unit variants are [Value::Unit], those of tuple and
+    /// struct variants are a [Value::Tuple].
+    Variant(u32, Box<Value>),
+}
+
+/// The layout of a [Value], needed to decode it
+///
+/// Shapes can themselves be stored with serde, e.g. as JSON, for tools that
+/// load them at runtime.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum Shape {
+    /// Decodes as [Value::Unit]
+    Unit,
+    /// Decodes as [Value::Bool]
+    Bool,
+    /// Decodes as [Value::Byte]
+    Byte,
+    /// Decodes as [Value::Word]
+    Word,
+    /// Decodes as [Value::DWord]
+    DWord,
+    /// Decodes as [Value::Str]
+    Str,
+    /// Decodes as [Value::Bytes]
+    Bytes,
+    /// Decodes as [Value::Option]
+    Option(Box<Shape>),
+    /// Decodes as [Value::Seq] with elements of the given shape
+    Seq(Box<Shape>),
+    /// Decodes as [Value::Tuple] with fields of the given shapes
+    Tuple(Vec<Shape>),
+    /// Decodes as [Value::Map] with keys and values of the given shapes
+    Map(Box<Shape>, Box<Shape>),
+    /// Decodes as [Value::Variant], with the contents of each variant indexed
+    /// by its tag
+    Enum(Vec<Shape>),
+}
+
+impl Value {
+    /// Decode `slice` as a value of the given shape
+    ///
+    /// `slice` must have been serialized with the default configuration.
+    pub fn from_slice(slice: &[u32], shape: &Shape) -> Result<Value> {
+        let mut deserializer = Deserializer::from_words(slice);
+        let value = shape.deserialize(&mut deserializer)?;
+        deserializer.finish()?;
+        Ok(value)
+    }
+}
+
+impl Serialize for Value {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> core::result::Result<S::Ok, S::Error> {
+        match self {
+            Value::Unit => serializer.serialize_unit(),
+            Value::Bool(v) => serializer.serialize_bool(*v),
+            Value::Byte(v) => serializer.serialize_u8(*v),
+            Value::Word(v) => serializer.serialize_u32(*v),
+            Value::DWord(v) => serializer.serialize_u64(*v),
+            Value::Str(v) => serializer.serialize_str(v),
+            Value::Bytes(v) => serializer.serialize_bytes(v),
+            Value::Option(None) => serializer.serialize_none(),
+            Value::Option(Some(v)) => serializer.serialize_some(v),
+            Value::Seq(values) => {
+                let mut seq = serializer.serialize_seq(Some(values.len()))?;
+                for value in values {
+                    seq.serialize_element(value)?;
+                }
+                seq.end()
+            }
+            Value::Tuple(values) => {
+                let mut tuple = serializer.serialize_tuple(values.len())?;
+                for value in values {
+                    tuple.serialize_element(value)?;
+                }
+                tuple.end()
+            }
+            Value::Map(entries) => {
+                let mut map = serializer.serialize_map(Some(entries.len()))?;
+                for (key, value) in entries {
+                    map.serialize_entry(key, value)?;
+                }
+                map.end()
+            }
+            Value::Variant(tag, contents) => {
+                let mut tuple = serializer.serialize_tuple(2)?;
+                tuple.serialize_element(tag)?;
+                tuple.serialize_element(contents)?;
+                tuple.end()
+            }
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Value {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> core::result::Result<Value, D::Error> {
+        deserializer.deserialize_any(ValueVisitor)
+    }
+}
+
+struct ValueVisitor;
+
+impl<'de> Visitor<'de> for ValueVisitor {
+    type Value = Value;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("any value")
+    }
+
+    fn visit_bool<E>(self, v: bool) -> core::result::Result<Value, E> {
+        Ok(Value::Bool(v))
+    }
+
+    fn visit_u8<E>(self, v: u8) -> core::result::Result<Value, E> {
+        Ok(Value::Byte(v))
+    }
+
+    fn visit_u32<E>(self, v: u32) -> core::result::Result<Value, E> {
+        Ok(Value::Word(v))
+    }
+
+    fn visit_i32<E>(self, v: i32) -> core::result::Result<Value, E> {
+        Ok(Value::Word(v as u32))
+    }
+
+    fn visit_f32<E>(self, v: f32) -> core::result::Result<Value, E> {
+        Ok(Value::Word(v.to_bits()))
+    }
+
+    fn visit_char<E>(self, v: char) -> core::result::Result<Value, E> {
+        Ok(Value::Word(v as u32))
+    }
+
+    fn visit_u64<E>(self, v: u64) -> core::result::Result<Value, E> {
+        Ok(Value::DWord(v))
+    }
+
+    fn visit_i64<E>(self, v: i64) -> core::result::Result<Value, E> {
+        Ok(Value::DWord(v as u64))
+    }
+
+    fn visit_f64<E>(self, v: f64) -> core::result::Result<Value, E> {
+        Ok(Value::DWord(v.to_bits()))
+    }
+
+    fn visit_str<E>(self, v: &str) -> core::result::Result<Value, E> {
+        Ok(Value::Str(v.into()))
+    }
+
+    fn visit_string<E>(self, v: String) -> core::result::Result<Value, E> {
+        Ok(Value::Str(v))
+    }
+
+    fn visit_bytes<E>(self, v: &[u8]) -> core::result::Result<Value, E> {
+        Ok(Value::Bytes(v.into()))
+    }
+
+    fn visit_byte_buf<E>(self, v: Vec<u8>) -> core::result::Result<Value, E> {
+        Ok(Value::Bytes(v))
+    }
+
+    fn visit_unit<E>(self) -> core::result::Result<Value, E> {
+        Ok(Value::Unit)
+    }
+
+    fn visit_none<E>(self) -> core::result::Result<Value, E> {
+        Ok(Value::Option(None))
+    }
+
+    fn visit_some<D: serde::Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> core::result::Result<Value, D::Error> {
+        Ok(Value::Option(Some(Box::new(Value::deserialize(
+            deserializer,
+        )?))))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> core::result::Result<Value, A::Error> {
+        let mut values = Vec::with_capacity(crate::cautious_capacity(seq.size_hint()));
+        while let Some(value) = seq.next_element()? {
+            values.push(value);
+        }
+        Ok(Value::Seq(values))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> core::result::Result<Value, A::Error> {
+        let mut entries = Vec::with_capacity(crate::cautious_capacity(map.size_hint()));
+        while let Some(entry) = map.next_entry()? {
+            entries.push(entry);
+        }
+        Ok(Value::Map(entries))
+    }
+}
+
+/// Decodes a [Value] of the given shape
+impl<'de> DeserializeSeed<'de> for &Shape {
+    type Value = Value;
+
+    fn deserialize<D: serde::Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> core::result::Result<Value, D::Error> {
+        match self {
+            Shape::Unit => <()>::deserialize(deserializer).map(|_| Value::Unit),
+            Shape::Bool => bool::deserialize(deserializer).map(Value::Bool),
+            Shape::Byte => u8::deserialize(deserializer).map(Value::Byte),
+            Shape::Word => u32::deserialize(deserializer).map(Value::Word),
+            Shape::DWord => u64::deserialize(deserializer).map(Value::DWord),
+            Shape::Str => String::deserialize(deserializer).map(Value::Str),
+            Shape::Bytes => deserializer.deserialize_byte_buf(ShapeVisitor(self, self)),
+            Shape::Option(shape) => deserializer.deserialize_option(ShapeVisitor(self, shape)),
+            Shape::Seq(shape) => deserializer.deserialize_seq(ShapeVisitor(self, shape)),
+            Shape::Tuple(shapes) => {
+                deserializer.deserialize_tuple(shapes.len(), ShapeVisitor(self, self))
+            }
+            Shape::Map(key, _) => deserializer.deserialize_map(ShapeVisitor(self, key)),
+            Shape::Enum(_) => deserializer.deserialize_tuple(2, ShapeVisitor(self, self)),
+        }
+    }
+}
+
+/// Visits the contents of a composite [Shape]
+///
+/// Holds the composite itself and the shape of its elements, where there is
+/// a single one.
+struct ShapeVisitor<'a>(&'a Shape, &'a Shape);
+
+impl<'de> Visitor<'de> for ShapeVisitor<'_> {
+    type Value = Value;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "a value of shape {:?}", self.0)
+    }
+
+    fn visit_bytes<E>(self, v: &[u8]) -> core::result::Result<Value, E> {
+        Ok(Value::Bytes(v.into()))
+    }
+
+    fn visit_byte_buf<E>(self, v: Vec<u8>) -> core::result::Result<Value, E> {
+        Ok(Value::Bytes(v))
+    }
+
+    fn visit_none<E>(self) -> core::result::Result<Value, E> {
+        Ok(Value::Option(None))
+    }
+
+    fn visit_some<D: serde::Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> core::result::Result<Value, D::Error> {
+        let value = self.1.deserialize(deserializer)?;
+        Ok(Value::Option(Some(Box::new(value))))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> core::result::Result<Value, A::Error> {
+        use serde::de::Error as _;
+
+        let missing = || A::Error::invalid_length(0, &"more elements");
+        match self.0 {
+            // Formats without a byte type, such as JSON, give bytes as a
+            // sequence of numbers.
+            Shape::Bytes => {
+                let mut bytes = Vec::with_capacity(crate::cautious_capacity(seq.size_hint()));
+                while let Some(byte) = seq.next_element()? {
+                    bytes.push(byte);
+                }
+                Ok(Value::Bytes(bytes))
+            }
+            Shape::Seq(shape) => {
+                let mut values = Vec::with_capacity(crate::cautious_capacity(seq.size_hint()));
+                while let Some(value) = seq.next_element_seed(&**shape)? {
+                    values.push(value);
+                }
+                Ok(Value::Seq(values))
+            }
+            Shape::Tuple(shapes) => {
+                let mut values = Vec::with_capacity(shapes.len());
+                for shape in shapes {
+                    values.push(seq.next_element_seed(shape)?.ok_or_else(missing)?);
+                }
+                Ok(Value::Tuple(values))
+            }
+            Shape::Enum(shapes) => {
+                let tag: u32 = seq.next_element()?.ok_or_else(missing)?;
+                let shape = shapes
+                    .get(tag as usize)
+                    .ok_or_else(|| A::Error::custom(Error::DeserializeBadVariant))?;
+                let contents = seq.next_element_seed(shape)?.ok_or_else(missing)?;
+                Ok(Value::Variant(tag, Box::new(contents)))
+            }
+            _ => Err(A::Error::invalid_type(Unexpected::Seq, &self)),
+        }
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> core::result::Result<Value, A::Error> {
+        use serde::de::Error as _;
+
+        let Shape::Map(key, value) = self.0 else {
+            return Err(A::Error::invalid_type(Unexpected::Map, &self));
+        };
+        let mut entries = Vec::with_capacity(crate::cautious_capacity(map.size_hint()));
+        while let Some(entry) = map.next_entry_seed(&**key, &**value)? {
+            entries.push(entry);
+        }
+        Ok(Value::Map(entries))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{collections::BTreeMap, string::String, vec, vec::Vec};
+
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+    use crate::to_vec_compact;
+
+    #[derive(Serialize, Deserialize)]
+    enum Op {
+        Nop,
+        Push(u64),
+        Call { target: u32, flags: [u8; 3] },
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct Program {
+        name: String,
+        ops: Vec<Op>,
+        labels: BTreeMap<String, u32>,
+        entry: Option<u8>,
+    }
+
+    fn program_shape() -> Shape {
+        Shape::Tuple(vec![
+            Shape::Str,
+            Shape::Seq(Box::new(Shape::Enum(vec![
+                Shape::Unit,
+                Shape::DWord,
+                Shape::Tuple(vec![
+                    Shape::Word,
+                    Shape::Tuple(vec![Shape::Byte, Shape::Byte, Shape::Byte]),
+                ]),
+            ]))),
+            Shape::Map(Box::new(Shape::Str), Box::new(Shape::Word)),
+            Shape::Option(Box::new(Shape::Byte)),
+        ])
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let input = Program {
+            name: "main".into(),
+            ops: vec![
+                Op::Push(1 << 40),
+                Op::Nop,
+                Op::Call {
+                    target: 9,
+                    flags: [1, 2, 3],
+                },
+            ],
+            labels: BTreeMap::from([("start".into(), 0), ("end".into(), 3)]),
+            entry: Some(5),
+        };
+        let data = to_vec_compact(&input).unwrap();
+        let value = Value::from_slice(&data, &program_shape()).unwrap();
+        let Value::Tuple(fields) = &value else {
+            panic!("expected a tuple");
+        };
+        assert_eq!(Value::Str("main".into()), fields[0]);
+        assert_eq!(
+            Value::Seq(vec![
+                Value::Variant(1, Box::new(Value::DWord(1 << 40))),
+                Value::Variant(0, Box::new(Value::Unit)),
+                Value::Variant(
+                    2,
+                    Box::new(Value::Tuple(vec![
+                        Value::Word(9),
+                        Value::Tuple(vec![Value::Byte(1), Value::Byte(2), Value::Byte(3)]),
+                    ]))
+                ),
+            ]),
+            fields[1]
+        );
+        assert_eq!(data, to_vec_compact(&value).unwrap());
+    }
+
+    #[test]
+    fn test_bad_variant() {
+        let data = to_vec_compact(&Op::Push(1)).unwrap();
+        let shape = Shape::Enum(vec![Shape::Unit]);
+        assert!(Value::from_slice(&data, &shape).is_err());
+    }
+
+    #[test]
+    fn test_unexpected_end() {
+        let data = to_vec_compact(&(String::from("a"), 1u64)).unwrap();
+        let shape = Shape::Tuple(vec![Shape::Str, Shape::DWord, Shape::Word]);
+        assert_eq!(
+            Err(Error::DeserializeUnexpectedEnd {
+                needed: 1,
+                available: Some(0)
+            }),
+            Value::from_slice(&data, &shape)
+        );
+    }
+
+    #[test]
+    fn test_huge_length() {
+        let shape = Shape::Seq(Box::new(Shape::Word));
+        assert!(Value::from_slice(&[0x0fffffff], &shape).is_err());
+        let shape = Shape::Map(Box::new(Shape::Word), Box::new(Shape::Word));
+        assert!(Value::from_slice(&[0x0fffffff], &shape).is_err());
+    }
+
+    #[test]
+    fn test_mismatched_visit() {
+        use serde::de::value::{self, MapDeserializer, SeqDeserializer};
+
+        let seq = SeqDeserializer::<_, value::Error>::new([1u32].into_iter());
+        let shape = Shape::Option(Box::new(Shape::Word));
+        assert!(DeserializeSeed::deserialize(&shape, seq).is_err());
+        let map = MapDeserializer::<_, value::Error>::new([(1u32, 2u32)].into_iter());
+        let shape = Shape::Seq(Box::new(Shape::Word));
+        assert!(DeserializeSeed::deserialize(&shape, map).is_err());
+    }
+}
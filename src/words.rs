@@ -0,0 +1,265 @@
+// Copyright 2024 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Bulk decoding of vectors of word-sized values
+//!
+//! For use with `#[serde(with = "l2r0_small_serde::words")]` on `Vec<T>`
+//! fields where `T` is [Pod] and serializes as its little-endian memory
+//! representation, such as `u32`, `i64`, `f32` or arrays of them. The encoding
+//! is the same as that of a plain `Vec<T>`, but decoding with a [Deserializer]
+//! copies the words in bulk instead of visiting every element.
+//! ```rust
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct Witness {
+//!     #[serde(with = "l2r0_small_serde::words")]
+//!     trace: Vec<u32>,
+//! }
+//! ```
+//!
+//! The [borrowed] module decodes `&[u32]` without copying.
+//!
+//! [Deserializer]: crate::Deserializer
+
+use alloc::vec::Vec;
+use core::{fmt, marker::PhantomData};
+
+use bytemuck::Pod;
+use serde::{de::Visitor, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::WORD_SIZE;
+
+/// Names of the newtype structs requesting words in bulk, by the number of
+/// words in an element
+const NAMES: [&str; 4] = [
+    "$l2r0_small_serde::words1",
+    "$l2r0_small_serde::words2",
+    "$l2r0_small_serde::words3",
+    "$l2r0_small_serde::words4",
+];
+
+/// Name of the newtype struct requesting borrowed words
+pub(crate) const BORROWED: &str = "$l2r0_small_serde::borrowed";
+
+/// Number of words in an element of a bulk request named `name`
+pub(crate) fn element_words(name: &str) -> Option<usize> {
+    NAMES.iter().position(|&n| n == name).map(|i| i + 1)
+}
+
+/// Serialize `values` like a `Vec<T>`
+pub fn serialize<T, S>(values: &[T], serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: Serialize,
+    S: Serializer,
+{
+    serializer.collect_seq(values)
+}
+
+/// Deserialize a `Vec<T>`, copying the words in bulk where possible
+pub fn deserialize<'de, T, D>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    T: Pod + Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    let size = core::mem::size_of::<T>();
+    let name = match size.is_multiple_of(WORD_SIZE) {
+        true => (size / WORD_SIZE).checked_sub(1).and_then(|i| NAMES.get(i)),
+        false => None,
+    };
+    match name {
+        Some(name) if cfg!(target_endian = "little") => {
+            deserializer.deserialize_newtype_struct(name, WordsVisitor(PhantomData))
+        }
+        _ => Vec::deserialize(deserializer),
+    }
+}
+
+struct WordsVisitor<T>(PhantomData<T>);
+
+impl<'de, T: Pod + Deserialize<'de>> Visitor<'de> for WordsVisitor<T> {
+    type Value = Vec<T>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a sequence of word-sized values")
+    }
+
+    /// Receives the words of all elements from a [Deserializer](crate::Deserializer)
+    fn visit_bytes<E: serde::de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+        Ok(bytemuck::allocation::pod_collect_to_vec(v))
+    }
+
+    /// Falls back to decoding elements one by one for other deserializers
+    fn visit_newtype_struct<D: Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> Result<Self::Value, D::Error> {
+        Vec::deserialize(deserializer)
+    }
+}
+
+/// Words borrowed from the input
+///
+/// For use with `#[serde(borrow, with = "l2r0_small_serde::words::borrowed")]`
+/// on `&[u32]` fields. The encoding is the same as that of a `Vec<u32>`.
+/// Decoding only succeeds with a [Deserializer](crate::Deserializer) over
+/// borrowed words, as created by [from_words](crate::from_words).
+/// ```rust
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct Proof<'a> {
+///     #[serde(borrow, with = "l2r0_small_serde::words::borrowed")]
+///     seal: &'a [u32],
+/// }
+///
+/// let data = l2r0_small_serde::to_vec_compact(&Proof { seal: &[1, 2, 3] }).unwrap();
+/// let proof: Proof = l2r0_small_serde::from_words(&data).unwrap();
+/// assert_eq!([1, 2, 3].as_slice(), proof.seal);
+/// ```
+pub mod borrowed {
+    use core::fmt;
+
+    use serde::{de::Visitor, Deserializer, Serializer};
+
+    /// Serialize `words` like a `Vec<u32>`
+    pub fn serialize<S: Serializer>(words: &[u32], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(words)
+    }
+
+    /// Deserialize a `&[u32]` pointing into the input
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<&'de [u32], D::Error> {
+        deserializer.deserialize_newtype_struct(super::BORROWED, BorrowedVisitor)
+    }
+
+    struct BorrowedVisitor;
+
+    impl<'de> Visitor<'de> for BorrowedVisitor {
+        type Value = &'de [u32];
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("borrowed words")
+        }
+
+        fn visit_borrowed_bytes<E: serde::de::Error>(self, v: &'de [u8]) -> Result<Self::Value, E> {
+            bytemuck::try_cast_slice(v).map_err(|_| E::custom("words: unaligned borrowed bytes"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{vec, vec::Vec};
+
+    use serde::{Deserialize, Serialize};
+
+    use crate::{from_slice_compact, from_slice_compact_with_config, to_vec_compact, Config};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Witness {
+        #[serde(with = "crate::words")]
+        small: Vec<u32>,
+        flag: u8,
+        #[serde(with = "crate::words")]
+        large: Vec<i64>,
+        #[serde(with = "crate::words")]
+        floats: Vec<[f32; 3]>,
+        #[serde(with = "crate::words")]
+        bytes: Vec<u8>,
+    }
+
+    fn witness() -> Witness {
+        Witness {
+            small: (0..100).collect(),
+            flag: 1,
+            large: vec![-1, 1 << 40],
+            floats: vec![[0.5, -2.0, 3.25]],
+            bytes: vec![1, 2, 3],
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let input = witness();
+        let data = to_vec_compact(&input).unwrap();
+        let plain = (
+            input.small.clone(),
+            input.flag,
+            input.large.clone(),
+            input.floats.clone(),
+            input.bytes.clone(),
+        );
+        assert_eq!(to_vec_compact(&plain).unwrap(), data);
+        let output: Witness = from_slice_compact(&data).unwrap();
+        assert_eq!(input, output);
+    }
+
+    #[test]
+    fn test_self_describing() {
+        let config = Config::new().self_describing(true);
+        let input = witness();
+        let data = crate::to_vec_compact_with_config(&input, config).unwrap();
+        let output: Witness = from_slice_compact_with_config(&data, config).unwrap();
+        assert_eq!(input, output);
+    }
+
+    #[test]
+    fn test_borrowed() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Proof<'a> {
+            flag: u8,
+            #[serde(borrow, with = "crate::words::borrowed")]
+            seal: &'a [u32],
+            journal: u8,
+        }
+
+        let seal: Vec<u32> = (0..64).collect();
+        let input = Proof {
+            flag: 1,
+            seal: &seal,
+            journal: 2,
+        };
+        let data = to_vec_compact(&input).unwrap();
+        assert_eq!(to_vec_compact(&(1u8, &seal, 2u8)).unwrap(), data);
+        let output: Proof = crate::from_words(&data).unwrap();
+        assert_eq!(input, output);
+        assert!(data.as_ptr_range().contains(&output.seal.as_ptr()));
+
+        // Words can't be borrowed from a generic reader.
+        let mut deserializer = crate::Deserializer::new(data.as_slice());
+        assert_eq!(
+            Err(crate::Error::NotSupported),
+            Proof::deserialize(&mut deserializer)
+        );
+        assert_eq!(
+            Err(crate::Error::DeserializeUnexpectedEnd {
+                needed: 64,
+                available: Some(8)
+            }),
+            crate::from_words::<Proof>(&data[..10])
+        );
+    }
+
+    #[test]
+    fn test_truncated() {
+        #[derive(Debug, Deserialize)]
+        struct Dwords(#[serde(with = "crate::words")] Vec<u64>);
+
+        let data = to_vec_compact(&vec![1u64, 2]).unwrap();
+        assert_eq!(
+            Err(crate::Error::DeserializeLengthOverflow { requested: 2 }),
+            from_slice_compact::<Dwords, _>(&data[..data.len() - 1]).map(|dwords| dwords.0)
+        );
+    }
+}